@@ -1,8 +1,14 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::{descriptors::records::WriteDescriptor, fields::MessageFields, Message};
+use crate::{
+    descriptors::{records::WriteDescriptor, MessageDescriptor},
+    fields::MessageFields,
+    permissions::PermissionGrant,
+    Message,
+};
 
-use super::jws::JWS;
+use super::jws::{JwsError, KeyResolver, Payload, JWS};
 
 #[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
 pub struct Authorization {
@@ -21,6 +27,192 @@ pub struct Authorization {
     pub owner_delegated_grant: Option<Box<Message<WriteDescriptor>>>,
 }
 
+impl Authorization {
+    /// signer returns the DID that signed the message, or `None` if the message is unsigned.
+    pub fn signer(&self) -> Result<Option<String>, JwsError> {
+        match self.signature.signatures {
+            Some(ref signatures) if !signatures.is_empty() => Ok(Some(self.signature.signer()?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// author returns the DID of the author of the message. If the message was signed with a
+    /// delegated grant, the author is the grantor of the delegation, rather than the signer.
+    pub fn author(&self) -> Result<Option<String>, JwsError> {
+        match self.author_delegated_grant {
+            Some(ref grant) => grant.fields.authorization.signer(),
+            None => self.signer(),
+        }
+    }
+
+    /// owner returns the DID of the owner of the message, or `None` if the message has not been
+    /// signed by an owner. If the owner signed with a delegated grant, the owner is the grantor
+    /// of the delegation, rather than the signer.
+    pub fn owner(&self) -> Result<Option<String>, JwsError> {
+        match (&self.owner_delegated_grant, &self.owner_signature) {
+            (Some(grant), _) => grant.fields.authorization.signer(),
+            (None, Some(signature)) => Ok(Some(signature.signer()?)),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// payload returns the decoded signature payload of the message, or `None` if the message
+    /// is unsigned. The signature is not verified.
+    pub fn payload(&self) -> Result<Option<Payload>, JwsError> {
+        match self.signature.signatures {
+            Some(ref signatures) if !signatures.is_empty() => {
+                Ok(Some(self.signature.decode_payload()?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// protocol_role returns the protocol role invoked by the author of the message, if any.
+    pub fn protocol_role(&self) -> Result<Option<String>, JwsError> {
+        Ok(self.payload()?.and_then(|payload| payload.protocol_role))
+    }
+
+    /// verify verifies the signature of the message, and that it signs the given descriptor.
+    /// If the message was signed with a delegated grant, the signature of the grant is verified
+    /// too. The author of the message is returned, along with the signature payload.
+    pub async fn verify<D, R>(
+        &self,
+        descriptor: &D,
+        resolver: &R,
+    ) -> Result<(String, Payload), JwsError>
+    where
+        D: MessageDescriptor,
+        R: KeyResolver,
+    {
+        verify_signature(
+            &self.signature,
+            self.author_delegated_grant.as_deref(),
+            descriptor,
+            resolver,
+        )
+        .await
+    }
+
+    /// verify_owner verifies the owner signature of the message (if any), and that it signs the
+    /// given descriptor. If the owner signed with a delegated grant, the signature of the grant is
+    /// verified too. The owner of the message is returned, along with the signature payload.
+    pub async fn verify_owner<D, R>(
+        &self,
+        descriptor: &D,
+        resolver: &R,
+    ) -> Result<Option<(String, Payload)>, JwsError>
+    where
+        D: MessageDescriptor,
+        R: KeyResolver,
+    {
+        match (&self.owner_signature, &self.owner_delegated_grant) {
+            (Some(signature), grant) => {
+                verify_signature(signature, grant.as_deref(), descriptor, resolver)
+                    .await
+                    .map(Some)
+            }
+            (None, Some(_)) => Err(JwsError::MissingSignature),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// verify_signature verifies a signature of a message, and that it signs the given descriptor.
+/// Signatures made with a delegated grant must name the grant, which is verified too, and must be
+/// made by the grantee while the grant is active. The grantor is then returned as the signer.
+async fn verify_signature<D, R>(
+    signature: &JWS,
+    delegated_grant: Option<&Message<WriteDescriptor>>,
+    descriptor: &D,
+    resolver: &R,
+) -> Result<(String, Payload), JwsError>
+where
+    D: MessageDescriptor,
+    R: KeyResolver,
+{
+    let (signer, payload) = signature.verify::<Payload, R>(resolver).await?;
+    check_descriptor_cid(descriptor, &payload.descriptor_cid)?;
+
+    let Some(grant) = delegated_grant else {
+        return Ok((signer, payload));
+    };
+
+    let grant_cid = grant
+        .stored_cid()
+        .map_err(|err| JwsError::EncodeError(err.to_string()))?;
+    if payload.delegated_grant_id != Some(grant_cid) {
+        return Err(JwsError::DelegatedGrantMismatch);
+    }
+
+    let (grantor, grant_payload) = grant
+        .fields
+        .authorization
+        .signature
+        .verify::<Payload, R>(resolver)
+        .await?;
+    check_descriptor_cid(&grant.descriptor, &grant_payload.descriptor_cid)?;
+
+    let timestamp = descriptor.message_timestamp().ok_or_else(|| {
+        JwsError::InvalidDelegatedGrant("the message has no timestamp".to_string())
+    })?;
+    check_delegated_grant(grant, &grantor, &signer, timestamp)?;
+
+    Ok((grantor, payload))
+}
+
+/// check_delegated_grant checks that a message is a delegated grant from the grantor to the
+/// signer, which is active at the given timestamp.
+fn check_delegated_grant(
+    grant: &Message<WriteDescriptor>,
+    grantor: &str,
+    signer: &str,
+    timestamp: &DateTime<Utc>,
+) -> Result<(), JwsError> {
+    let grant = PermissionGrant::parse(grant)
+        .map_err(|err| JwsError::InvalidDelegatedGrant(err.to_string()))?;
+
+    let invalid = |reason: &str| {
+        Err(JwsError::InvalidDelegatedGrant(format!(
+            "permission grant {} {}",
+            grant.id, reason
+        )))
+    };
+    if !grant.delegated {
+        return invalid("is not a delegated grant");
+    }
+    if grant.grantor != grantor {
+        return invalid("was not signed by its grantor");
+    }
+    if grant.grantee != signer {
+        return invalid("was not granted to the signer");
+    }
+    if *timestamp < grant.date_granted {
+        return invalid("is not yet active");
+    }
+    if *timestamp >= grant.date_expires {
+        return invalid("has expired");
+    }
+
+    Ok(())
+}
+
+/// check_descriptor_cid checks that the `descriptorCid` of a signature payload is the CID of the
+/// descriptor.
+pub(crate) fn check_descriptor_cid<D: MessageDescriptor>(
+    descriptor: &D,
+    descriptor_cid: &cid::Cid,
+) -> Result<(), JwsError> {
+    let expected = descriptor.cid();
+    if &expected != descriptor_cid {
+        return Err(JwsError::DescriptorCidMismatch {
+            expected,
+            actual: *descriptor_cid,
+        });
+    }
+
+    Ok(())
+}
+
 impl MessageFields for Authorization {
     fn set_authorization(&mut self, authorization: Authorization) {
         self.signature = authorization.signature;
@@ -29,3 +221,38 @@ impl MessageFields for Authorization {
         self.owner_delegated_grant = authorization.owner_delegated_grant;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use ssi_jwk::JWK;
+
+    use super::*;
+    use crate::descriptors::protocols::{QueryDescriptor, QueryParameters};
+
+    #[tokio::test]
+    async fn test_authorization_verify() {
+        let mut jwk = JWK::generate_ed25519().unwrap();
+        jwk.key_id = Some("did:example:alice#key1".to_string());
+
+        let message =
+            Message::<QueryDescriptor>::create(QueryParameters::default(), Some(jwk.clone()))
+                .await
+                .unwrap();
+
+        let (author, payload) = message
+            .fields
+            .verify(&message.descriptor, &jwk)
+            .await
+            .unwrap();
+        assert_eq!(author, "did:example:alice");
+        assert_eq!(payload.descriptor_cid, message.descriptor.cid());
+        assert!(payload.delegated_grant_id.is_none());
+
+        let mut other = message.descriptor.clone();
+        other.message_timestamp += chrono::Duration::seconds(1);
+        assert!(matches!(
+            message.fields.verify(&other, &jwk).await,
+            Err(JwsError::DescriptorCidMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,615 @@
+use chrono::{DateTime, Utc};
+
+use crate::{
+    descriptors::{
+        MessageDescriptor, Messages, Protocols, Records, RecordsWriteDescriptor, RECORDS, WRITE,
+    },
+    errors::GrantAuthorizationError,
+    filters::{Filter, FilterKey, Filters, ValueFilter},
+    indexes::{
+        INTERFACE, IS_LATEST_BASE_STATE, METHOD, PARENT_ID, PROTOCOL, PROTOCOL_PATH, RECORD_ID,
+    },
+    permissions::{
+        PermissionGrant, PublicationCondition, GRANT_PATH, PERMISSIONS_PROTOCOL, REVOCATION_PATH,
+    },
+    stores::MessageStore,
+    Descriptor, Message, Value,
+};
+
+use super::{jws::Payload, Authorization};
+
+/// GrantAuthorizer authorizes messages which invoke a permission grant, either by naming the
+/// `permissionGrantId` of a grant written by the tenant, or by embedding a delegated grant which
+/// allows the signer to sign messages on behalf of the grantor. Grants must be active as of the
+/// `messageTimestamp` of the message, and their scope and conditions must cover the message.
+pub struct GrantAuthorizer<'a, MS: MessageStore> {
+    message_store: &'a MS,
+    tenant: &'a str,
+    target: Option<&'a Descriptor>,
+}
+
+impl<'a, MS: MessageStore> GrantAuthorizer<'a, MS> {
+    pub fn new(message_store: &'a MS, tenant: &'a str) -> Self {
+        Self {
+            message_store,
+            tenant,
+            target: None,
+        }
+    }
+
+    /// with_target sets the message read by a MessagesRead, which the protocol of the grants
+    /// is checked against. Records messages are checked against the record they act upon, which
+    /// is given to [`GrantAuthorizer::authorize_message`] instead.
+    pub fn with_target(mut self, target: &'a Descriptor) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// authorize_message authorizes the grants invoked by a message. `record` is the record
+    /// acted upon by a Records interface message, which the scope of the grants is checked
+    /// against. Returns `true` if the message is authorized by a permission grant of the tenant,
+    /// and `false` if it names no permission grant. Delegated grants of the author or owner only
+    /// allow the signer to act as the grantor, so the grantor must still be authorized by the
+    /// caller.
+    pub async fn authorize_message(
+        &self,
+        authorization: &Authorization,
+        descriptor: &Descriptor,
+        record: Option<&Message<RecordsWriteDescriptor>>,
+    ) -> Result<bool, GrantAuthorizationError> {
+        let (Some(payload), Some(signer)) = (authorization.payload()?, authorization.signer()?)
+        else {
+            return Ok(false);
+        };
+        let author = authorization.author()?.unwrap_or_else(|| signer.clone());
+
+        if let Some(ref delegated) = authorization.author_delegated_grant {
+            let grant = delegated_grant(delegated, payload.delegated_grant_id.as_ref())?;
+            self.authorize(&grant, &author, &signer, descriptor, record)
+                .await?;
+        }
+
+        if let (Some(grant), Some(signature)) = (
+            &authorization.owner_delegated_grant,
+            &authorization.owner_signature,
+        ) {
+            let owner_payload: Payload = signature.decode_payload()?;
+            let grant = delegated_grant(grant, owner_payload.delegated_grant_id.as_ref())?;
+            self.authorize(
+                &grant,
+                &grant.grantor,
+                &signature.signer()?,
+                descriptor,
+                record,
+            )
+            .await?;
+        }
+
+        match payload.permission_grant_id {
+            Some(grant_id) => {
+                let grant = self.fetch_grant(&grant_id).await?;
+                self.authorize(&grant, self.tenant, &author, descriptor, record)
+                    .await?;
+
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// authorize checks that the grant was given by the grantor to the grantee, and that it
+    /// authorizes the message.
+    pub async fn authorize(
+        &self,
+        grant: &PermissionGrant,
+        grantor: &str,
+        grantee: &str,
+        descriptor: &Descriptor,
+        record: Option<&Message<RecordsWriteDescriptor>>,
+    ) -> Result<(), GrantAuthorizationError> {
+        if grant.grantor != grantor {
+            return Err(GrantAuthorizationError::Unauthorized(format!(
+                "permission grant {} was not given by {}",
+                grant.id, grantor
+            )));
+        }
+        if grant.grantee != grantee {
+            return Err(GrantAuthorizationError::Unauthorized(format!(
+                "permission grant {} was not given to {}",
+                grant.id, grantee
+            )));
+        }
+
+        let timestamp = descriptor.message_timestamp();
+        verify_active(grant, timestamp)?;
+        if let Some(revoked) = self.revocation_timestamp(&grant.id).await? {
+            if revoked <= *timestamp {
+                return Err(GrantAuthorizationError::Unauthorized(format!(
+                    "permission grant {} has been revoked",
+                    grant.id
+                )));
+            }
+        }
+
+        verify_scope(grant, descriptor, record, self.target)?;
+        verify_conditions(grant, descriptor)
+    }
+
+    /// fetch_grant returns the permission grant with the given record ID, written by the tenant.
+    pub async fn fetch_grant(
+        &self,
+        grant_id: &str,
+    ) -> Result<PermissionGrant, GrantAuthorizationError> {
+        let filter = ValueFilter::<FilterKey>::from([
+            index(INTERFACE, RECORDS),
+            index(METHOD, WRITE),
+            index(PROTOCOL, PERMISSIONS_PROTOCOL),
+            index(PROTOCOL_PATH, GRANT_PATH),
+            index(RECORD_ID, grant_id),
+            (
+                FilterKey::Index(IS_LATEST_BASE_STATE.to_string()),
+                Filter::Equal(Value::Bool(true)),
+            ),
+        ]);
+
+        let mut grants = self
+            .message_store
+            .query::<Descriptor>(self.tenant, Filters::from(filter), None, None)
+            .await?
+            .items;
+        if grants.is_empty() {
+            return Err(GrantAuthorizationError::Unauthorized(format!(
+                "unable to find permission grant {}",
+                grant_id
+            )));
+        }
+
+        let grant: Message<RecordsWriteDescriptor> = grants
+            .remove(0)
+            .try_into_message()
+            .map_err(|err| GrantAuthorizationError::InvalidMessage(err.to_string()))?;
+
+        Ok(PermissionGrant::parse(&grant)?)
+    }
+
+    /// revocation_timestamp returns when the grant was first revoked, if it has been.
+    async fn revocation_timestamp(
+        &self,
+        grant_id: &str,
+    ) -> Result<Option<DateTime<Utc>>, GrantAuthorizationError> {
+        let filter = ValueFilter::<FilterKey>::from([
+            index(INTERFACE, RECORDS),
+            index(METHOD, WRITE),
+            index(PROTOCOL, PERMISSIONS_PROTOCOL),
+            index(PROTOCOL_PATH, REVOCATION_PATH),
+            index(PARENT_ID, grant_id),
+        ]);
+
+        let revocations = self
+            .message_store
+            .query::<Descriptor>(self.tenant, Filters::from(filter), None, None)
+            .await?;
+
+        Ok(revocations
+            .items
+            .iter()
+            .map(|revocation| *revocation.descriptor.message_timestamp())
+            .min())
+    }
+}
+
+/// delegated_grant parses the delegated grant embedded in a message, checking that it is the
+/// grant the signature of the message names.
+fn delegated_grant(
+    message: &Message<RecordsWriteDescriptor>,
+    delegated_grant_id: Option<&cid::Cid>,
+) -> Result<PermissionGrant, GrantAuthorizationError> {
+    let grant = PermissionGrant::parse(message)?;
+    if !grant.delegated {
+        return Err(GrantAuthorizationError::Unauthorized(format!(
+            "permission grant {} is not a delegated grant",
+            grant.id
+        )));
+    }
+
+    let cid = message
+        .stored_cid()
+        .map_err(|err| GrantAuthorizationError::InvalidMessage(err.to_string()))?;
+    if delegated_grant_id != Some(&cid) {
+        return Err(GrantAuthorizationError::InvalidMessage(
+            "delegatedGrantId does not match the delegated grant".to_string(),
+        ));
+    }
+
+    Ok(grant)
+}
+
+/// verify_active checks that the grant is active at the timestamp.
+fn verify_active(
+    grant: &PermissionGrant,
+    timestamp: &DateTime<Utc>,
+) -> Result<(), GrantAuthorizationError> {
+    if *timestamp < grant.date_granted {
+        return Err(GrantAuthorizationError::Unauthorized(format!(
+            "permission grant {} is not yet active",
+            grant.id
+        )));
+    }
+    if *timestamp >= grant.date_expires {
+        return Err(GrantAuthorizationError::Unauthorized(format!(
+            "permission grant {} has expired",
+            grant.id
+        )));
+    }
+
+    Ok(())
+}
+
+/// verify_scope checks that the scope of the grant covers the interface method of the message.
+/// Grants scoped to a protocol (and a context or protocol path of it) only cover the messages of
+/// records in that protocol, context or protocol path.
+fn verify_scope(
+    grant: &PermissionGrant,
+    descriptor: &Descriptor,
+    record: Option<&Message<RecordsWriteDescriptor>>,
+    target: Option<&Descriptor>,
+) -> Result<(), GrantAuthorizationError> {
+    let scope = &grant.scope;
+    let out_of_scope = |what: &str| -> Result<(), GrantAuthorizationError> {
+        Err(GrantAuthorizationError::Unauthorized(format!(
+            "{} is not within the scope of permission grant {}",
+            what, grant.id
+        )))
+    };
+
+    if scope.interface != descriptor.interface() || scope.method != descriptor.method() {
+        return out_of_scope(&format!(
+            "{}{}",
+            descriptor.interface(),
+            descriptor.method()
+        ));
+    }
+
+    let (protocol, context_id, protocol_path) = message_scope(descriptor, record, target);
+    if scope.protocol.is_some() && scope.protocol.as_deref() != protocol {
+        return out_of_scope(&format!("protocol {:?}", protocol));
+    }
+    if let Some(ref scope_context_id) = scope.context_id {
+        let within = context_id.is_some_and(|context_id| {
+            context_id == scope_context_id
+                || context_id.starts_with(&format!("{}/", scope_context_id))
+        });
+        if !within {
+            return out_of_scope(&format!("context {:?}", context_id));
+        }
+    }
+    if scope.protocol_path.is_some() && scope.protocol_path.as_deref() != protocol_path {
+        return out_of_scope(&format!("protocolPath {:?}", protocol_path));
+    }
+
+    Ok(())
+}
+
+/// verify_conditions checks that a RecordsWrite meets the publication condition of the grant.
+fn verify_conditions(
+    grant: &PermissionGrant,
+    descriptor: &Descriptor,
+) -> Result<(), GrantAuthorizationError> {
+    let Descriptor::Records(Records::Write(write)) = descriptor else {
+        return Ok(());
+    };
+    let publication = grant
+        .conditions
+        .as_ref()
+        .and_then(|conditions| conditions.publication.as_ref());
+
+    match (publication, write.published.unwrap_or_default()) {
+        (Some(PublicationCondition::Required), false) => {
+            Err(GrantAuthorizationError::Unauthorized(format!(
+                "permission grant {} requires records to be published",
+                grant.id
+            )))
+        }
+        (Some(PublicationCondition::Prohibited), true) => {
+            Err(GrantAuthorizationError::Unauthorized(format!(
+                "permission grant {} prohibits records from being published",
+                grant.id
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// message_scope returns the protocol, context ID and protocol path a message acts within. These
+/// are taken from the record acted upon, if there is one, and otherwise from the message's
+/// filter. A MessagesRead acts within the protocol of the message it reads, and a MessagesQuery
+/// or MessagesSubscribe within the protocol of its filters, if all of them filter on the same
+/// protocol.
+fn message_scope<'d>(
+    descriptor: &'d Descriptor,
+    record: Option<&'d Message<RecordsWriteDescriptor>>,
+    target: Option<&'d Descriptor>,
+) -> (Option<&'d str>, Option<&'d str>, Option<&'d str>) {
+    if let Some(record) = record {
+        return (
+            record.descriptor.protocol.as_deref(),
+            record.fields.context_id.as_deref(),
+            record.descriptor.protocol_path.as_deref(),
+        );
+    }
+
+    let filter = match descriptor {
+        Descriptor::Records(Records::Read(read)) => &read.filter,
+        Descriptor::Records(Records::Query(query)) => &query.filter,
+        Descriptor::Records(Records::Subscribe(subscribe)) => &subscribe.filter,
+        Descriptor::Protocols(Protocols::Configure(configure)) => {
+            return (Some(configure.definition.protocol.as_str()), None, None)
+        }
+        Descriptor::Protocols(Protocols::Query(query)) => {
+            let protocol = query.filter.as_ref().and_then(|f| f.protocol.as_deref());
+            return (protocol, None, None);
+        }
+        Descriptor::Messages(Messages::Read(_)) => {
+            return match target {
+                Some(Descriptor::Protocols(Protocols::Configure(configure))) => {
+                    (Some(configure.definition.protocol.as_str()), None, None)
+                }
+                _ => (None, None, None),
+            }
+        }
+        Descriptor::Messages(Messages::Query(query)) => {
+            return (filters_protocol(&query.filters), None, None)
+        }
+        Descriptor::Messages(Messages::Subscribe(subscribe)) => {
+            return (filters_protocol(&subscribe.filters), None, None)
+        }
+        _ => return (None, None, None),
+    };
+
+    (
+        filter.protocol.as_deref(),
+        filter.context_id.as_deref(),
+        filter.protocol_path.as_deref(),
+    )
+}
+
+/// filters_protocol returns the protocol of the Messages filters, if every filter set is for the
+/// same protocol. Filters without a protocol match the messages of every protocol.
+fn filters_protocol(filters: &[Filters]) -> Option<&str> {
+    let mut protocols = filters.iter().flat_map(|filters| &filters.set).map(|set| {
+        match set.get(&FilterKey::Index(PROTOCOL.to_string())) {
+            Some(Filter::Equal(Value::String(protocol))) => Some(protocol.as_str()),
+            _ => None,
+        }
+    });
+
+    let protocol = protocols.next()??;
+    protocols
+        .all(|other| other == Some(protocol))
+        .then_some(protocol)
+}
+
+fn index(key: &str, value: &str) -> (FilterKey, Filter<Value>) {
+    (
+        FilterKey::Index(key.to_string()),
+        Filter::Equal(Value::String(value.to_string())),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::{
+        descriptors::{
+            ConfigureDescriptor, DeleteDescriptor, MessagesQueryDescriptor, MessagesReadDescriptor,
+            RecordsQueryDescriptor, DELETE, MESSAGES, QUERY, READ,
+        },
+        fields::WriteFields,
+        filters::message_filters::{Messages as MessagesFilter, Records as RecordsFilter},
+        permissions::{Conditions, Scope},
+        protocols::Definition,
+    };
+
+    fn grant(scope: Scope) -> PermissionGrant {
+        let now = Utc::now();
+
+        PermissionGrant {
+            id: "grant".to_string(),
+            grantor: "did:example:alice".to_string(),
+            grantee: "did:example:bob".to_string(),
+            date_granted: now - Duration::hours(1),
+            date_expires: now + Duration::hours(1),
+            request_id: None,
+            description: None,
+            delegated: false,
+            scope,
+            conditions: None,
+        }
+    }
+
+    fn records_scope(method: &str) -> Scope {
+        Scope {
+            interface: RECORDS.to_string(),
+            method: method.to_string(),
+            protocol: Some("https://example.com/chat".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn record(context_id: &str, published: bool) -> Message<RecordsWriteDescriptor> {
+        Message {
+            descriptor: RecordsWriteDescriptor {
+                protocol: Some("https://example.com/chat".to_string()),
+                protocol_path: Some("thread/chat".to_string()),
+                published: Some(published),
+                message_timestamp: Utc::now(),
+                ..Default::default()
+            },
+            fields: WriteFields {
+                context_id: Some(context_id.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_verify_active() {
+        let grant = grant(records_scope(WRITE));
+
+        assert!(verify_active(&grant, &Utc::now()).is_ok());
+        assert!(verify_active(&grant, &(grant.date_granted - Duration::seconds(1))).is_err());
+        assert!(verify_active(&grant, &grant.date_expires).is_err());
+    }
+
+    #[test]
+    fn test_verify_scope() {
+        let write = record("thread/chat", false);
+        let descriptor = Descriptor::Records(Records::Write(write.descriptor.clone()));
+
+        assert!(verify_scope(
+            &grant(records_scope(WRITE)),
+            &descriptor,
+            Some(&write),
+            None
+        )
+        .is_ok());
+        assert!(verify_scope(
+            &grant(records_scope(DELETE)),
+            &descriptor,
+            Some(&write),
+            None
+        )
+        .is_err());
+
+        let other_protocol = Scope {
+            protocol: Some("https://example.com/other".to_string()),
+            ..records_scope(WRITE)
+        };
+        assert!(verify_scope(&grant(other_protocol), &descriptor, Some(&write), None).is_err());
+
+        let context = Scope {
+            context_id: Some("thread".to_string()),
+            ..records_scope(WRITE)
+        };
+        assert!(verify_scope(&grant(context.clone()), &descriptor, Some(&write), None).is_ok());
+        let other_context = record("threads/chat", false);
+        assert!(verify_scope(&grant(context), &descriptor, Some(&other_context), None).is_err());
+
+        let protocol_path = Scope {
+            protocol_path: Some("thread".to_string()),
+            ..records_scope(WRITE)
+        };
+        assert!(verify_scope(&grant(protocol_path), &descriptor, Some(&write), None).is_err());
+
+        // without a record, the scope is checked against the filter of the message.
+        let query = Descriptor::Records(Records::Query(RecordsQueryDescriptor {
+            message_timestamp: Utc::now(),
+            filter: RecordsFilter {
+                protocol: Some("https://example.com/chat".to_string()),
+                ..Default::default()
+            },
+            pagination: None,
+            date_sort: None,
+        }));
+        assert!(verify_scope(&grant(records_scope(QUERY)), &query, None, None).is_ok());
+
+        let delete = Descriptor::Records(Records::Delete(DeleteDescriptor {
+            message_timestamp: Utc::now(),
+            record_id: "record".to_string(),
+            prune: false,
+        }));
+        assert!(verify_scope(&grant(records_scope(DELETE)), &delete, None, None).is_err());
+    }
+
+    #[test]
+    fn test_verify_messages_scope() {
+        let scope = |method: &str| Scope {
+            interface: MESSAGES.to_string(),
+            method: method.to_string(),
+            protocol: Some("https://example.com/chat".to_string()),
+            ..Default::default()
+        };
+        let query = |protocols: &[Option<&str>]| {
+            Descriptor::Messages(Messages::Query(MessagesQueryDescriptor {
+                message_timestamp: Utc::now(),
+                filters: protocols
+                    .iter()
+                    .map(|protocol| {
+                        Filters::from(ValueFilter::<FilterKey>::from(MessagesFilter {
+                            interface: None,
+                            method: None,
+                            protocol: protocol.map(String::from),
+                            message_timestamp: None,
+                        }))
+                    })
+                    .collect(),
+                cursor: None,
+            }))
+        };
+
+        // a protocol-scoped MessagesQuery grant only covers queries which filter every message
+        // by the protocol.
+        let query_grant = grant(scope(QUERY));
+        let chat = Some("https://example.com/chat");
+        assert!(verify_scope(&query_grant, &query(&[chat]), None, None).is_ok());
+        assert!(verify_scope(&query_grant, &query(&[chat, chat]), None, None).is_ok());
+        assert!(verify_scope(&query_grant, &query(&[]), None, None).is_err());
+        assert!(verify_scope(&query_grant, &query(&[None]), None, None).is_err());
+        assert!(verify_scope(&query_grant, &query(&[chat, None]), None, None).is_err());
+        assert!(verify_scope(
+            &query_grant,
+            &query(&[Some("https://example.com/other")]),
+            None,
+            None
+        )
+        .is_err());
+
+        // a MessagesRead is within the protocol of the message it reads.
+        let read_grant = grant(scope(READ));
+        let read = Descriptor::Messages(Messages::Read(MessagesReadDescriptor {
+            message_timestamp: Utc::now(),
+            message_cid: None,
+        }));
+        let write = record("thread", false);
+        assert!(verify_scope(&read_grant, &read, Some(&write), None).is_ok());
+
+        let configure = |protocol: &str| {
+            Descriptor::Protocols(Protocols::Configure(ConfigureDescriptor {
+                message_timestamp: Utc::now(),
+                definition: Definition {
+                    protocol: protocol.to_string(),
+                    ..Default::default()
+                },
+            }))
+        };
+        let chat_configure = configure("https://example.com/chat");
+        let other_configure = configure("https://example.com/other");
+        assert!(verify_scope(&read_grant, &read, None, Some(&chat_configure)).is_ok());
+        assert!(verify_scope(&read_grant, &read, None, Some(&other_configure)).is_err());
+        assert!(verify_scope(&read_grant, &read, None, None).is_err());
+    }
+
+    #[test]
+    fn test_verify_conditions() {
+        let mut grant = grant(records_scope(WRITE));
+        let published = Descriptor::Records(Records::Write(record("thread", true).descriptor));
+        let unpublished = Descriptor::Records(Records::Write(record("thread", false).descriptor));
+
+        assert!(verify_conditions(&grant, &published).is_ok());
+        assert!(verify_conditions(&grant, &unpublished).is_ok());
+
+        grant.conditions = Some(Conditions {
+            publication: Some(PublicationCondition::Required),
+        });
+        assert!(verify_conditions(&grant, &published).is_ok());
+        assert!(verify_conditions(&grant, &unpublished).is_err());
+
+        grant.conditions = Some(Conditions {
+            publication: Some(PublicationCondition::Prohibited),
+        });
+        assert!(verify_conditions(&grant, &published).is_err());
+        assert!(verify_conditions(&grant, &unpublished).is_ok());
+    }
+}
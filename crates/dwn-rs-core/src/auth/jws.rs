@@ -1,8 +1,11 @@
 use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD as base64url};
 use cid::Cid;
+use std::future::Future;
+
 use futures_util::{stream, StreamExt, TryStreamExt};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use ssi_claims_core::SignatureError;
+use ssi_jwk::{Algorithm, JWK};
 use ssi_jws::{JwsPayload, JwsSigner};
 use thiserror::Error;
 
@@ -14,6 +17,45 @@ pub enum JwsError {
     ParseError(#[from] serde_json::Error),
     #[error("Error signing JWS: {0}")]
     SignError(#[from] ssi_claims_core::SignatureError),
+    #[error("Error decoding JWS: {0}")]
+    DecodeError(#[from] base64::DecodeError),
+    #[error("JWS has no signatures")]
+    MissingSignature,
+    #[error("JWS signature has no protected header")]
+    MissingProtectedHeader,
+    #[error("JWS protected header has no key ID")]
+    MissingKeyId,
+    #[error("JWS has no payload")]
+    MissingPayload,
+    #[error("Unsupported JWS algorithm: {0}")]
+    UnsupportedAlgorithm(String),
+    #[error("Unable to resolve key {0}: {1}")]
+    KeyResolutionError(String, String),
+    #[error("Invalid JWS signature: {0}")]
+    InvalidSignature(#[from] ssi_jws::Error),
+    #[error("JWS payload descriptorCid {actual} does not match descriptor CID {expected}")]
+    DescriptorCidMismatch { expected: Cid, actual: Cid },
+    #[error("JWS payload delegatedGrantId does not match the delegated grant")]
+    DelegatedGrantMismatch,
+    #[error("Invalid delegated grant: {0}")]
+    InvalidDelegatedGrant(String),
+    #[error("Error encoding CID: {0}")]
+    EncodeError(String),
+}
+
+/// KeyResolver resolves the key ID (`kid`) of a JWS signature to the public key which is used to
+/// verify the signature.
+pub trait KeyResolver {
+    fn resolve_key(&self, kid: &str) -> impl Future<Output = Result<JWK, JwsError>> + Send;
+}
+
+/// In tests, a single JWK resolves any key ID to itself. This is never done outside of tests, as
+/// it would accept a signature by the key in place of whichever key the `kid` names.
+#[cfg(test)]
+impl KeyResolver for JWK {
+    async fn resolve_key(&self, _kid: &str) -> Result<JWK, JwsError> {
+        Ok(self.to_public())
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
@@ -28,7 +70,15 @@ pub struct JWS {
     pub extra: MapValue,
 }
 
-#[derive(Serialize)]
+/// ProtectedHeader is the decoded `protected` header of a JWS signature entry.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
+pub struct ProtectedHeader {
+    pub alg: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Payload {
     #[serde(rename = "descriptorCid")]
     pub descriptor_cid: Cid,
@@ -36,8 +86,13 @@ pub struct Payload {
     pub delegated_grant_id: Option<Cid>,
     #[serde(rename = "permissionGrantId", skip_serializing_if = "Option::is_none")]
     pub permission_grant_id: Option<String>,
-    #[serde(rename = "protocolRule", skip_serializing_if = "Option::is_none")]
-    pub protocol_rule: Option<String>,
+    // earlier versions of this crate named the role `protocolRule`, so it is still accepted.
+    #[serde(
+        rename = "protocolRole",
+        alias = "protocolRule",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub protocol_role: Option<String>,
 }
 
 impl JwsPayload for Payload {
@@ -47,7 +102,7 @@ impl JwsPayload for Payload {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct AttestationPayload {
     #[serde(rename = "descriptorCid")]
     pub descriptor_cid: Cid,
@@ -81,6 +136,51 @@ impl JWS {
         }
     }
 
+    /// signer returns the DID of the first signer of the JWS, taken from the `kid` of the
+    /// protected header. The signature itself is not verified.
+    pub fn signer(&self) -> Result<String, JwsError> {
+        let entry = self
+            .signatures
+            .as_ref()
+            .and_then(|signatures| signatures.first())
+            .ok_or(JwsError::MissingSignature)?;
+
+        let kid = entry
+            .protected_header()?
+            .kid
+            .ok_or(JwsError::MissingKeyId)?;
+
+        Ok(kid.split('#').next().unwrap_or_default().to_string())
+    }
+
+    /// verify verifies every signature of the JWS against the keys resolved for the `kid` of
+    /// each signature, returning the DID of the first signer and the decoded payload.
+    pub async fn verify<P, R>(&self, resolver: &R) -> Result<(String, P), JwsError>
+    where
+        P: DeserializeOwned,
+        R: KeyResolver,
+    {
+        let payload = self.payload.as_ref().ok_or(JwsError::MissingPayload)?;
+        let signatures = match self.signatures {
+            Some(ref signatures) if !signatures.is_empty() => signatures,
+            _ => return Err(JwsError::MissingSignature),
+        };
+
+        for entry in signatures {
+            entry.verify(payload, resolver).await?;
+        }
+
+        Ok((self.signer()?, self.decode_payload()?))
+    }
+
+    /// decode_payload decodes the base64url encoded payload of the JWS. The signatures of the
+    /// JWS are not verified.
+    pub fn decode_payload<P: DeserializeOwned>(&self) -> Result<P, JwsError> {
+        let payload = self.payload.as_ref().ok_or(JwsError::MissingPayload)?;
+
+        Ok(serde_json::from_slice(&base64url.decode(payload)?)?)
+    }
+
     async fn generate_signatures<S, P>(
         signers: Vec<S>,
         payload: P,
@@ -119,6 +219,50 @@ pub struct SignatureEntry {
     pub extra: MapValue,
 }
 
+impl SignatureEntry {
+    /// protected_header decodes the base64url encoded protected header of the signature.
+    pub fn protected_header(&self) -> Result<ProtectedHeader, JwsError> {
+        let protected = self
+            .protected
+            .as_ref()
+            .ok_or(JwsError::MissingProtectedHeader)?;
+
+        Ok(serde_json::from_slice(&base64url.decode(protected)?)?)
+    }
+
+    /// verify verifies the signature over the protected header and the given base64url encoded
+    /// payload, with the key resolved for the `kid` of the protected header.
+    async fn verify<R: KeyResolver>(&self, payload: &str, resolver: &R) -> Result<(), JwsError> {
+        let protected = self
+            .protected
+            .as_ref()
+            .ok_or(JwsError::MissingProtectedHeader)?;
+        let signature = self.signature.as_ref().ok_or(JwsError::MissingSignature)?;
+
+        let header = self.protected_header()?;
+        let kid = header.kid.ok_or(JwsError::MissingKeyId)?;
+        let algorithm = match serde_json::from_value::<Algorithm>(serde_json::Value::String(
+            header.alg.clone(),
+        )) {
+            // unsigned JWSs are never accepted.
+            Ok(Algorithm::None) | Err(_) => return Err(JwsError::UnsupportedAlgorithm(header.alg)),
+            Ok(algorithm) => algorithm,
+        };
+
+        let key = resolver.resolve_key(&kid).await?;
+        let signing_input = format!("{}.{}", protected, payload);
+
+        ssi_jws::verify_bytes(
+            algorithm,
+            signing_input.as_bytes(),
+            &key,
+            &base64url.decode(signature)?,
+        )?;
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 pub struct NoSigner {}
 
@@ -167,4 +311,97 @@ mod tests {
             .unwrap()
             .is_empty());
     }
+
+    #[tokio::test]
+    async fn test_jws_signer() {
+        let mut jwk = JWK::generate_secp256k1();
+        jwk.key_id = Some("did:example:alice#key1".to_string());
+
+        let jws = JWS::create(b"hello world".to_vec(), Some(vec![jwk]))
+            .await
+            .expect("could not create JWS");
+
+        let header = jws.signatures.as_ref().unwrap()[0]
+            .protected_header()
+            .expect("could not decode protected header");
+        assert_eq!(header.alg, "ES256K");
+        assert_eq!(header.kid, Some("did:example:alice#key1".to_string()));
+
+        assert_eq!(jws.signer().unwrap(), "did:example:alice");
+
+        let jws = JWS::create(
+            b"hello world".to_vec(),
+            Some(vec![JWK::generate_secp256k1()]),
+        )
+        .await
+        .expect("could not create JWS");
+        assert!(matches!(jws.signer(), Err(JwsError::MissingKeyId)));
+
+        assert!(matches!(
+            JWS::default().signer(),
+            Err(JwsError::MissingSignature)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_jws_verify() {
+        let mut jwk = JWK::generate_secp256k1();
+        jwk.key_id = Some("did:example:alice#key1".to_string());
+
+        let descriptor_cid = Cid::new_v1(0x71, cid::multihash::Multihash::default());
+        let jws = JWS::create(
+            AttestationPayload { descriptor_cid },
+            Some(vec![jwk.clone()]),
+        )
+        .await
+        .expect("could not create JWS");
+
+        let (signer, payload) = jws
+            .verify::<AttestationPayload, _>(&jwk)
+            .await
+            .expect("could not verify JWS");
+        assert_eq!(signer, "did:example:alice");
+        assert_eq!(payload.descriptor_cid, descriptor_cid);
+
+        let other = JWK::generate_secp256k1();
+        assert!(matches!(
+            jws.verify::<AttestationPayload, _>(&other).await,
+            Err(JwsError::InvalidSignature(_))
+        ));
+
+        let mut forged = jws.clone();
+        forged.payload = Some(base64url.encode(b"{}"));
+        assert!(matches!(
+            forged.verify::<AttestationPayload, _>(&jwk).await,
+            Err(JwsError::InvalidSignature(_))
+        ));
+
+        assert!(matches!(
+            JWS::default().verify::<AttestationPayload, _>(&jwk).await,
+            Err(JwsError::MissingPayload)
+        ));
+    }
+
+    #[test]
+    fn test_payload_protocol_role() {
+        let descriptor_cid = Cid::new_v1(0x71, cid::multihash::Multihash::default());
+
+        // the payload of a message invoking a role, as signed by dwn-sdk-js.
+        let json = serde_json::json!({
+            "descriptorCid": descriptor_cid,
+            "permissionGrantId": "bafyreigrant",
+            "protocolRole": "friend",
+        });
+        let payload: Payload = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(payload.protocol_role.as_deref(), Some("friend"));
+        assert_eq!(payload.permission_grant_id.as_deref(), Some("bafyreigrant"));
+        assert_eq!(serde_json::to_value(&payload).unwrap(), json);
+
+        let json = serde_json::json!({
+            "descriptorCid": descriptor_cid,
+            "protocolRule": "friend",
+        });
+        let payload: Payload = serde_json::from_value(json).unwrap();
+        assert_eq!(payload.protocol_role.as_deref(), Some("friend"));
+    }
 }
@@ -1,5 +1,9 @@
 pub mod authorization;
+pub mod grant;
 pub mod jws;
+pub mod protocol;
 
 pub use authorization::Authorization;
-pub use jws::{JwsError, JWS}; // TODO: JWS -> Jws
+pub use grant::GrantAuthorizer;
+pub use jws::{JwsError, KeyResolver, JWS}; // TODO: JWS -> Jws
+pub use protocol::ProtocolAuthorizer;
@@ -0,0 +1,570 @@
+use crate::{
+    descriptors::{DeleteDescriptor, RecordsWriteDescriptor, RECORDS, WRITE},
+    errors::ProtocolAuthorizationError,
+    filters::{Filter, FilterKey, Filters, MessageSort, SortDirection, ValueFilter},
+    indexes::{
+        CONTEXT_ID, INTERFACE, IS_LATEST_BASE_STATE, METHOD, PROTOCOL, PROTOCOL_PATH, RECIPIENT,
+        RECORD_ID,
+    },
+    protocols::{Action, Can, Definition, RuleSet, Who},
+    stores::MessageStore,
+    Descriptor, Message, Value,
+};
+
+/// ProtocolAuthorizer authorizes the messages of authors other than the tenant against the rule
+/// sets of a protocol definition. Each rule set lists the actions which may be taken on records
+/// at its protocol path, either by anyone, by the author or recipient of an ancestor record
+/// (`of`), or by the recipients of a role record which the author invokes.
+pub struct ProtocolAuthorizer<'a, MS: MessageStore> {
+    message_store: &'a MS,
+    tenant: &'a str,
+    definition: &'a Definition,
+}
+
+impl<'a, MS: MessageStore> ProtocolAuthorizer<'a, MS> {
+    pub fn new(message_store: &'a MS, tenant: &'a str, definition: &'a Definition) -> Self {
+        Self {
+            message_store,
+            tenant,
+            definition,
+        }
+    }
+
+    /// verify_write checks that a RecordsWrite follows the structure of the protocol, whoever
+    /// authors it. Writes without an initial write create the record, and must be placed at a
+    /// protocol path which is a child of the parent record's path.
+    pub async fn verify_write(
+        &self,
+        write: &Message<RecordsWriteDescriptor>,
+        initial_write: Option<&Message<RecordsWriteDescriptor>>,
+    ) -> Result<(), ProtocolAuthorizationError> {
+        let protocol_path = self.protocol_path(&write.descriptor)?;
+        if initial_write.is_some() {
+            return Ok(());
+        }
+
+        let parent = match write.descriptor.parent_id.as_deref() {
+            Some(parent_id) => Some(self.initial_write(parent_id).await?.ok_or_else(|| {
+                ProtocolAuthorizationError::InvalidMessage(format!(
+                    "unable to find the parent record {}",
+                    parent_id
+                ))
+            })?),
+            None => None,
+        };
+
+        verify_structure(self.definition, protocol_path, parent.as_ref())
+    }
+
+    /// authorize_write authorizes the author of a RecordsWrite to create the record (when there
+    /// is no initial write), or to update it. The write must already have been checked against
+    /// the structure of the protocol by `verify_write`.
+    pub async fn authorize_write(
+        &self,
+        write: &Message<RecordsWriteDescriptor>,
+        initial_write: Option<&Message<RecordsWriteDescriptor>>,
+    ) -> Result<(), ProtocolAuthorizationError> {
+        let author = required_author(write)?;
+        let protocol_path = self.protocol_path(&write.descriptor)?;
+        let parent_id = write.descriptor.parent_id.as_deref();
+
+        let ancestors = self.record_chain(parent_id).await?;
+        let actions = match initial_write {
+            None => vec![Can::Create],
+            Some(initial) if required_author(initial)? == author => {
+                vec![Can::CoUpdate, Can::Update]
+            }
+            Some(_) => vec![Can::CoUpdate],
+        };
+
+        let context_id = write.fields.context_id.as_deref();
+        let role = write.fields.authorization.protocol_role()?;
+        self.authorize_actions(
+            protocol_path,
+            &ancestors,
+            initial_write,
+            context_id,
+            &author,
+            role.as_deref(),
+            &actions,
+        )
+        .await
+    }
+
+    /// authorize_read authorizes reading a record, given the latest write of the record.
+    pub async fn authorize_read(
+        &self,
+        record: &Message<RecordsWriteDescriptor>,
+        requester: &str,
+        role: Option<&str>,
+    ) -> Result<(), ProtocolAuthorizationError> {
+        let protocol_path = self.protocol_path(&record.descriptor)?;
+
+        let ancestors = self
+            .record_chain(record.descriptor.parent_id.as_deref())
+            .await?;
+
+        self.authorize_actions(
+            protocol_path,
+            &ancestors,
+            Some(record),
+            record.fields.context_id.as_deref(),
+            requester,
+            role,
+            &[Can::Read],
+        )
+        .await
+    }
+
+    /// authorize_query authorizes querying (or subscribing to) the records at a protocol path.
+    /// Queries are only authorized by invoking a role, as no single record is acted upon.
+    pub async fn authorize_query(
+        &self,
+        protocol_path: &str,
+        context_id: Option<&str>,
+        requester: &str,
+        role: Option<&str>,
+        action: Can,
+    ) -> Result<(), ProtocolAuthorizationError> {
+        if role.is_none() {
+            return Err(ProtocolAuthorizationError::Unauthorized(format!(
+                "a protocol role is required to {:?} records at {}",
+                action, protocol_path
+            )));
+        }
+
+        self.authorize_actions(
+            protocol_path,
+            &[],
+            None,
+            context_id,
+            requester,
+            role,
+            &[action],
+        )
+        .await
+    }
+
+    /// authorize_delete authorizes a RecordsDelete, given the initial write of the deleted
+    /// record. Deletes which prune the record's descendants require a prune action.
+    pub async fn authorize_delete(
+        &self,
+        delete: &Message<DeleteDescriptor>,
+        initial_write: &Message<RecordsWriteDescriptor>,
+    ) -> Result<(), ProtocolAuthorizationError> {
+        let author = delete.fields.author()?.ok_or_else(|| {
+            ProtocolAuthorizationError::Unauthorized("message must be signed".to_string())
+        })?;
+        let protocol_path = self.protocol_path(&initial_write.descriptor)?;
+
+        let is_record_author = required_author(initial_write)? == author;
+        let actions = match (delete.descriptor.prune, is_record_author) {
+            (true, true) => vec![Can::CoPrune, Can::Prune],
+            (true, false) => vec![Can::CoPrune],
+            (false, true) => vec![Can::CoDelete, Can::Delete],
+            (false, false) => vec![Can::CoDelete],
+        };
+
+        let ancestors = self
+            .record_chain(initial_write.descriptor.parent_id.as_deref())
+            .await?;
+
+        let role = delete.fields.protocol_role()?;
+        self.authorize_actions(
+            protocol_path,
+            &ancestors,
+            Some(initial_write),
+            initial_write.fields.context_id.as_deref(),
+            &author,
+            role.as_deref(),
+            &actions,
+        )
+        .await
+    }
+
+    /// authorize_actions checks that one of the actions is allowed for the author by the rule
+    /// set at the protocol path. `ancestors` are the records from the root record to the parent
+    /// of the record acted upon (if any), which the `of` of each action is resolved against.
+    #[allow(clippy::too_many_arguments)]
+    async fn authorize_actions(
+        &self,
+        protocol_path: &str,
+        ancestors: &[Message<RecordsWriteDescriptor>],
+        record: Option<&Message<RecordsWriteDescriptor>>,
+        context_id: Option<&str>,
+        author: &str,
+        role: Option<&str>,
+        actions: &[Can],
+    ) -> Result<(), ProtocolAuthorizationError> {
+        if author == self.tenant {
+            return Ok(());
+        }
+
+        let rule_set = rule_set(self.definition, protocol_path)?;
+        if let Some(role) = role {
+            self.verify_role(role, context_id, author).await?;
+        }
+
+        for action in &rule_set.actions {
+            let allowed = match action {
+                Action::Role(rule) => {
+                    role == Some(rule.role.as_str()) && can_any(&rule.can, actions)
+                }
+                Action::Who(rule) if can_any(&rule.can, actions) => {
+                    match (&rule.who, rule.of.as_deref()) {
+                        (Who::Anyone, _) => true,
+                        // without `of`, the recipient is the recipient of the record acted upon.
+                        (Who::Recipient, None) => record.is_some_and(|record| {
+                            record.descriptor.recipient.as_deref() == Some(author)
+                        }),
+                        (Who::Author, None) => false,
+                        (who, Some(of)) => {
+                            let ancestor = ancestors.iter().chain(record).find(|record| {
+                                record.descriptor.protocol_path.as_deref() == Some(of)
+                            });
+                            match (who, ancestor) {
+                                (Who::Author, Some(ancestor)) => {
+                                    ancestor.fields.authorization.author()?.as_deref()
+                                        == Some(author)
+                                }
+                                (Who::Recipient, Some(ancestor)) => {
+                                    ancestor.descriptor.recipient.as_deref() == Some(author)
+                                }
+                                _ => false,
+                            }
+                        }
+                    }
+                }
+                Action::Who(_) => false,
+            };
+
+            if allowed {
+                return Ok(());
+            }
+        }
+
+        Err(ProtocolAuthorizationError::Unauthorized(format!(
+            "no rule at {} allows {} to {:?}",
+            protocol_path, author, actions
+        )))
+    }
+
+    /// verify_role checks that the author is the recipient of a role record for the invoked
+    /// role. Roles nested under another record only apply within the context of that record.
+    async fn verify_role(
+        &self,
+        role: &str,
+        context_id: Option<&str>,
+        author: &str,
+    ) -> Result<(), ProtocolAuthorizationError> {
+        let rule_set = rule_set(self.definition, role)?;
+        if rule_set.role != Some(true) {
+            return Err(ProtocolAuthorizationError::InvalidMessage(format!(
+                "{} is not a role",
+                role
+            )));
+        }
+
+        let mut filter = ValueFilter::<FilterKey>::from([
+            index(INTERFACE, RECORDS),
+            index(METHOD, WRITE),
+            index(PROTOCOL, &self.definition.protocol),
+            index(PROTOCOL_PATH, role),
+            index(RECIPIENT, author),
+            (
+                FilterKey::Index(IS_LATEST_BASE_STATE.to_string()),
+                Filter::Equal(Value::Bool(true)),
+            ),
+        ]);
+
+        let depth = role.split('/').count();
+        if depth > 1 {
+            let context_id = context_id.ok_or_else(|| {
+                ProtocolAuthorizationError::InvalidMessage(format!(
+                    "contextId is required to invoke the role {}",
+                    role
+                ))
+            })?;
+            let segments = context_id.split('/').collect::<Vec<&str>>();
+            if segments.len() < depth - 1 {
+                return Err(ProtocolAuthorizationError::Unauthorized(format!(
+                    "the role {} does not apply to context {}",
+                    role, context_id
+                )));
+            }
+
+            let prefix = format!("{}/", segments[..depth - 1].join("/"));
+            filter.insert(
+                FilterKey::Index(CONTEXT_ID.to_string()),
+                Filter::Prefix(Value::String(prefix)),
+            );
+        }
+
+        let roles = self
+            .message_store
+            .query::<Descriptor>(self.tenant, Filters::from(filter), None, None)
+            .await?;
+
+        match roles.items.is_empty() {
+            true => Err(ProtocolAuthorizationError::Unauthorized(format!(
+                "{} has not been granted the role {}",
+                author, role
+            ))),
+            false => Ok(()),
+        }
+    }
+
+    /// protocol_path returns the protocol path of a record, checking that the record belongs to
+    /// the protocol.
+    fn protocol_path<'d>(
+        &self,
+        descriptor: &'d RecordsWriteDescriptor,
+    ) -> Result<&'d str, ProtocolAuthorizationError> {
+        if descriptor.protocol.as_deref() != Some(self.definition.protocol.as_str()) {
+            return Err(ProtocolAuthorizationError::InvalidMessage(format!(
+                "record does not belong to the protocol {}",
+                self.definition.protocol
+            )));
+        }
+
+        descriptor.protocol_path.as_deref().ok_or_else(|| {
+            ProtocolAuthorizationError::InvalidMessage("protocolPath is required".to_string())
+        })
+    }
+
+    /// record_chain returns the initial writes of a record and each of its ancestors, starting
+    /// from the root record.
+    async fn record_chain(
+        &self,
+        record_id: Option<&str>,
+    ) -> Result<Vec<Message<RecordsWriteDescriptor>>, ProtocolAuthorizationError> {
+        let mut chain = Vec::new();
+        let mut next = record_id.map(str::to_string);
+
+        while let Some(record_id) = next {
+            let record = self.initial_write(&record_id).await?.ok_or_else(|| {
+                ProtocolAuthorizationError::InvalidMessage(format!(
+                    "unable to find the parent record {}",
+                    record_id
+                ))
+            })?;
+
+            next = record.descriptor.parent_id.clone();
+            chain.push(record);
+        }
+
+        chain.reverse();
+        Ok(chain)
+    }
+
+    async fn initial_write(
+        &self,
+        record_id: &str,
+    ) -> Result<Option<Message<RecordsWriteDescriptor>>, ProtocolAuthorizationError> {
+        let filter = ValueFilter::<FilterKey>::from([
+            index(INTERFACE, RECORDS),
+            index(METHOD, WRITE),
+            index(PROTOCOL, &self.definition.protocol),
+            index(RECORD_ID, record_id),
+        ]);
+
+        let writes = self
+            .message_store
+            .query::<Descriptor>(
+                self.tenant,
+                Filters::from(filter),
+                Some(MessageSort::Timestamp(SortDirection::Ascending)),
+                None,
+            )
+            .await?;
+
+        for write in writes.items {
+            let write: Message<RecordsWriteDescriptor> = write
+                .try_into_message()
+                .map_err(|err| ProtocolAuthorizationError::InvalidMessage(err.to_string()))?;
+            if write.descriptor.message_timestamp == write.descriptor.date_created {
+                return Ok(Some(write));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// rule_set returns the rule set at a protocol path, by walking the protocol structure.
+fn rule_set<'d>(
+    definition: &'d Definition,
+    protocol_path: &str,
+) -> Result<&'d RuleSet, ProtocolAuthorizationError> {
+    let not_found = || {
+        ProtocolAuthorizationError::InvalidMessage(format!(
+            "no rule set is defined for {}",
+            protocol_path
+        ))
+    };
+
+    let mut segments = protocol_path.split('/');
+    let root = segments.next().unwrap_or_default();
+    let mut rule_set = definition.structure.get(root).ok_or_else(not_found)?;
+    for segment in segments {
+        rule_set = rule_set.rules.get(segment).ok_or_else(not_found)?;
+    }
+
+    Ok(rule_set)
+}
+
+/// verify_structure checks that a new record is placed at a protocol path which is defined by the
+/// protocol, and is a direct child of the parent record's protocol path.
+fn verify_structure(
+    definition: &Definition,
+    protocol_path: &str,
+    parent: Option<&Message<RecordsWriteDescriptor>>,
+) -> Result<(), ProtocolAuthorizationError> {
+    rule_set(definition, protocol_path)?;
+
+    let (parent_path, record_type) = match protocol_path.rsplit_once('/') {
+        Some((parent_path, record_type)) => (Some(parent_path), record_type),
+        None => (None, protocol_path),
+    };
+
+    if !definition.types.contains_key(record_type) {
+        return Err(ProtocolAuthorizationError::InvalidMessage(format!(
+            "{} is not a type of the protocol",
+            record_type
+        )));
+    }
+
+    let actual = parent.and_then(|parent| parent.descriptor.protocol_path.as_deref());
+    if actual != parent_path {
+        return Err(ProtocolAuthorizationError::InvalidMessage(format!(
+            "protocolPath {} does not match the parent record's protocolPath {:?}",
+            protocol_path, actual
+        )));
+    }
+
+    Ok(())
+}
+
+fn required_author(
+    write: &Message<RecordsWriteDescriptor>,
+) -> Result<String, ProtocolAuthorizationError> {
+    write.fields.authorization.author()?.ok_or_else(|| {
+        ProtocolAuthorizationError::Unauthorized("message must be signed".to_string())
+    })
+}
+
+fn can_any(can: &[Can], actions: &[Can]) -> bool {
+    actions.iter().any(|action| can.contains(action))
+}
+
+fn index(key: &str, value: &str) -> (FilterKey, Filter<Value>) {
+    (
+        FilterKey::Index(key.to_string()),
+        Filter::Equal(Value::String(value.to_string())),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::protocols::{ActionRole, ActionWho, Type};
+
+    fn definition() -> Definition {
+        let thread = RuleSet {
+            actions: vec![Action::Who(ActionWho {
+                who: Who::Anyone,
+                of: None,
+                can: vec![Can::Read],
+            })],
+            rules: BTreeMap::from([
+                (
+                    "participant".to_string(),
+                    RuleSet {
+                        role: Some(true),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "chat".to_string(),
+                    RuleSet {
+                        actions: vec![
+                            Action::Role(ActionRole {
+                                role: "thread/participant".to_string(),
+                                can: vec![Can::Create, Can::Query],
+                            }),
+                            Action::Who(ActionWho {
+                                who: Who::Recipient,
+                                of: Some("thread".to_string()),
+                                can: vec![Can::Create],
+                            }),
+                        ],
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            ..Default::default()
+        };
+
+        Definition {
+            protocol: "http://example.com/chat".to_string(),
+            published: true,
+            types: ["thread", "participant", "chat"]
+                .into_iter()
+                .map(|name| {
+                    (
+                        name.to_string(),
+                        Type {
+                            schema: None,
+                            data_formats: None,
+                        },
+                    )
+                })
+                .collect(),
+            structure: BTreeMap::from([("thread".to_string(), thread)]),
+        }
+    }
+
+    #[test]
+    fn test_rule_set() {
+        let definition = definition();
+
+        assert!(rule_set(&definition, "thread").is_ok());
+        assert_eq!(
+            rule_set(&definition, "thread/participant").unwrap().role,
+            Some(true)
+        );
+        assert!(rule_set(&definition, "thread/chat").is_ok());
+        assert!(rule_set(&definition, "chat").is_err());
+        assert!(rule_set(&definition, "thread/chat/reply").is_err());
+    }
+
+    #[test]
+    fn test_verify_structure() {
+        let definition = definition();
+        let thread = Message::<RecordsWriteDescriptor> {
+            descriptor: RecordsWriteDescriptor {
+                protocol: Some(definition.protocol.clone()),
+                protocol_path: Some("thread".to_string()),
+                ..Default::default()
+            },
+            fields: Default::default(),
+        };
+
+        assert!(verify_structure(&definition, "thread", None).is_ok());
+        assert!(verify_structure(&definition, "thread/chat", Some(&thread)).is_ok());
+        assert!(verify_structure(&definition, "thread/chat", None).is_err());
+        assert!(verify_structure(&definition, "thread", Some(&thread)).is_err());
+        assert!(verify_structure(&definition, "thread/unknown", Some(&thread)).is_err());
+        assert!(verify_structure(&definition, "chat", None).is_err());
+    }
+
+    #[test]
+    fn test_can_any() {
+        assert!(can_any(&[Can::Read, Can::Query], &[Can::Query]));
+        assert!(can_any(&[Can::CoUpdate], &[Can::CoUpdate, Can::Update]));
+        assert!(!can_any(&[Can::Update], &[Can::CoUpdate]));
+        assert!(!can_any(&[], &[Can::Read]));
+    }
+}
@@ -0,0 +1,204 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+
+use super::{DidDocument, DidResolutionError, DidResolver};
+
+/// DEFAULT_TTL_SECONDS is how long resolved DID documents are cached for by default.
+pub const DEFAULT_TTL_SECONDS: i64 = 15 * 60;
+
+/// DEFAULT_CAPACITY is the number of DID documents cached by default.
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// CachingResolver caches the DID documents resolved by another resolver, until their TTL
+/// expires. Once the cache is full, the least recently used document is evicted to make room
+/// for the next. Failed resolutions are not cached.
+pub struct CachingResolver<R: DidResolver> {
+    resolver: R,
+    ttl: Duration,
+    capacity: usize,
+    documents: Mutex<Documents>,
+}
+
+/// Documents are the cached DID documents, with the tick each was last used at.
+#[derive(Default)]
+struct Documents {
+    entries: HashMap<String, Entry>,
+    tick: u64,
+}
+
+struct Entry {
+    expires: DateTime<Utc>,
+    used: u64,
+    document: DidDocument,
+}
+
+impl<R: DidResolver> CachingResolver<R> {
+    pub fn new(resolver: R) -> Self {
+        Self::with_ttl(resolver, Duration::seconds(DEFAULT_TTL_SECONDS))
+    }
+
+    pub fn with_ttl(resolver: R, ttl: Duration) -> Self {
+        Self {
+            resolver,
+            ttl,
+            capacity: DEFAULT_CAPACITY,
+            documents: Mutex::new(Documents::default()),
+        }
+    }
+
+    /// with_capacity sets the number of DID documents the resolver caches.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// evict removes the cached document for a DID, so that it is resolved again.
+    pub fn evict(&self, did: &str) {
+        self.documents().entries.remove(did);
+    }
+
+    fn documents(&self) -> std::sync::MutexGuard<'_, Documents> {
+        self.documents
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn cached(&self, did: &str) -> Option<DidDocument> {
+        let mut documents = self.documents();
+        documents.tick += 1;
+        let tick = documents.tick;
+
+        match documents.entries.get_mut(did) {
+            Some(entry) if entry.expires > Utc::now() => {
+                entry.used = tick;
+                Some(entry.document.clone())
+            }
+            Some(_) => {
+                documents.entries.remove(did);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn cache(&self, did: &str, document: DidDocument) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut documents = self.documents();
+        documents.tick += 1;
+        let tick = documents.tick;
+
+        if !documents.entries.contains_key(did) && documents.entries.len() >= self.capacity {
+            let now = Utc::now();
+            documents.entries.retain(|_, entry| entry.expires > now);
+        }
+        while !documents.entries.contains_key(did) && documents.entries.len() >= self.capacity {
+            let lru = documents
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(did, _)| did.clone());
+            match lru {
+                Some(lru) => documents.entries.remove(&lru),
+                None => break,
+            };
+        }
+
+        documents.entries.insert(
+            did.to_string(),
+            Entry {
+                expires: Utc::now() + self.ttl,
+                used: tick,
+                document,
+            },
+        );
+    }
+}
+
+impl<R: DidResolver + Sync> DidResolver for CachingResolver<R> {
+    async fn resolve(&self, did: &str) -> Result<DidDocument, DidResolutionError> {
+        if let Some(document) = self.cached(did) {
+            return Ok(document);
+        }
+
+        let document = self.resolver.resolve(did).await?;
+        self.cache(did, document.clone());
+
+        Ok(document)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::dids::key;
+
+    /// CountingResolver resolves did:key DIDs, counting the resolutions.
+    #[derive(Default)]
+    struct CountingResolver {
+        count: AtomicUsize,
+    }
+
+    impl DidResolver for CountingResolver {
+        async fn resolve(&self, did: &str) -> Result<DidDocument, DidResolutionError> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            key::resolve(did)
+        }
+    }
+
+    const DID: &str = "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp";
+
+    #[tokio::test]
+    async fn test_caching_resolver() {
+        let resolver = CachingResolver::new(CountingResolver::default());
+
+        let document = resolver.resolve(DID).await.unwrap();
+        assert_eq!(resolver.resolve(DID).await.unwrap(), document);
+        assert_eq!(resolver.resolver.count.load(Ordering::SeqCst), 1);
+
+        resolver.evict(DID);
+        resolver.resolve(DID).await.unwrap();
+        assert_eq!(resolver.resolver.count.load(Ordering::SeqCst), 2);
+
+        // failures are not cached.
+        assert!(resolver.resolve("did:key:invalid").await.is_err());
+        assert!(resolver.resolve("did:key:invalid").await.is_err());
+        assert_eq!(resolver.resolver.count.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_caching_resolver_ttl() {
+        let resolver = CachingResolver::with_ttl(CountingResolver::default(), Duration::zero());
+
+        resolver.resolve(DID).await.unwrap();
+        resolver.resolve(DID).await.unwrap();
+        assert_eq!(resolver.resolver.count.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_caching_resolver_capacity() {
+        let did = || key::did_key(&ssi_jwk::JWK::generate_ed25519().unwrap()).unwrap();
+        let (other, third) = (did(), did());
+
+        let resolver = CachingResolver::new(CountingResolver::default()).with_capacity(2);
+
+        resolver.resolve(DID).await.unwrap();
+        resolver.resolve(&other).await.unwrap();
+        // using DID makes other the least recently used document, which third evicts.
+        resolver.resolve(DID).await.unwrap();
+        resolver.resolve(&third).await.unwrap();
+        assert_eq!(resolver.resolver.count.load(Ordering::SeqCst), 3);
+
+        resolver.resolve(DID).await.unwrap();
+        resolver.resolve(&third).await.unwrap();
+        assert_eq!(resolver.resolver.count.load(Ordering::SeqCst), 3);
+
+        resolver.resolve(&other).await.unwrap();
+        assert_eq!(resolver.resolver.count.load(Ordering::SeqCst), 4);
+    }
+}
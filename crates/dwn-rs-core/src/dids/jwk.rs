@@ -0,0 +1,65 @@
+use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD as base64url};
+use ssi_jwk::JWK;
+
+use super::{verification_document, DidDocument, DidResolutionError};
+
+pub const METHOD: &str = "jwk";
+
+/// resolve resolves a `did:jwk` DID. The DID document has a single verification method, whose
+/// key is the base64url encoded JWK of the DID.
+pub fn resolve(did: &str) -> Result<DidDocument, DidResolutionError> {
+    let encoded = did
+        .strip_prefix("did:jwk:")
+        .ok_or_else(|| DidResolutionError::InvalidDid(did.to_string()))?;
+
+    let decoded = base64url
+        .decode(encoded)
+        .map_err(|err| DidResolutionError::InvalidDid(err.to_string()))?;
+    let jwk: JWK = serde_json::from_slice(&decoded)?;
+
+    if jwk.to_public() != jwk {
+        return Err(DidResolutionError::InvalidKey(
+            "did:jwk must not contain a private key".to_string(),
+        ));
+    }
+
+    Ok(verification_document(did, "0", jwk))
+}
+
+/// did_jwk returns the `did:jwk` DID for the public key of a JWK.
+pub fn did_jwk(jwk: &JWK) -> Result<String, DidResolutionError> {
+    let encoded = base64url.encode(serde_json::to_vec(&jwk.to_public())?);
+
+    Ok(format!("did:{}:{}", METHOD, encoded))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_did_jwk_roundtrip() {
+        let jwk = JWK::generate_secp256k1();
+        let did = did_jwk(&jwk).unwrap();
+        assert!(did.starts_with("did:jwk:ey"));
+
+        let document = resolve(&did).unwrap();
+        let method = document.verification_method(&format!("{}#0", did)).unwrap();
+        assert_eq!(method.jwk().unwrap(), jwk.to_public());
+    }
+
+    #[test]
+    fn test_resolve_private_key() {
+        let jwk = JWK::generate_secp256k1();
+        let did = format!(
+            "did:jwk:{}",
+            base64url.encode(serde_json::to_vec(&jwk).unwrap())
+        );
+
+        assert!(matches!(
+            resolve(&did),
+            Err(DidResolutionError::InvalidKey(_))
+        ));
+        assert!(resolve("did:jwk:not-base64!").is_err());
+    }
+}
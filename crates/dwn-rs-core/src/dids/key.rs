@@ -0,0 +1,176 @@
+use ssi_jwk::{Base64urlUInt, OctetParams, Params, JWK};
+
+use super::{verification_document, DidDocument, DidResolutionError};
+
+pub const METHOD: &str = "key";
+
+// multicodec prefixes of the public keys supported by did:key, as unsigned varints.
+const ED25519_PUB: [u8; 2] = [0xed, 0x01];
+const X25519_PUB: [u8; 2] = [0xec, 0x01];
+const SECP256K1_PUB: [u8; 2] = [0xe7, 0x01];
+const P256_PUB: [u8; 2] = [0x80, 0x24];
+
+/// resolve resolves a `did:key` DID. The DID document has a single verification method, whose
+/// key is encoded in the DID.
+pub fn resolve(did: &str) -> Result<DidDocument, DidResolutionError> {
+    let identifier = did
+        .strip_prefix("did:key:")
+        .ok_or_else(|| DidResolutionError::InvalidDid(did.to_string()))?;
+
+    let jwk = decode_multibase_key(identifier)?;
+
+    Ok(verification_document(did, identifier, jwk))
+}
+
+/// did_key returns the `did:key` DID for a public key.
+pub fn did_key(jwk: &JWK) -> Result<String, DidResolutionError> {
+    Ok(format!("did:{}:{}", METHOD, encode_multibase_key(jwk)?))
+}
+
+/// decode_multibase_key decodes a multibase encoded, multicodec prefixed public key, as used by
+/// `did:key` and the `publicKeyMultibase` of verification methods.
+pub fn decode_multibase_key(key: &str) -> Result<JWK, DidResolutionError> {
+    let (base, bytes) =
+        multibase::decode(key).map_err(|err| DidResolutionError::InvalidKey(err.to_string()))?;
+    if base != multibase::Base::Base58Btc {
+        return Err(DidResolutionError::InvalidKey(format!(
+            "key must be base58btc encoded, found {:?}",
+            base
+        )));
+    }
+
+    if bytes.len() < 2 {
+        return Err(DidResolutionError::InvalidKey(
+            "key is too short".to_string(),
+        ));
+    }
+
+    let invalid = |err: ssi_jwk::Error| DidResolutionError::InvalidKey(err.to_string());
+    let key = &bytes[2..];
+    match [bytes[0], bytes[1]] {
+        ED25519_PUB => ssi_jwk::ed25519_parse(key).map_err(invalid),
+        SECP256K1_PUB => ssi_jwk::secp256k1_parse(key).map_err(invalid),
+        P256_PUB => ssi_jwk::p256_parse(key).map_err(invalid),
+        X25519_PUB if key.len() == 32 => Ok(JWK::from(Params::OKP(OctetParams {
+            curve: "X25519".to_string(),
+            public_key: Base64urlUInt(key.to_vec()),
+            private_key: None,
+        }))),
+        _ => Err(DidResolutionError::InvalidKey(
+            "unsupported key type".to_string(),
+        )),
+    }
+}
+
+/// encode_multibase_key encodes a public key as a multibase (base58btc) encoded, multicodec
+/// prefixed key.
+pub fn encode_multibase_key(jwk: &JWK) -> Result<String, DidResolutionError> {
+    let mut bytes = Vec::new();
+
+    match jwk.params {
+        Params::OKP(ref okp) => {
+            let prefix = match okp.curve.as_str() {
+                "Ed25519" => ED25519_PUB,
+                "X25519" => X25519_PUB,
+                curve => {
+                    return Err(DidResolutionError::InvalidKey(format!(
+                        "unsupported curve: {}",
+                        curve
+                    )))
+                }
+            };
+            bytes.extend_from_slice(&prefix);
+            bytes.extend_from_slice(&okp.public_key.0);
+        }
+        Params::EC(ref ec) => {
+            let prefix = match ec.curve.as_deref() {
+                Some("secp256k1") => SECP256K1_PUB,
+                Some("P-256") => P256_PUB,
+                curve => {
+                    return Err(DidResolutionError::InvalidKey(format!(
+                        "unsupported curve: {:?}",
+                        curve
+                    )))
+                }
+            };
+            let (Some(x), Some(y)) = (&ec.x_coordinate, &ec.y_coordinate) else {
+                return Err(DidResolutionError::InvalidKey(
+                    "EC key is missing coordinates".to_string(),
+                ));
+            };
+
+            // keys are encoded as compressed points, which are prefixed by the parity of y.
+            bytes.extend_from_slice(&prefix);
+            bytes.push(0x02 | (y.0.last().copied().unwrap_or_default() & 1));
+            bytes.extend_from_slice(&x.0);
+        }
+        _ => {
+            return Err(DidResolutionError::InvalidKey(
+                "unsupported key type".to_string(),
+            ))
+        }
+    }
+
+    Ok(multibase::encode(multibase::Base::Base58Btc, bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        // from the did:key specification test vectors.
+        let did = "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp";
+        let document = resolve(did).unwrap();
+
+        assert_eq!(document.id, did);
+        let method = document
+            .verification_method(&format!(
+                "{}#z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+                did
+            ))
+            .unwrap();
+        match method.jwk().unwrap().params {
+            Params::OKP(okp) => assert_eq!(okp.curve, "Ed25519"),
+            _ => panic!("expected an Ed25519 key"),
+        }
+
+        assert!(resolve("did:jwk:z6Mk").is_err());
+        assert!(resolve("did:key:6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp").is_err());
+    }
+
+    #[test]
+    fn test_did_key_roundtrip() {
+        let keys = vec![
+            JWK::generate_ed25519().unwrap(),
+            JWK::generate_secp256k1(),
+            JWK::generate_p256(),
+        ];
+
+        for key in keys {
+            let public = key.to_public();
+            let did = did_key(&key).unwrap();
+            let document = resolve(&did).unwrap();
+
+            assert_eq!(
+                document.verification_method[0].jwk().unwrap().params,
+                public.params,
+                "{}",
+                did
+            );
+        }
+    }
+
+    #[test]
+    fn test_did_key_prefixes() {
+        let ed25519 = did_key(&JWK::generate_ed25519().unwrap()).unwrap();
+        assert!(ed25519.starts_with("did:key:z6Mk"));
+
+        let secp256k1 = did_key(&JWK::generate_secp256k1()).unwrap();
+        assert!(secp256k1.starts_with("did:key:zQ3s"));
+
+        let p256 = did_key(&JWK::generate_p256()).unwrap();
+        assert!(p256.starts_with("did:key:zDn"));
+    }
+}
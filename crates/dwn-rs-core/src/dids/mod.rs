@@ -0,0 +1,307 @@
+//! DID resolution for the keys which sign and encrypt DWN messages.
+//!
+//! [`DidResolver`] resolves a DID to its [`DidDocument`]. [`MethodResolver`] resolves the
+//! `did:key`, `did:jwk` and `did:web` methods, and [`CachingResolver`] caches the documents
+//! resolved by another resolver. Every `DidResolver` is also a [`KeyResolver`], resolving the
+//! key ID of a JWS signature to the public key of the verification method it names.
+pub mod cache;
+pub mod jwk;
+pub mod key;
+pub mod web;
+
+use std::future::Future;
+
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use ssi_jwk::JWK;
+use thiserror::Error;
+
+use crate::auth::{JwsError, KeyResolver};
+
+pub use cache::CachingResolver;
+pub use web::{HttpFetcher, WebResolver};
+
+#[derive(Error, Debug)]
+pub enum DidResolutionError {
+    #[error("Invalid DID: {0}")]
+    InvalidDid(String),
+    #[error("Unsupported DID method: {0}")]
+    UnsupportedMethod(String),
+    #[error("Invalid DID key: {0}")]
+    InvalidKey(String),
+    #[error("Error fetching DID document: {0}")]
+    FetchError(String),
+    #[error("Error parsing DID document: {0}")]
+    ParseError(#[from] serde_json::Error),
+    #[error("DID document {0} does not match the resolved DID")]
+    DocumentMismatch(String),
+    #[error("Verification method not found: {0}")]
+    VerificationMethodNotFound(String),
+}
+
+/// DidDocument is a resolved DID document. Only the properties used for verifying signatures
+/// and encrypting records are kept.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
+pub struct DidDocument {
+    pub id: String,
+    #[serde(rename = "verificationMethod", default)]
+    pub verification_method: Vec<VerificationMethod>,
+    #[serde(default)]
+    pub authentication: Vec<VerificationRelationship>,
+    #[serde(rename = "assertionMethod", default)]
+    pub assertion_method: Vec<VerificationRelationship>,
+    #[serde(rename = "keyAgreement", default)]
+    pub key_agreement: Vec<VerificationRelationship>,
+}
+
+/// VerificationMethod is a public key of a DID document.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
+pub struct VerificationMethod {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub controller: String,
+    #[serde(rename = "publicKeyJwk")]
+    pub public_key_jwk: Option<JWK>,
+    #[serde(rename = "publicKeyMultibase")]
+    pub public_key_multibase: Option<String>,
+}
+
+/// VerificationRelationship refers to a verification method of the document by ID, or embeds
+/// the verification method.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum VerificationRelationship {
+    Reference(String),
+    Embedded(VerificationMethod),
+}
+
+impl DidDocument {
+    /// verification_method returns the verification method with the given ID. IDs relative to
+    /// the document (such as `#key-1`) are resolved against the document ID.
+    pub fn verification_method(&self, id: &str) -> Option<&VerificationMethod> {
+        let id = absolute_id(&self.id, id);
+        let embedded = self
+            .authentication
+            .iter()
+            .chain(&self.assertion_method)
+            .chain(&self.key_agreement)
+            .filter_map(|relationship| match relationship {
+                VerificationRelationship::Embedded(method) => Some(method),
+                VerificationRelationship::Reference(_) => None,
+            });
+
+        self.verification_method
+            .iter()
+            .chain(embedded)
+            .find(|method| absolute_id(&self.id, &method.id) == id)
+    }
+
+    /// signing_method returns the verification method with the given ID, if it may be used to
+    /// sign messages: it must be referenced or embedded by the `authentication` or
+    /// `assertionMethod` relationships of the document. Key agreement keys are only used for
+    /// encryption.
+    pub fn signing_method(&self, id: &str) -> Option<&VerificationMethod> {
+        let absolute = absolute_id(&self.id, id);
+        let signs = self
+            .authentication
+            .iter()
+            .chain(&self.assertion_method)
+            .any(|relationship| {
+                let method_id = match relationship {
+                    VerificationRelationship::Reference(method_id) => method_id,
+                    VerificationRelationship::Embedded(method) => &method.id,
+                };
+                absolute_id(&self.id, method_id) == absolute
+            });
+
+        match signs {
+            true => self.verification_method(id),
+            false => None,
+        }
+    }
+}
+
+impl VerificationMethod {
+    /// jwk returns the public key of the verification method as a JWK.
+    pub fn jwk(&self) -> Result<JWK, DidResolutionError> {
+        match (&self.public_key_jwk, &self.public_key_multibase) {
+            (Some(jwk), _) => Ok(jwk.to_public()),
+            (None, Some(multibase)) => key::decode_multibase_key(multibase),
+            (None, None) => Err(DidResolutionError::InvalidKey(format!(
+                "verification method {} has no public key",
+                self.id
+            ))),
+        }
+    }
+}
+
+/// DidResolver resolves DIDs to their DID documents.
+pub trait DidResolver {
+    fn resolve(
+        &self,
+        did: &str,
+    ) -> impl Future<Output = Result<DidDocument, DidResolutionError>> + Send;
+}
+
+/// Every DID resolver can resolve the key ID of a JWS signature, which is a DID URL naming a
+/// verification method of the signer's DID document.
+impl<R: DidResolver + Sync> KeyResolver for R {
+    async fn resolve_key(&self, kid: &str) -> Result<JWK, JwsError> {
+        resolve_key(self, kid)
+            .await
+            .map_err(|err| JwsError::KeyResolutionError(kid.to_string(), err.to_string()))
+    }
+}
+
+/// resolve_key resolves a DID URL to the public key of the verification method it names. Only
+/// verification methods which may sign messages are resolved.
+pub async fn resolve_key<R: DidResolver>(
+    resolver: &R,
+    kid: &str,
+) -> Result<JWK, DidResolutionError> {
+    let did = kid.split('#').next().unwrap_or_default();
+    let document = resolver.resolve(did).await?;
+
+    document
+        .signing_method(kid)
+        .ok_or_else(|| DidResolutionError::VerificationMethodNotFound(kid.to_string()))?
+        .jwk()
+}
+
+/// MethodResolver resolves the `did:key`, `did:jwk` and `did:web` DID methods. `did:web`
+/// documents are fetched with the given HTTP fetcher.
+pub struct MethodResolver<F: HttpFetcher> {
+    web: WebResolver<F>,
+}
+
+impl<F: HttpFetcher> MethodResolver<F> {
+    pub fn new(fetcher: F) -> Self {
+        Self {
+            web: WebResolver::new(fetcher),
+        }
+    }
+}
+
+impl<F: HttpFetcher + Sync> DidResolver for MethodResolver<F> {
+    async fn resolve(&self, did: &str) -> Result<DidDocument, DidResolutionError> {
+        match did_method(did)? {
+            key::METHOD => key::resolve(did),
+            jwk::METHOD => jwk::resolve(did),
+            web::METHOD => self.web.resolve(did).await,
+            method => Err(DidResolutionError::UnsupportedMethod(method.to_string())),
+        }
+    }
+}
+
+/// did_method returns the method of a DID, such as `key` for `did:key:z6Mk...`.
+pub fn did_method(did: &str) -> Result<&str, DidResolutionError> {
+    match did.split(':').collect::<Vec<&str>>()[..] {
+        ["did", method, ref id @ ..] if !method.is_empty() && !id.is_empty() => Ok(method),
+        _ => Err(DidResolutionError::InvalidDid(did.to_string())),
+    }
+}
+
+fn absolute_id(did: &str, id: &str) -> String {
+    match id.starts_with('#') {
+        true => format!("{}{}", did, id),
+        false => id.to_string(),
+    }
+}
+
+/// verification_document returns the DID document for a DID with a single verification method,
+/// used for authentication, assertions and key agreement.
+fn verification_document(did: &str, fragment: &str, jwk: JWK) -> DidDocument {
+    let id = format!("{}#{}", did, fragment);
+
+    DidDocument {
+        id: did.to_string(),
+        verification_method: vec![VerificationMethod {
+            id: id.clone(),
+            type_: "JsonWebKey2020".to_string(),
+            controller: did.to_string(),
+            public_key_jwk: Some(jwk),
+            public_key_multibase: None,
+        }],
+        authentication: vec![VerificationRelationship::Reference(id.clone())],
+        assertion_method: vec![VerificationRelationship::Reference(id.clone())],
+        key_agreement: vec![VerificationRelationship::Reference(id)],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_did_method() {
+        assert_eq!(did_method("did:key:z6Mk").unwrap(), "key");
+        assert_eq!(did_method("did:web:example.com:alice").unwrap(), "web");
+        assert!(did_method("did:key").is_err());
+        assert!(did_method("key:z6Mk").is_err());
+        assert!(did_method("did::z6Mk").is_err());
+    }
+
+    #[test]
+    fn test_verification_method() {
+        let jwk = JWK::generate_ed25519().unwrap().to_public();
+        let mut document = verification_document("did:example:alice", "key-1", jwk.clone());
+
+        let method = document
+            .verification_method("did:example:alice#key-1")
+            .unwrap();
+        assert_eq!(method.jwk().unwrap(), jwk);
+        assert!(document.verification_method("#key-1").is_some());
+        assert!(document.verification_method("#key-2").is_none());
+
+        document.verification_method[0].id = "#key-2".to_string();
+        assert!(document
+            .verification_method("did:example:alice#key-2")
+            .is_some());
+    }
+
+    #[test]
+    fn test_signing_method() {
+        let jwk = JWK::generate_ed25519().unwrap().to_public();
+        let mut document = verification_document("did:example:alice", "key-1", jwk.clone());
+        assert!(document.signing_method("did:example:alice#key-1").is_some());
+        assert!(document.signing_method("#key-1").is_some());
+
+        // a key only used for key agreement can't sign.
+        document.authentication.clear();
+        document.assertion_method.clear();
+        assert!(document.verification_method("#key-1").is_some());
+        assert!(document.signing_method("#key-1").is_none());
+
+        document.key_agreement.clear();
+        document.verification_method.clear();
+        document
+            .key_agreement
+            .push(VerificationRelationship::Embedded(VerificationMethod {
+                id: "#key-2".to_string(),
+                type_: "JsonWebKey2020".to_string(),
+                controller: "did:example:alice".to_string(),
+                public_key_jwk: Some(jwk.clone()),
+                public_key_multibase: None,
+            }));
+        assert!(document.signing_method("#key-2").is_none());
+
+        // embedded authentication methods can sign, as can those referenced by relative ID.
+        let embedded = document.key_agreement.remove(0);
+        document.authentication.push(embedded);
+        assert!(document.signing_method("did:example:alice#key-2").is_some());
+
+        document
+            .assertion_method
+            .push(VerificationRelationship::Reference("#key-3".to_string()));
+        document.verification_method.push(VerificationMethod {
+            id: "did:example:alice#key-3".to_string(),
+            type_: "JsonWebKey2020".to_string(),
+            controller: "did:example:alice".to_string(),
+            public_key_jwk: Some(jwk),
+            public_key_multibase: None,
+        });
+        assert!(document.signing_method("#key-3").is_some());
+    }
+}
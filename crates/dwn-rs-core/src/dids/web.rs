@@ -0,0 +1,146 @@
+use std::future::Future;
+
+use url::Url;
+
+use super::{DidDocument, DidResolutionError, DidResolver};
+
+pub const METHOD: &str = "web";
+
+/// HttpFetcher fetches the body of an HTTP(S) URL. It is used to fetch `did:web` documents, so
+/// that the HTTP client can be chosen for the target (such as `fetch` for WebAssembly).
+pub trait HttpFetcher {
+    fn fetch(&self, url: &Url) -> impl Future<Output = Result<Vec<u8>, DidResolutionError>> + Send;
+}
+
+/// WebResolver resolves `did:web` DIDs, by fetching the DID document from the domain of the DID.
+pub struct WebResolver<F: HttpFetcher> {
+    fetcher: F,
+}
+
+impl<F: HttpFetcher> WebResolver<F> {
+    pub fn new(fetcher: F) -> Self {
+        Self { fetcher }
+    }
+}
+
+impl<F: HttpFetcher + Sync> DidResolver for WebResolver<F> {
+    async fn resolve(&self, did: &str) -> Result<DidDocument, DidResolutionError> {
+        let url = did_web_url(did)?;
+        let body = self.fetcher.fetch(&url).await?;
+        let document: DidDocument = serde_json::from_slice(&body)?;
+
+        if document.id != did {
+            return Err(DidResolutionError::DocumentMismatch(document.id));
+        }
+
+        Ok(document)
+    }
+}
+
+/// did_web_url returns the URL of the DID document for a `did:web` DID. The document of a DID
+/// with no path is at `/.well-known/did.json`, otherwise it is at `/<path>/did.json`. Ports are
+/// percent encoded in the domain of the DID.
+pub fn did_web_url(did: &str) -> Result<Url, DidResolutionError> {
+    let invalid = || DidResolutionError::InvalidDid(did.to_string());
+
+    let identifier = did.strip_prefix("did:web:").ok_or_else(invalid)?;
+    let mut segments = identifier.split(':');
+    let domain = segments
+        .next()
+        .filter(|domain| !domain.is_empty())
+        .ok_or_else(invalid)?
+        .replace("%3A", ":")
+        .replace("%3a", ":");
+
+    let path = segments.collect::<Vec<&str>>();
+    if path.iter().any(|segment| segment.is_empty()) {
+        return Err(invalid());
+    }
+
+    let path = match path.is_empty() {
+        true => ".well-known".to_string(),
+        false => path.join("/"),
+    };
+
+    Url::parse(&format!("https://{}/{}/did.json", domain, path)).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use ssi_jwk::JWK;
+
+    use super::*;
+    use crate::dids::verification_document;
+
+    /// LocalFetcher serves documents from memory, in place of an HTTP server.
+    struct LocalFetcher {
+        documents: HashMap<String, Vec<u8>>,
+    }
+
+    impl HttpFetcher for LocalFetcher {
+        async fn fetch(&self, url: &Url) -> Result<Vec<u8>, DidResolutionError> {
+            self.documents
+                .get(url.as_str())
+                .cloned()
+                .ok_or_else(|| DidResolutionError::FetchError(format!("404 Not Found: {}", url)))
+        }
+    }
+
+    #[test]
+    fn test_did_web_url() {
+        let tcs = vec![
+            (
+                "did:web:w3c-ccg.github.io",
+                "https://w3c-ccg.github.io/.well-known/did.json",
+            ),
+            (
+                "did:web:w3c-ccg.github.io:user:alice",
+                "https://w3c-ccg.github.io/user/alice/did.json",
+            ),
+            (
+                "did:web:example.com%3A3000:user:alice",
+                "https://example.com:3000/user/alice/did.json",
+            ),
+        ];
+
+        for (did, url) in tcs {
+            assert_eq!(did_web_url(did).unwrap().as_str(), url);
+        }
+
+        assert!(did_web_url("did:web:").is_err());
+        assert!(did_web_url("did:web:example.com::alice").is_err());
+        assert!(did_web_url("did:key:example.com").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let did = "did:web:example.com%3A3000:alice";
+        let jwk = JWK::generate_ed25519().unwrap().to_public();
+        let document = verification_document(did, "key-1", jwk);
+
+        let resolver = WebResolver::new(LocalFetcher {
+            documents: HashMap::from([
+                (
+                    "https://example.com:3000/alice/did.json".to_string(),
+                    serde_json::to_vec(&document).unwrap(),
+                ),
+                (
+                    "https://example.com:3000/bob/did.json".to_string(),
+                    serde_json::to_vec(&document).unwrap(),
+                ),
+            ]),
+        });
+
+        assert_eq!(resolver.resolve(did).await.unwrap(), document);
+        assert!(matches!(
+            resolver.resolve("did:web:example.com%3A3000:bob").await,
+            Err(DidResolutionError::DocumentMismatch(_))
+        ));
+        assert!(matches!(
+            resolver.resolve("did:web:example.com%3A3000:carol").await,
+            Err(DidResolutionError::FetchError(_))
+        ));
+    }
+}
@@ -0,0 +1,296 @@
+use bytes::Bytes;
+use futures_util::Stream;
+use tracing::{debug, instrument};
+
+use crate::{
+    auth::KeyResolver,
+    descriptors::{
+        MessageDescriptor, MessageValidator, CONFIGURE, DELETE, MESSAGES, PROTOCOLS, QUERY, READ,
+        RECORDS, SUBSCRIBE, WRITE,
+    },
+    emitter::EventStreamer,
+    errors::{Error, HandlerError},
+    replies::{Empty, Status},
+    stores::{DataStore, EventLog, GetDataResults, MessageStore, ResumableTaskStore},
+    Cursor, Descriptor, EventChannel, Message, Reply, Response, Subscription,
+};
+
+/// MessageReply is the result of processing a message with a [`Dwn`]. The response is the
+/// reply sent for the message, `data` holds the record data for replies which include
+/// data (such as `RecordsRead`), and `subscription` holds the subscription created by
+/// subscribe messages, which is closed to stop receiving events.
+pub struct MessageReply {
+    pub response: Response,
+    pub data: Option<GetDataResults>,
+    pub subscription: Option<Subscription>,
+}
+
+impl MessageReply {
+    pub fn new(status: Status, reply: Reply) -> Self {
+        Self {
+            response: Response::new(status, reply),
+            data: None,
+            subscription: None,
+        }
+    }
+
+    pub fn with_data(mut self, data: GetDataResults) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    pub fn with_subscription(mut self, subscription: Subscription) -> Self {
+        self.subscription = Some(subscription);
+        self
+    }
+}
+
+impl From<HandlerError> for MessageReply {
+    fn from(err: HandlerError) -> Self {
+        Self::new(
+            Status::new(err.code(), &err.to_string()),
+            Reply::Empty(Empty {}),
+        )
+    }
+}
+
+/// Dwn is a Decentralized Web Node. It processes messages for tenants against the message store,
+/// data store, event log and resumable task store it is created with, and emits events for the
+/// messages it accepts to its event stream. The signatures of each message are verified with
+/// keys from its resolver before the message is handled.
+pub struct Dwn<MS, DS, EL, RT, R>
+where
+    MS: MessageStore,
+    DS: DataStore,
+    EL: EventLog,
+    RT: ResumableTaskStore,
+    R: KeyResolver,
+{
+    pub(crate) message_store: MS,
+    pub(crate) data_store: DS,
+    pub(crate) event_log: EL,
+    pub(crate) task_store: RT,
+    pub(crate) event_stream: EventStreamer<Descriptor>,
+    pub(crate) resolver: R,
+}
+
+impl<MS, DS, EL, RT, R> Dwn<MS, DS, EL, RT, R>
+where
+    MS: MessageStore,
+    DS: DataStore,
+    EL: EventLog,
+    RT: ResumableTaskStore,
+    R: KeyResolver,
+{
+    pub fn new(
+        message_store: MS,
+        data_store: DS,
+        event_log: EL,
+        task_store: RT,
+        event_stream: EventStreamer<Descriptor>,
+        resolver: R,
+    ) -> Self {
+        Self {
+            message_store,
+            data_store,
+            event_log,
+            task_store,
+            event_stream,
+            resolver,
+        }
+    }
+
+    /// open opens all of the stores, and the event stream for the DWN. Any tasks which were
+    /// interrupted when the DWN was last running are resumed.
+    pub async fn open(&mut self) -> Result<(), Error> {
+        self.message_store.open().await?;
+        self.data_store.open().await?;
+        self.event_log.open().await?;
+        self.task_store.open().await?;
+        self.event_stream.open().await;
+
+        self.resume_tasks().await?;
+
+        Ok(())
+    }
+
+    /// close closes all of the stores, and the event stream for the DWN.
+    pub async fn close(&mut self) {
+        self.event_stream.close().await;
+        self.task_store.close().await;
+        self.event_log.close().await;
+        self.data_store.close().await;
+        self.message_store.close().await;
+    }
+
+    /// process_message processes a message for the given tenant, routing it to the handler
+    /// for the message's interface and method. `data` is the record data for messages which
+    /// carry data (such as `RecordsWrite`). Errors are returned as a reply with the
+    /// appropriate status code.
+    #[instrument(skip(self, message, data))]
+    pub async fn process_message<S>(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+        data: Option<S>,
+    ) -> MessageReply
+    where
+        S: Stream<Item = Bytes> + Send + Unpin,
+    {
+        match self.route_message(tenant, message, data).await {
+            Ok(reply) => reply,
+            Err(err) => {
+                debug!(error = %err, "unable to process message");
+                err.into()
+            }
+        }
+    }
+
+    /// process_subscription processes a subscribe message for the given tenant. Events for the
+    /// subscription are sent to `listener` until the subscription in the reply is closed.
+    #[instrument(skip(self, message, listener))]
+    pub async fn process_subscription(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+        listener: EventChannel<Descriptor>,
+    ) -> MessageReply {
+        self.subscribe(tenant, message, None, listener).await
+    }
+
+    /// process_subscription_from processes a subscribe message for the given tenant, like
+    /// `process_subscription`, first sending `listener` the events logged after the cursor. This
+    /// allows a subscriber to resume a subscription from the last event it received, without
+    /// missing or repeating events.
+    #[instrument(skip(self, message, listener))]
+    pub async fn process_subscription_from(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+        cursor: Cursor,
+        listener: EventChannel<Descriptor>,
+    ) -> MessageReply {
+        self.subscribe(tenant, message, Some(cursor), listener)
+            .await
+    }
+
+    async fn subscribe(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+        cursor: Option<Cursor>,
+        listener: EventChannel<Descriptor>,
+    ) -> MessageReply {
+        match self
+            .route_subscription(tenant, message, cursor, listener)
+            .await
+        {
+            Ok(reply) => reply,
+            Err(err) => {
+                debug!(error = %err, "unable to process subscription");
+                err.into()
+            }
+        }
+    }
+
+    async fn route_subscription(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+        cursor: Option<Cursor>,
+        listener: EventChannel<Descriptor>,
+    ) -> Result<MessageReply, HandlerError> {
+        message.descriptor.validate()?;
+        let signers = self.verify_message(&message).await?;
+
+        match (message.descriptor.interface(), message.descriptor.method()) {
+            (MESSAGES, SUBSCRIBE) => {
+                self.handle_messages_subscribe(tenant, message, signers, cursor, listener)
+                    .await
+            }
+            (interface, method) => Err(HandlerError::NotImplemented { interface, method }),
+        }
+    }
+
+    async fn route_message<S>(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+        data: Option<S>,
+    ) -> Result<MessageReply, HandlerError>
+    where
+        S: Stream<Item = Bytes> + Send + Unpin,
+    {
+        message.descriptor.validate()?;
+        let signers = self.verify_message(&message).await?;
+
+        match (message.descriptor.interface(), message.descriptor.method()) {
+            (RECORDS, WRITE) => {
+                self.handle_records_write(tenant, message, signers, data)
+                    .await
+            }
+            (RECORDS, QUERY) => self.handle_records_query(tenant, message, signers).await,
+            (RECORDS, READ) => self.handle_records_read(tenant, message, signers).await,
+            (RECORDS, DELETE) => self.handle_records_delete(tenant, message, signers).await,
+            (PROTOCOLS, CONFIGURE) => {
+                self.handle_protocols_configure(tenant, message, signers)
+                    .await
+            }
+            (PROTOCOLS, QUERY) => self.handle_protocols_query(tenant, message, signers).await,
+            (MESSAGES, READ) => self.handle_messages_read(tenant, message, signers).await,
+            (MESSAGES, QUERY) => self.handle_messages_query(tenant, message, signers).await,
+            (_, SUBSCRIBE) => Err(HandlerError::InvalidMessage(
+                "subscribe messages must be processed with a listener".to_string(),
+            )),
+            (interface, method) => Err(HandlerError::NotImplemented { interface, method }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_message_reply_from_error() {
+        struct TestCase {
+            err: HandlerError,
+            code: i32,
+        }
+
+        let tcs = vec![
+            TestCase {
+                err: HandlerError::InvalidMessage("missing recordId".to_string()),
+                code: 400,
+            },
+            TestCase {
+                err: HandlerError::Unauthorized("not the tenant".to_string()),
+                code: 401,
+            },
+            TestCase {
+                err: HandlerError::NotFound,
+                code: 404,
+            },
+            TestCase {
+                err: HandlerError::Conflict("newer message exists".to_string()),
+                code: 409,
+            },
+            TestCase {
+                err: HandlerError::NotImplemented {
+                    interface: RECORDS,
+                    method: SUBSCRIBE,
+                },
+                code: 501,
+            },
+        ];
+
+        for tc in tcs {
+            let detail = tc.err.to_string();
+            let reply: MessageReply = tc.err.into();
+
+            assert_eq!(reply.response.status, Status::new(tc.code, &detail));
+            assert_eq!(reply.response.reply, Reply::Empty(Empty {}));
+            assert!(reply.data.is_none());
+        }
+    }
+}
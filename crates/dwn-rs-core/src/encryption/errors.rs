@@ -1,7 +1,8 @@
 use thiserror::Error;
 
-use super::asymmetric;
 use super::hd_keys::Error as HDKeysError;
+use super::{asymmetric, symmetric};
+use crate::descriptors::ValidationError;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -9,4 +10,12 @@ pub enum Error {
     JWKSecretKeyError(#[from] asymmetric::Error),
     #[error("Error deriving key: {0}")]
     DeriveKeyError(#[from] HDKeysError),
+    #[error("Error encrypting data: {0}")]
+    SymmetricEncryptionError(#[from] symmetric::Error),
+    #[error("Error decoding encryption property: {0}")]
+    DecodeError(#[from] base64::DecodeError),
+    #[error("Error decrypting record: {0}")]
+    DecryptionError(String),
+    #[error("Invalid record: {0}")]
+    InvalidRecordError(#[from] ValidationError),
 }
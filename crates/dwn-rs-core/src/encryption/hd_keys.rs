@@ -4,6 +4,7 @@ use super::{
     asymmetric::{self, secretkey::SecretKey},
     DerivationScheme,
 };
+use crate::descriptors::RecordsWriteDescriptor;
 use thiserror::Error as ThisError;
 
 #[derive(Debug, ThisError)]
@@ -20,6 +21,127 @@ pub enum Error {
     InvalidPathSegment(String),
     #[error("Unsupported key type")]
     UnsupportedKeyType,
+    #[error("Missing derivation path property: {0}")]
+    MissingPathProperty(String),
+    #[error("The key is not an ancestor of the derivation path")]
+    NotAncestor,
+}
+
+/// DerivationPath is the path a record's leaf key is derived along from a root key, for one of
+/// the derivation schemes. The first segment of the path is the name of the scheme.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivationPath {
+    scheme: DerivationScheme,
+    segments: Vec<String>,
+}
+
+impl DerivationPath {
+    /// protocol_path returns the path of a record with the protocol path in the protocol,
+    /// which is `[protocolPath, protocol, ...protocolPathSegments]`.
+    pub fn protocol_path(protocol: &str, protocol_path: &str) -> Self {
+        let segments = [protocol]
+            .into_iter()
+            .chain(protocol_path.split('/'))
+            .map(String::from);
+
+        Self::new(DerivationScheme::ProtocolPath, segments)
+    }
+
+    /// protocol_context returns the path of a record in the context, which is
+    /// `[protocolContext, rootContextId]`. All records in a context tree share the path of the
+    /// root context.
+    pub fn protocol_context(context_id: &str) -> Self {
+        let root_context_id = context_id.split('/').next().unwrap_or_default();
+
+        Self::new(
+            DerivationScheme::ProtocolContext,
+            [root_context_id.to_string()],
+        )
+    }
+
+    /// schemas returns the path of a record with the schema, which is `[schemas, schema]`.
+    pub fn schemas(schema: &str) -> Self {
+        Self::new(DerivationScheme::Schemas, [schema.to_string()])
+    }
+
+    /// data_formats returns the path of a record with the data format, which is
+    /// `[dataFormats, schema, dataFormat]`, or `[dataFormats, dataFormat]` for records without a
+    /// schema.
+    pub fn data_formats(schema: Option<&str>, data_format: &str) -> Self {
+        let segments = schema.into_iter().chain([data_format]).map(String::from);
+
+        Self::new(DerivationScheme::DataFormats, segments)
+    }
+
+    /// record returns the path of the RecordsWrite for the scheme. Protocol context paths
+    /// require the context ID of the record.
+    pub fn record(
+        scheme: &DerivationScheme,
+        descriptor: &RecordsWriteDescriptor,
+        context_id: Option<&str>,
+    ) -> Result<Self, Error> {
+        let required = |property: &str| {
+            Error::MissingPathProperty(format!("{} paths require a {}", scheme.as_str(), property))
+        };
+
+        match scheme {
+            DerivationScheme::DataFormats => Ok(Self::data_formats(
+                descriptor.schema.as_deref(),
+                &descriptor.data_format,
+            )),
+            DerivationScheme::ProtocolContext => Ok(Self::protocol_context(
+                context_id.ok_or_else(|| required("contextId"))?,
+            )),
+            DerivationScheme::ProtocolPath => Ok(Self::protocol_path(
+                descriptor
+                    .protocol
+                    .as_deref()
+                    .ok_or_else(|| required("protocol"))?,
+                descriptor
+                    .protocol_path
+                    .as_deref()
+                    .ok_or_else(|| required("protocolPath"))?,
+            )),
+            DerivationScheme::Schemas => Ok(Self::schemas(
+                descriptor
+                    .schema
+                    .as_deref()
+                    .ok_or_else(|| required("schema"))?,
+            )),
+        }
+    }
+
+    fn new(scheme: DerivationScheme, segments: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            segments: [scheme.as_str().to_string()]
+                .into_iter()
+                .chain(segments)
+                .collect(),
+            scheme,
+        }
+    }
+
+    pub fn scheme(&self) -> &DerivationScheme {
+        &self.scheme
+    }
+
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    /// descendant_of returns the segments of the path below the ancestor path, or `None` if the
+    /// path does not descend from the ancestor path.
+    pub fn descendant_of(&self, ancestor: &[String]) -> Option<Vec<String>> {
+        self.segments
+            .strip_prefix(ancestor)
+            .map(|segments| segments.to_vec())
+    }
+}
+
+impl From<DerivationPath> for Vec<String> {
+    fn from(path: DerivationPath) -> Self {
+        path.segments
+    }
 }
 
 /// DerivedPrivateJWK represents a derived private JWK, which includes the root key ID, derivation
@@ -65,6 +187,20 @@ impl DerivedPrivateJWK {
         })
     }
 
+    /// derive_path derives the key at the derivation path from an ancestor key, which is either
+    /// the root key or a key already derived along a prefix of the path.
+    pub fn derive_path(
+        ancestor_key: DerivedPrivateJWK,
+        path: &DerivationPath,
+    ) -> Result<DerivedPrivateJWK, Error> {
+        let ancestor_path = ancestor_key.path.as_deref().unwrap_or_default();
+        let descendant_path = path
+            .descendant_of(ancestor_path)
+            .ok_or(Error::NotAncestor)?;
+
+        Self::derive(ancestor_key, descendant_path)
+    }
+
     /// derive_public_key derives a new public key from the root key using the derivation path.
     pub fn derive_public_key(
         ancestor_key: DerivedPrivateJWK,
@@ -107,6 +243,7 @@ impl DerivedPrivateJWK {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD as base64url};
     use ssi_jwk::JWK;
     use tracing_test::traced_test;
 
@@ -248,4 +385,181 @@ mod tests {
             "Invalid path segment: Empty path segment"
         );
     }
+
+    // keys derived from a fixed secp256k1 root key (the bytes 1 to 32) along the path of each
+    // scheme. The expected keys were computed separately from this implementation, with
+    // HKDF-SHA256 over each path segment in turn (an empty salt, and the segment as the info).
+    #[test]
+    fn test_derive_known_answers() {
+        let root_bytes = (1..=32).collect::<Vec<u8>>();
+        let root = SecretKey::Secp256k1(k256::SecretKey::from_slice(&root_bytes).unwrap().into());
+
+        let tcs = vec![
+            (
+                DerivationPath::protocol_path("https://example.com/chat", "thread/message"),
+                "lRvu2sEmD3x44q0KCMGBTX_U1_zGF5Soiq9KAsO0irE",
+                "noLRbpowwARHEDjaVZ9W8J2GGhKPbnllqOTQMvmTCOY",
+                "0NORWsUtl94lIgJrIb0L8h0ZlFzWyDtr-ZBzxhSrE2U",
+            ),
+            (
+                DerivationPath::protocol_context("bafyrootcontext"),
+                "bovSB9-v23DhPtkKo_AWWTicQA5gBP3rxJdXa_OJ6y4",
+                "ydn9KsSosy6LPrxnlOsSOJZlvrjFgSnqe97CLcclhTI",
+                "fBL5Fuc4xFsCbdGoUTSnqJiruY10oHtDzi5mDDc3Adc",
+            ),
+            (
+                DerivationPath::schemas("https://example.com/schema"),
+                "o01BtW46kJ4K5CFMDuUL_Gg2KqIMgLBV8fg7antVsB4",
+                "L3nH8cDH57csvd1MzbFs4Vr9YdWjBYSrQoGvtltt4DY",
+                "MWEX4SaeFILGZtWhxfrVOCvMSBAWWZF1sDwkcPZ7t0A",
+            ),
+            (
+                DerivationPath::data_formats(Some("https://example.com/schema"), "text/plain"),
+                "JPrlPjwoPXQHkgxvSuA0CNnoIFxJgYsGD30iFG6IWaQ",
+                "qx42cEHH6a83MY6E_K784vmX7rMwAlae85gvmniUyBs",
+                "ykUq5TIgv_79AqVdiw5H2CpOuUBetXqmt63A-xkx0NQ",
+            ),
+        ];
+
+        for (path, private_key, x, y) in tcs {
+            let segments = path
+                .segments()
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<&str>>();
+            let derived = DerivedPrivateJWK::derive_secret(&root, &segments).unwrap();
+            assert_eq!(base64url.encode(derived.to_bytes()), private_key);
+
+            let public_key = serde_json::to_value(derived.public_key().jwk()).unwrap();
+            assert_eq!(public_key["x"], x);
+            assert_eq!(public_key["y"], y);
+        }
+    }
+
+    #[test]
+    fn test_derive_path() {
+        let root_key = || DerivedPrivateJWK {
+            root_key_id: "root_key_id".to_string(),
+            scheme: DerivationScheme::ProtocolPath,
+            path: None,
+            key: JWK::generate_secp256k1(),
+        };
+        let root = root_key();
+        let path = DerivationPath::protocol_path("https://example.com/chat", "thread/message");
+
+        // the leaf key is the same whether derived from the root key, or an ancestor of the leaf.
+        let from_root = DerivedPrivateJWK::derive_path(
+            DerivedPrivateJWK {
+                key: root.key.clone(),
+                ..root_key()
+            },
+            &path,
+        )
+        .unwrap();
+        let ancestor = DerivedPrivateJWK::derive(
+            DerivedPrivateJWK {
+                key: root.key.clone(),
+                ..root_key()
+            },
+            vec!["protocolPath".to_string()],
+        )
+        .unwrap();
+        let from_ancestor = DerivedPrivateJWK::derive_path(ancestor, &path).unwrap();
+
+        assert_eq!(from_root.key, from_ancestor.key);
+        assert_eq!(from_root.path.as_deref(), Some(path.segments()));
+
+        let unrelated = DerivedPrivateJWK::derive(root, vec!["schemas".to_string()]).unwrap();
+        assert!(matches!(
+            DerivedPrivateJWK::derive_path(unrelated, &path),
+            Err(Error::NotAncestor)
+        ));
+    }
+
+    // the paths constructed by dwn-sdk-js for each of the derivation schemes.
+    #[test]
+    fn test_derivation_path() {
+        let tcs = vec![
+            (
+                DerivationPath::protocol_path("https://example.com/chat", "thread/message"),
+                DerivationScheme::ProtocolPath,
+                vec![
+                    "protocolPath",
+                    "https://example.com/chat",
+                    "thread",
+                    "message",
+                ],
+            ),
+            (
+                DerivationPath::protocol_context("bafyrootcontext/bafychild/bafygrandchild"),
+                DerivationScheme::ProtocolContext,
+                vec!["protocolContext", "bafyrootcontext"],
+            ),
+            (
+                DerivationPath::schemas("https://example.com/schema"),
+                DerivationScheme::Schemas,
+                vec!["schemas", "https://example.com/schema"],
+            ),
+            (
+                DerivationPath::data_formats(Some("https://example.com/schema"), "text/plain"),
+                DerivationScheme::DataFormats,
+                vec!["dataFormats", "https://example.com/schema", "text/plain"],
+            ),
+            (
+                DerivationPath::data_formats(None, "application/json"),
+                DerivationScheme::DataFormats,
+                vec!["dataFormats", "application/json"],
+            ),
+        ];
+
+        for (path, scheme, segments) in tcs {
+            assert_eq!(path.scheme(), &scheme);
+            assert_eq!(path.segments(), segments.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_derivation_path_record() {
+        let descriptor = RecordsWriteDescriptor {
+            protocol: Some("https://example.com/chat".to_string()),
+            protocol_path: Some("thread".to_string()),
+            data_format: "application/json".to_string(),
+            ..Default::default()
+        };
+
+        let path =
+            DerivationPath::record(&DerivationScheme::ProtocolPath, &descriptor, None).unwrap();
+        assert_eq!(
+            path,
+            DerivationPath::protocol_path("https://example.com/chat", "thread")
+        );
+
+        let path = DerivationPath::record(
+            &DerivationScheme::ProtocolContext,
+            &descriptor,
+            Some("root/child"),
+        )
+        .unwrap();
+        assert_eq!(path, DerivationPath::protocol_context("root"));
+
+        let path =
+            DerivationPath::record(&DerivationScheme::DataFormats, &descriptor, None).unwrap();
+        assert_eq!(path, DerivationPath::data_formats(None, "application/json"));
+
+        assert!(matches!(
+            DerivationPath::record(&DerivationScheme::Schemas, &descriptor, None),
+            Err(Error::MissingPathProperty(_))
+        ));
+        assert!(matches!(
+            DerivationPath::record(&DerivationScheme::ProtocolContext, &descriptor, None),
+            Err(Error::MissingPathProperty(_))
+        ));
+
+        let path = DerivationPath::schemas("https://example.com/schema");
+        assert_eq!(
+            path.descendant_of(&["schemas".to_string()]),
+            Some(vec!["https://example.com/schema".to_string()])
+        );
+        assert_eq!(path.descendant_of(&["dataFormats".to_string()]), None);
+    }
 }
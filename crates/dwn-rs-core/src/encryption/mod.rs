@@ -1,11 +1,13 @@
 pub mod asymmetric;
 pub mod errors;
 pub mod hd_keys;
+pub mod record;
 pub mod symmetric;
 
 pub use asymmetric::secretkey::SecretKey;
 pub use errors::Error;
-pub use hd_keys::{DerivedPrivateJWK, HashAlgorithm};
+pub use hd_keys::{DerivationPath, DerivedPrivateJWK, HashAlgorithm};
+pub use record::{decrypt_record, RecordEncryptor};
 
 use serde::{Deserialize, Serialize};
 use ssi_jwk::JWK;
@@ -23,6 +25,19 @@ pub enum DerivationScheme {
     Schemas,
 }
 
+impl DerivationScheme {
+    /// as_str returns the name of the scheme, which is the first segment of the derivation paths
+    /// of the scheme.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DerivationScheme::DataFormats => "dataFormats",
+            DerivationScheme::ProtocolContext => "protocolContext",
+            DerivationScheme::ProtocolPath => "protocolPath",
+            DerivationScheme::Schemas => "schemas",
+        }
+    }
+}
+
 /// KeyEncryptionAlgorithm represents the key encryption algorithm used for encrypting keys.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(untagged)]
@@ -41,7 +56,7 @@ pub enum KeyEncryptionAlgorithmAsymmetric {
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub enum KeyEncryptionAlgorithmSymmetric {
-    #[serde(rename = "A256CTR")] // dwn-sdk-js does aes-gcm but calls the property aes-ctr
+    #[serde(rename = "A256CTR")] // dwn-sdk-js encrypts record data with AES-256-CTR
     AES256CTR,
     #[serde(rename = "A256GCM")]
     AES256GCM,
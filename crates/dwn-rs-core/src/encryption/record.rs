@@ -0,0 +1,526 @@
+use std::pin::Pin;
+
+use aes::cipher::generic_array::GenericArray;
+use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD as base64url};
+use bytes::{Bytes, BytesMut};
+use futures_util::{stream, Stream, StreamExt, TryStreamExt};
+use typenum::Unsigned;
+
+use crate::{
+    descriptors::{
+        records::{EncryptionInput, KeyEncryptionInput, WriteDescriptor, WriteParameters},
+        MessageParameters,
+    },
+    Message,
+};
+
+use super::{
+    asymmetric::{self, publickey::PublicKey},
+    symmetric::{
+        self,
+        aead::{XSalsa20Poly1305, AES256GCM},
+        stream::AES256CTR,
+        Encryption, IVEncryption, StreamEncryptionExt,
+    },
+    DerivationPath, DerivedPrivateJWK, Error, KeyEncryptionAlgorithm,
+    KeyEncryptionAlgorithmSymmetric, SecretKey,
+};
+
+/// DecryptedData is the stream of decrypted record data.
+pub type DecryptedData = Pin<Box<dyn Stream<Item = Result<Bytes, Error>> + Send>>;
+
+/// RecordEncryptor encrypts the data of a record for a set of recipients. The data is encrypted
+/// with a newly generated symmetric key and IV, and the key is encrypted to each recipient when
+/// the RecordsWrite is built from the returned parameters. Data is encrypted with AES-256-CTR,
+/// as by dwn-sdk-js, unless another algorithm is given with [`RecordEncryptor::with_algorithm`].
+///
+/// The public key of each recipient is the key derived from the recipient's root key along the
+/// derivation path of the record for the recipient's derivation scheme. Recipients given with
+/// [`RecordEncryptor::with_key`] have their leaf key derived when the data is encrypted.
+pub struct RecordEncryptor {
+    algorithm: KeyEncryptionAlgorithmSymmetric,
+    key: Vec<u8>,
+    initialization_vector: Vec<u8>,
+    recipients: Vec<KeyEncryptionInput>,
+    keys: Vec<DerivedPrivateJWK>,
+}
+
+impl RecordEncryptor {
+    pub fn new(recipients: Vec<KeyEncryptionInput>) -> Result<Self, Error> {
+        Self::with_algorithm(recipients, KeyEncryptionAlgorithmSymmetric::AES256CTR)
+    }
+
+    pub fn with_algorithm(
+        recipients: Vec<KeyEncryptionInput>,
+        algorithm: KeyEncryptionAlgorithmSymmetric,
+    ) -> Result<Self, Error> {
+        let key: [u8; 32] = rand::random();
+        let initialization_vector = match algorithm {
+            KeyEncryptionAlgorithmSymmetric::AES256CTR => nonce::<AES256CTR>(&key)?,
+            KeyEncryptionAlgorithmSymmetric::AES256GCM => nonce::<AES256GCM>(&key)?,
+            KeyEncryptionAlgorithmSymmetric::XSalsa20Poly1305 => nonce::<XSalsa20Poly1305>(&key)?,
+        };
+
+        Ok(Self {
+            algorithm,
+            key: key.to_vec(),
+            initialization_vector,
+            recipients,
+            keys: Vec::new(),
+        })
+    }
+
+    /// with_key adds a recipient by its root key, or a key derived from it. The recipient's
+    /// public key is derived from the key along the derivation path of the record for the key's
+    /// derivation scheme.
+    pub fn with_key(mut self, key: DerivedPrivateJWK) -> Self {
+        self.keys.push(key);
+        self
+    }
+
+    /// encryption_input returns the encryption input of the RecordsWrite, with which the key is
+    /// encrypted to each of the recipients.
+    pub fn encryption_input(&self) -> EncryptionInput {
+        EncryptionInput {
+            algorithm: Some(KeyEncryptionAlgorithm::Symmetric(self.algorithm.clone())),
+            initialization_vector: self.initialization_vector.clone(),
+            key: self.key.clone(),
+            key_encryption_input: self.recipients.clone(),
+        }
+    }
+
+    /// encrypt encrypts the record data, returning the parameters with the encrypted data, its
+    /// size, and the encryption input set. The encryptor is consumed, as its key and IV must
+    /// only be used once. The `dataCid` of the record is the CID of the
+    /// encrypted data.
+    pub async fn encrypt<S>(
+        mut self,
+        data: S,
+        parameters: WriteParameters,
+    ) -> Result<WriteParameters, Error>
+    where
+        S: Stream<Item = Bytes> + Unpin,
+    {
+        if !self.keys.is_empty() {
+            let (descriptor, _) = parameters.build().await?;
+            for key in std::mem::take(&mut self.keys) {
+                let key_id = key.root_key_id.clone();
+                let path = DerivationPath::record(
+                    &key.scheme,
+                    &descriptor,
+                    parameters.derivation_context_id(),
+                )?;
+                let leaf_key: SecretKey =
+                    DerivedPrivateJWK::derive_path(key, &path)?.key.try_into()?;
+
+                self.recipients.push(KeyEncryptionInput {
+                    derivation_schema: path.scheme().clone(),
+                    public_key_id: key_id,
+                    public_key: leaf_key.public_key().jwk(),
+                    algorithm: None,
+                });
+            }
+        }
+
+        let (key, iv) = (&self.key, &self.initialization_vector);
+        // AEAD ciphers authenticate all of the data with a single tag, so the data is encrypted
+        // as one chunk, while AES-CTR encrypts each chunk as it is read.
+        let ciphertext = match self.algorithm {
+            KeyEncryptionAlgorithmSymmetric::AES256CTR => {
+                encrypt_data::<AES256CTR, _>(key, iv, data.map(Ok)).await?
+            }
+            KeyEncryptionAlgorithmSymmetric::AES256GCM => {
+                let data = stream::iter([Ok(collect(data).await)]);
+                encrypt_data::<AES256GCM, _>(key, iv, data).await?
+            }
+            KeyEncryptionAlgorithmSymmetric::XSalsa20Poly1305 => {
+                let data = stream::iter([Ok(collect(data).await)]);
+                encrypt_data::<XSalsa20Poly1305, _>(key, iv, data).await?
+            }
+        };
+
+        Ok(WriteParameters {
+            data_cid: None,
+            data_size: Some(ciphertext.len() as u64),
+            data: Some(ciphertext),
+            encryption_input: Some(self.encryption_input()),
+            ..parameters
+        })
+    }
+}
+
+/// decrypt_record decrypts the data of an encrypted record with a private key derived from the
+/// recipient's root key. The key encryption for the key's root key ID and derivation scheme is
+/// decrypted with the leaf key of the record, derived from the given key along the record's
+/// derivation path, and the data is decrypted with the decrypted key.
+///
+/// Data encrypted with AES-CTR is decrypted as it is streamed, while data encrypted with an AEAD
+/// cipher is read in full before it is decrypted, since it can't be authenticated until then.
+pub async fn decrypt_record<S>(
+    message: &Message<WriteDescriptor>,
+    key: DerivedPrivateJWK,
+    data: S,
+) -> Result<DecryptedData, Error>
+where
+    S: Stream<Item = Bytes> + Unpin + Send + 'static,
+{
+    let encryption = message
+        .fields
+        .encryption
+        .as_ref()
+        .ok_or_else(|| Error::DecryptionError("the record is not encrypted".to_string()))?;
+    let key_encryption = encryption
+        .key_encryption
+        .iter()
+        .find(|key_encryption| {
+            key_encryption.root_key_id == key.root_key_id
+                && key_encryption.derivation_scheme == key.scheme
+        })
+        .ok_or_else(|| {
+            Error::DecryptionError(format!(
+                "no key encryption for root key {} with the {:?} scheme",
+                key.root_key_id, key.scheme
+            ))
+        })?;
+
+    let path = DerivationPath::record(
+        &key.scheme,
+        &message.descriptor,
+        message.fields.context_id.as_deref(),
+    )?;
+    let leaf_key: SecretKey = DerivedPrivateJWK::derive_path(key, &path)?.key.try_into()?;
+
+    // the encrypted key is unwrapped from the ECIES ciphertext it was encrypted into.
+    let ephemeral_public_key = PublicKey::try_from(key_encryption.ephemeral_public_key.clone())
+        .map_err(asymmetric::Error::from)?;
+    let ciphertext = [
+        ephemeral_public_key.to_bytes(),
+        base64url.decode(&key_encryption.initialization_vector)?,
+        base64url.decode(&key_encryption.encrypted_key)?,
+        base64url.decode(&key_encryption.message_authentication_code)?,
+    ]
+    .concat();
+    let data_key = leaf_key.decrypt(&ciphertext)?;
+
+    let iv = base64url.decode(&encryption.initialization_vector)?;
+    match &encryption.algorithm {
+        KeyEncryptionAlgorithm::Symmetric(KeyEncryptionAlgorithmSymmetric::AES256CTR) => {
+            decrypt_data::<AES256CTR, _>(&data_key, &iv, data.map(Ok))
+        }
+        KeyEncryptionAlgorithm::Symmetric(KeyEncryptionAlgorithmSymmetric::AES256GCM) => {
+            let data = stream::iter([Ok(collect(data).await)]);
+            decrypt_data::<AES256GCM, _>(&data_key, &iv, data)
+        }
+        KeyEncryptionAlgorithm::Symmetric(KeyEncryptionAlgorithmSymmetric::XSalsa20Poly1305) => {
+            let data = stream::iter([Ok(collect(data).await)]);
+            decrypt_data::<XSalsa20Poly1305, _>(&data_key, &iv, data)
+        }
+        algorithm => Err(Error::DecryptionError(format!(
+            "unsupported encryption algorithm: {:?}",
+            algorithm
+        ))),
+    }
+}
+
+/// nonce generates a new IV for the encryption algorithm.
+fn nonce<E: IVEncryption>(key: &[u8]) -> Result<Vec<u8>, Error> {
+    Ok(E::new(GenericArray::clone_from_slice(key))?
+        .nonce()
+        .to_vec())
+}
+
+/// encrypt_data encrypts the chunks of data with the key and IV, into a single buffer.
+async fn encrypt_data<E, D>(key: &[u8], iv: &[u8], data: D) -> Result<Vec<u8>, Error>
+where
+    E: IVEncryption,
+    D: Stream<Item = Result<Bytes, symmetric::Error>>,
+{
+    let ciphertext = data
+        .encrypt::<E>(GenericArray::clone_from_slice(key))?
+        .with_iv(GenericArray::clone_from_slice(iv))?
+        .try_fold(Vec::new(), |mut buf, chunk| async move {
+            buf.extend_from_slice(&chunk);
+            Ok(buf)
+        })
+        .await?;
+
+    Ok(ciphertext)
+}
+
+/// decrypt_data decrypts the chunks of data with the key and IV.
+fn decrypt_data<E, D>(key: &[u8], iv: &[u8], data: D) -> Result<DecryptedData, Error>
+where
+    E: IVEncryption + Send + 'static,
+    D: Stream<Item = Result<Bytes, symmetric::Error>> + Send + 'static,
+{
+    if key.len() != <E::KeySize as Unsigned>::USIZE || iv.len() != <E::NonceSize as Unsigned>::USIZE
+    {
+        return Err(Error::DecryptionError(
+            "invalid key or initialization vector length".to_string(),
+        ));
+    }
+
+    let decrypted = data
+        .decrypt::<E>(GenericArray::clone_from_slice(key))?
+        .with_iv(GenericArray::clone_from_slice(iv))?
+        .map_err(Error::from);
+
+    Ok(Box::pin(decrypted))
+}
+
+/// collect collects a stream of data into a single buffer.
+async fn collect<S>(data: S) -> Bytes
+where
+    S: Stream<Item = Bytes> + Unpin,
+{
+    data.fold(BytesMut::new(), |mut buf, chunk| async move {
+        buf.extend_from_slice(&chunk);
+        buf
+    })
+    .await
+    .freeze()
+}
+
+#[cfg(test)]
+mod test {
+    use ssi_jwk::JWK;
+
+    use super::*;
+    use crate::encryption::{DerivationScheme, KeyEncryptionAlgorithmAsymmetric};
+
+    #[tokio::test]
+    async fn test_record_encryptor() {
+        let recipient = JWK::generate_secp256k1();
+        let encryptor = RecordEncryptor::new(vec![KeyEncryptionInput {
+            derivation_schema: DerivationScheme::Schemas,
+            public_key_id: "root-key".to_string(),
+            public_key: recipient.to_public(),
+            algorithm: Some(KeyEncryptionAlgorithm::Asymmetric(
+                KeyEncryptionAlgorithmAsymmetric::EciesSecp256k1,
+            )),
+        }])
+        .unwrap();
+
+        let plaintext = b"Hello, world!";
+        let parameters = encryptor
+            .encrypt(
+                stream::iter([
+                    Bytes::from_static(&plaintext[..5]),
+                    Bytes::from_static(&plaintext[5..]),
+                ]),
+                WriteParameters {
+                    schema: Some("https://example.com/schema".to_string()),
+                    data_format: "text/plain".to_string(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let ciphertext = parameters.data.clone().unwrap();
+        assert_ne!(ciphertext.as_slice(), plaintext.as_slice());
+        assert_eq!(parameters.data_size, Some(ciphertext.len() as u64));
+
+        let input = parameters.encryption_input.clone().unwrap();
+        let decrypted = AES256CTR::new(GenericArray::clone_from_slice(&input.key))
+            .unwrap()
+            .with_iv(GenericArray::clone_from_slice(&input.initialization_vector))
+            .unwrap()
+            .decrypt(&mut BytesMut::from(ciphertext.as_slice()))
+            .unwrap();
+        assert_eq!(decrypted.as_ref(), plaintext.as_slice());
+
+        let (_, fields) = parameters.build().await.unwrap();
+        let encryption = fields.unwrap().encryption.unwrap();
+        assert_eq!(
+            encryption.algorithm,
+            KeyEncryptionAlgorithm::Symmetric(KeyEncryptionAlgorithmSymmetric::AES256CTR)
+        );
+        assert_eq!(encryption.key_encryption.len(), 1);
+        assert_eq!(encryption.key_encryption[0].root_key_id, "root-key");
+        assert_eq!(
+            encryption.key_encryption[0].derivation_scheme,
+            DerivationScheme::Schemas
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_encryptor_with_key() {
+        let root_jwk = JWK::generate_secp256k1();
+        let context_key = || DerivedPrivateJWK {
+            scheme: DerivationScheme::ProtocolContext,
+            ..root_key(root_jwk.clone())
+        };
+        let parameters = WriteParameters {
+            protocol: Some("https://example.com/chat".to_string()),
+            protocol_path: Some("thread/message".to_string()),
+            data_format: "text/plain".to_string(),
+            ..Default::default()
+        };
+        let data = || stream::iter([Bytes::from_static(b"Hello, world!")]);
+
+        // the context of a new root record isn't known until the record is signed.
+        let encryptor = RecordEncryptor::new(vec![])
+            .unwrap()
+            .with_key(context_key());
+        assert!(matches!(
+            encryptor.encrypt(data(), parameters.clone()).await,
+            Err(Error::DeriveKeyError(
+                crate::encryption::hd_keys::Error::MissingPathProperty(_)
+            ))
+        ));
+
+        // the recipient's public key is the leaf key of the record's root context.
+        let encryptor = RecordEncryptor::new(vec![])
+            .unwrap()
+            .with_key(context_key());
+        let parameters = encryptor
+            .encrypt(
+                data(),
+                WriteParameters {
+                    parent_context_id: Some("bafyroot/bafyparent".to_string()),
+                    ..parameters
+                },
+            )
+            .await
+            .unwrap();
+        let input = parameters.encryption_input.clone().unwrap();
+        let leaf_key = DerivedPrivateJWK::derive_public_key(
+            context_key(),
+            DerivationPath::protocol_context("bafyroot").into(),
+        )
+        .unwrap();
+        assert_eq!(input.key_encryption_input.len(), 1);
+        assert_eq!(input.key_encryption_input[0].public_key, leaf_key);
+        assert_eq!(input.key_encryption_input[0].public_key_id, "root-key");
+
+        let (_, fields) = parameters.build().await.unwrap();
+        let encryption = fields.unwrap().encryption.unwrap();
+        assert_eq!(
+            encryption.key_encryption[0].derived_public_key,
+            Some(leaf_key)
+        );
+    }
+
+    fn root_key(key: JWK) -> DerivedPrivateJWK {
+        DerivedPrivateJWK {
+            root_key_id: "root-key".to_string(),
+            scheme: DerivationScheme::Schemas,
+            path: None,
+            key,
+        }
+    }
+
+    /// encrypted_record returns an encrypted record with the schema, and its encrypted data.
+    async fn encrypted_record(
+        root_jwk: &JWK,
+        schema: &str,
+        algorithm: KeyEncryptionAlgorithmSymmetric,
+        plaintext: &'static [u8],
+    ) -> (Message<WriteDescriptor>, Bytes) {
+        let encryptor = RecordEncryptor::with_algorithm(vec![], algorithm)
+            .unwrap()
+            .with_key(root_key(root_jwk.clone()));
+
+        let parameters = encryptor
+            .encrypt(
+                stream::iter([Bytes::from_static(plaintext)]),
+                WriteParameters {
+                    schema: Some(schema.to_string()),
+                    data_format: "text/plain".to_string(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let ciphertext = Bytes::from(parameters.data.clone().unwrap());
+        let (descriptor, fields) = parameters.build().await.unwrap();
+
+        (
+            Message {
+                descriptor,
+                fields: fields.unwrap(),
+            },
+            ciphertext,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_record() {
+        let schema = "https://example.com/schema";
+        let root_jwk = JWK::generate_secp256k1();
+        let plaintext = b"Hello, world!";
+        let (message, ciphertext) = encrypted_record(
+            &root_jwk,
+            schema,
+            KeyEncryptionAlgorithmSymmetric::AES256CTR,
+            plaintext,
+        )
+        .await;
+
+        // the record can be decrypted with the root key, or any of the leaf key's ancestors.
+        let keys = [
+            root_key(root_jwk.clone()),
+            DerivedPrivateJWK::derive(
+                root_key(root_jwk.clone()),
+                vec![DerivationScheme::Schemas.as_str().to_string()],
+            )
+            .unwrap(),
+        ];
+        for key in keys {
+            let decrypted = decrypt_record(&message, key, stream::iter([ciphertext.clone()]))
+                .await
+                .unwrap()
+                .try_collect::<Vec<Bytes>>()
+                .await
+                .unwrap()
+                .concat();
+            assert_eq!(decrypted, plaintext);
+        }
+
+        let unknown = DerivedPrivateJWK {
+            root_key_id: "unknown-key".to_string(),
+            ..root_key(root_jwk)
+        };
+        assert!(matches!(
+            decrypt_record(&message, unknown, stream::iter([ciphertext])).await,
+            Err(Error::DecryptionError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_record_algorithms() {
+        let schema = "https://example.com/schema";
+        let root_jwk = JWK::generate_secp256k1();
+        let plaintext = b"Hello, world! This record is longer than one AES block.";
+
+        for algorithm in [
+            KeyEncryptionAlgorithmSymmetric::AES256CTR,
+            KeyEncryptionAlgorithmSymmetric::AES256GCM,
+            KeyEncryptionAlgorithmSymmetric::XSalsa20Poly1305,
+        ] {
+            let (message, ciphertext) =
+                encrypted_record(&root_jwk, schema, algorithm.clone(), plaintext).await;
+            assert_eq!(
+                message.fields.encryption.as_ref().unwrap().algorithm,
+                KeyEncryptionAlgorithm::Symmetric(algorithm)
+            );
+
+            // the data is streamed in chunks which aren't aligned to the AES block size.
+            let chunks = [
+                ciphertext.slice(..7),
+                ciphertext.slice(7..23),
+                ciphertext.slice(23..),
+            ];
+            let decrypted =
+                decrypt_record(&message, root_key(root_jwk.clone()), stream::iter(chunks))
+                    .await
+                    .unwrap()
+                    .try_collect::<Vec<Bytes>>()
+                    .await
+                    .unwrap()
+                    .concat();
+            assert_eq!(decrypted, plaintext);
+        }
+    }
+}
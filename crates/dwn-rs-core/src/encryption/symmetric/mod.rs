@@ -7,11 +7,14 @@ use pin_project_lite::pin_project;
 use thiserror::Error;
 
 pub mod aead;
+pub mod stream;
 
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("AEAD encryption error: {0}")]
     AEAD(#[from] aead::Error),
+    #[error("Stream cipher encryption error: {0}")]
+    Stream(#[from] stream::Error),
 }
 
 impl<T: ?Sized> StreamEncryptionExt for T where T: Stream {}
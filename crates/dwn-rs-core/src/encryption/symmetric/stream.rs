@@ -0,0 +1,166 @@
+use aes::{
+    cipher::{generic_array::GenericArray, IvSizeUser, KeyIvInit, KeySizeUser, StreamCipher},
+    Aes256,
+};
+use bytes::{Bytes, BytesMut};
+use ctr::Ctr128BE;
+use thiserror::Error;
+use typenum::U0;
+
+use super::{Encryption, IVEncryption};
+
+/// Keystream encrypts data with a stream cipher. The position in the keystream is kept between
+/// calls, so data may be encrypted or decrypted in chunks of any size, as long as the chunks are
+/// given in order. Since encryption and decryption share the keystream, an instance should only
+/// be used for one or the other.
+pub struct Keystream<C: KeyIvInit> {
+    key: GenericArray<u8, C::KeySize>,
+    cipher: Option<C>,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("stream cipher error: {0}")]
+    StreamCipherError(aes::cipher::StreamCipherError),
+    #[error("stream cipher Initialization Vector error")]
+    NoIVError,
+}
+
+impl<C: KeyIvInit + StreamCipher> Keystream<C> {
+    fn apply_keystream(&mut self, data: &mut BytesMut) -> Result<Bytes, super::Error> {
+        let cipher = self.cipher.as_mut().ok_or(Error::NoIVError)?;
+        cipher
+            .try_apply_keystream(data.as_mut())
+            .map_err(Error::StreamCipherError)?;
+
+        Ok(data.clone().freeze())
+    }
+}
+
+impl<C: KeyIvInit + StreamCipher> Encryption for Keystream<C> {
+    type KeySize = <C as KeySizeUser>::KeySize;
+    type TagSize = U0;
+
+    fn new(key: GenericArray<u8, Self::KeySize>) -> Result<Self, super::Error> {
+        Ok(Self { key, cipher: None })
+    }
+
+    fn encrypt(&mut self, data: &mut BytesMut) -> Result<Bytes, super::Error> {
+        self.apply_keystream(data)
+    }
+
+    fn decrypt(&mut self, data: &mut BytesMut) -> Result<Bytes, super::Error> {
+        self.apply_keystream(data)
+    }
+}
+
+impl<C: KeyIvInit + StreamCipher> IVEncryption for Keystream<C> {
+    type NonceSize = <C as IvSizeUser>::IvSize;
+
+    // the IV is the initial counter block, and starts a new keystream.
+    fn with_iv(&mut self, iv: GenericArray<u8, Self::NonceSize>) -> Result<Self, super::Error> {
+        Ok(Self {
+            key: self.key.clone(),
+            cipher: Some(C::new(&self.key, &iv)),
+        })
+    }
+}
+
+/// AES256CTR is AES-256 in counter mode, with the whole 16 byte IV used as a big-endian counter,
+/// as with the A256CTR algorithm of dwn-sdk-js.
+pub type AES256CTR = Keystream<Ctr128BE<Aes256>>;
+
+#[cfg(test)]
+mod test {
+    use futures_util::{stream, TryStreamExt};
+
+    use super::*;
+    use crate::encryption::symmetric::{self, StreamEncryptionExt};
+
+    // F.5.5 CTR-AES256.Encrypt, NIST SP 800-38A
+    const KEY: [u8; 32] = [
+        0x60, 0x3d, 0xeb, 0x10, 0x15, 0xca, 0x71, 0xbe, 0x2b, 0x73, 0xae, 0xf0, 0x85, 0x7d, 0x77,
+        0x81, 0x1f, 0x35, 0x2c, 0x07, 0x3b, 0x61, 0x08, 0xd7, 0x2d, 0x98, 0x10, 0xa3, 0x09, 0x14,
+        0xdf, 0xf4,
+    ];
+    const IV: [u8; 16] = [
+        0xf0, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa, 0xfb, 0xfc, 0xfd, 0xfe,
+        0xff,
+    ];
+    const PLAINTEXT: [u8; 64] = [
+        0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93, 0x17,
+        0x2a, 0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c, 0x9e, 0xb7, 0x6f, 0xac, 0x45, 0xaf,
+        0x8e, 0x51, 0x30, 0xc8, 0x1c, 0x46, 0xa3, 0x5c, 0xe4, 0x11, 0xe5, 0xfb, 0xc1, 0x19, 0x1a,
+        0x0a, 0x52, 0xef, 0xf6, 0x9f, 0x24, 0x45, 0xdf, 0x4f, 0x9b, 0x17, 0xad, 0x2b, 0x41, 0x7b,
+        0xe6, 0x6c, 0x37, 0x10,
+    ];
+    const CIPHERTEXT: [u8; 64] = [
+        0x60, 0x1e, 0xc3, 0x13, 0x77, 0x57, 0x89, 0xa5, 0xb7, 0xa7, 0xf5, 0x04, 0xbb, 0xf3, 0xd2,
+        0x28, 0xf4, 0x43, 0xe3, 0xca, 0x4d, 0x62, 0xb5, 0x9a, 0xca, 0x84, 0xe9, 0x90, 0xca, 0xca,
+        0xf5, 0xc5, 0x2b, 0x09, 0x30, 0xda, 0xa2, 0x3d, 0xe9, 0x4c, 0xe8, 0x70, 0x17, 0xba, 0x2d,
+        0x84, 0x98, 0x8d, 0xdf, 0xc9, 0xc5, 0x8d, 0xb6, 0x7a, 0xad, 0xa6, 0x13, 0xc2, 0xdd, 0x08,
+        0x45, 0x79, 0x41, 0xa6,
+    ];
+
+    fn cipher() -> AES256CTR {
+        AES256CTR::new(KEY.into())
+            .unwrap()
+            .with_iv(IV.into())
+            .expect("IV error")
+    }
+
+    #[test]
+    fn test_aes256ctr() {
+        let enc_data = cipher()
+            .encrypt(&mut BytesMut::from(PLAINTEXT.as_slice()))
+            .unwrap();
+        assert_eq!(enc_data.as_ref(), CIPHERTEXT.as_slice());
+
+        let dec_data = cipher()
+            .decrypt(&mut BytesMut::from(CIPHERTEXT.as_slice()))
+            .unwrap();
+        assert_eq!(dec_data.as_ref(), PLAINTEXT.as_slice());
+    }
+
+    #[test]
+    fn test_aes256ctr_no_iv() {
+        let mut enc = AES256CTR::new(KEY.into()).unwrap();
+
+        let data = BytesMut::from("Hello, world!");
+
+        assert!(enc.encrypt(&mut data.clone()).is_err());
+        assert!(enc.decrypt(&mut data.clone()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_aes256ctr_chunks() {
+        // chunks which are not aligned to the cipher's block size continue the counter from
+        // where the previous chunk ended.
+        let chunks = [&PLAINTEXT[..5], &PLAINTEXT[5..21], &PLAINTEXT[21..]]
+            .map(|chunk| Ok::<_, symmetric::Error>(Bytes::copy_from_slice(chunk)));
+
+        let enc_data = stream::iter(chunks)
+            .encrypt::<AES256CTR>(KEY.into())
+            .unwrap()
+            .with_iv(IV.into())
+            .unwrap()
+            .try_collect::<Vec<Bytes>>()
+            .await
+            .unwrap()
+            .concat();
+        assert_eq!(enc_data, CIPHERTEXT);
+
+        let chunks = [&CIPHERTEXT[..33], &CIPHERTEXT[33..34], &CIPHERTEXT[34..]]
+            .map(|chunk| Ok::<_, symmetric::Error>(Bytes::copy_from_slice(chunk)));
+        let dec_data = stream::iter(chunks)
+            .decrypt::<AES256CTR>(KEY.into())
+            .unwrap()
+            .with_iv(IV.into())
+            .unwrap()
+            .try_collect::<Vec<Bytes>>()
+            .await
+            .unwrap()
+            .concat();
+        assert_eq!(dec_data, PLAINTEXT);
+    }
+}
@@ -3,7 +3,7 @@ use std::{collections::TryReserveError, convert::Infallible};
 use thiserror::Error;
 use ulid::MonotonicError;
 
-use crate::{FilterError, QueryError};
+use crate::{auth::JwsError, descriptors::ValidationError, FilterError, QueryError};
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -24,6 +24,9 @@ pub enum Error {
 
     #[error("error processing event stream: {0}")]
     EventStreamError(#[from] EventStreamError),
+
+    #[error("error handling message: {0}")]
+    HandlerError(#[from] HandlerError),
 }
 
 #[derive(Error, Debug)]
@@ -127,3 +130,153 @@ pub enum EventStreamError {
     #[error("actor error: {0}")]
     ActorError(#[from] xtra::Error),
 }
+
+/// ProtocolAuthorizationError represents an error authorizing a message against the rules of a
+/// protocol definition.
+#[derive(Error, Debug)]
+pub enum ProtocolAuthorizationError {
+    #[error("invalid protocol message: {0}")]
+    InvalidMessage(String),
+
+    #[error("{0}")]
+    Unauthorized(String),
+
+    #[error("invalid authorization: {0}")]
+    AuthorizationError(#[from] JwsError),
+
+    #[error("error querying messages: {0}")]
+    MessageStoreError(#[from] MessageStoreError),
+}
+
+/// GrantAuthorizationError represents an error authorizing a message against the permission
+/// grants it invokes.
+#[derive(Error, Debug)]
+pub enum GrantAuthorizationError {
+    #[error("invalid permission grant: {0}")]
+    InvalidMessage(String),
+
+    #[error("{0}")]
+    Unauthorized(String),
+
+    #[error("invalid permission grant: {0}")]
+    PermissionsError(#[from] PermissionsError),
+
+    #[error("invalid authorization: {0}")]
+    AuthorizationError(#[from] JwsError),
+
+    #[error("error querying messages: {0}")]
+    MessageStoreError(#[from] MessageStoreError),
+}
+
+/// PermissionsError represents an error creating or parsing a permission grant, request or
+/// revocation.
+#[derive(Error, Debug)]
+pub enum PermissionsError {
+    #[error("invalid permissions message: {0}")]
+    InvalidMessage(String),
+
+    #[error("invalid permission scope: {0}")]
+    InvalidScope(String),
+
+    #[error("unable to decode permission data: {0}")]
+    DecodeError(#[from] base64::DecodeError),
+
+    #[error("invalid permission data: {0}")]
+    DataError(#[from] serde_json::Error),
+
+    #[error("invalid authorization: {0}")]
+    AuthorizationError(#[from] JwsError),
+}
+
+/// HandlerError represents an error that occurs while a `Dwn` handles a message. Each error
+/// maps to the status code returned in the reply to the message.
+#[derive(Error, Debug)]
+pub enum HandlerError {
+    #[error("invalid message: {0}")]
+    InvalidMessage(String),
+
+    #[error("invalid message: {0}")]
+    ValidationError(#[from] ValidationError),
+
+    #[error("invalid authorization: {0}")]
+    AuthorizationError(#[from] JwsError),
+
+    #[error("unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("not found")]
+    NotFound,
+
+    #[error("conflict: {0}")]
+    Conflict(String),
+
+    #[error("{interface}{method} is not implemented")]
+    NotImplemented {
+        interface: &'static str,
+        method: &'static str,
+    },
+
+    #[error("error processing message: {0}")]
+    MessageStoreError(#[from] MessageStoreError),
+
+    #[error("error processing data: {0}")]
+    DataStoreError(#[from] DataStoreError),
+
+    #[error("error processing event log: {0}")]
+    EventLogError(#[from] EventLogError),
+
+    #[error("error processing resumable task: {0}")]
+    ResumableTaskError(#[from] ResumableTaskStoreError),
+
+    #[error("error processing event stream: {0}")]
+    EventStreamError(#[from] EventStreamError),
+}
+
+impl HandlerError {
+    /// code returns the reply status code for the error.
+    pub fn code(&self) -> i32 {
+        match self {
+            HandlerError::InvalidMessage(_) | HandlerError::ValidationError(_) => 400,
+            HandlerError::AuthorizationError(_) | HandlerError::Unauthorized(_) => 401,
+            HandlerError::NotFound => 404,
+            HandlerError::Conflict(_) => 409,
+            HandlerError::NotImplemented { .. } => 501,
+            HandlerError::MessageStoreError(_)
+            | HandlerError::DataStoreError(_)
+            | HandlerError::EventLogError(_)
+            | HandlerError::ResumableTaskError(_)
+            | HandlerError::EventStreamError(_) => 500,
+        }
+    }
+}
+
+impl From<ProtocolAuthorizationError> for HandlerError {
+    fn from(err: ProtocolAuthorizationError) -> Self {
+        match err {
+            ProtocolAuthorizationError::InvalidMessage(message) => {
+                HandlerError::InvalidMessage(message)
+            }
+            ProtocolAuthorizationError::Unauthorized(message) => {
+                HandlerError::Unauthorized(message)
+            }
+            ProtocolAuthorizationError::AuthorizationError(err) => err.into(),
+            ProtocolAuthorizationError::MessageStoreError(err) => err.into(),
+        }
+    }
+}
+
+impl From<GrantAuthorizationError> for HandlerError {
+    fn from(err: GrantAuthorizationError) -> Self {
+        match err {
+            GrantAuthorizationError::InvalidMessage(message) => {
+                HandlerError::InvalidMessage(message)
+            }
+            GrantAuthorizationError::Unauthorized(message) => HandlerError::Unauthorized(message),
+            GrantAuthorizationError::PermissionsError(err) => {
+                HandlerError::InvalidMessage(err.to_string())
+            }
+            GrantAuthorizationError::AuthorizationError(err) => err.into(),
+            GrantAuthorizationError::MessageStoreError(err) => err.into(),
+        }
+    }
+}
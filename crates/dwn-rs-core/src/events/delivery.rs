@@ -0,0 +1,422 @@
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::{debug, info, trace};
+use xtra::{Actor, Address, Handler, Mailbox};
+
+use crate::{descriptors::MessageDescriptor, Message};
+
+use super::{Event, EventChannel};
+
+/// DEFAULT_SUBSCRIPTION_CAPACITY is the number of events queued for a subscription, before its
+/// overflow policy is applied.
+pub const DEFAULT_SUBSCRIPTION_CAPACITY: usize = 1024;
+
+/// OverflowPolicy decides what happens to events emitted for a subscription whose queue is full,
+/// because its listener is not keeping up with the events.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// DropOldest drops the oldest queued event, to make room for the new event.
+    DropOldest,
+    /// DropNewest drops the new event.
+    DropNewest,
+    /// Disconnect drops the new event and closes the subscription. Listeners which can't miss
+    /// events should resubscribe from the last event they received.
+    #[default]
+    Disconnect,
+}
+
+/// SubscriptionOptions configures how events are queued for a subscription.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriptionOptions {
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for SubscriptionOptions {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_SUBSCRIPTION_CAPACITY,
+            overflow: OverflowPolicy::default(),
+        }
+    }
+}
+
+/// EventStreamOptions configures an event stream. The mailbox capacity bounds the number of
+/// emitted events waiting to be handled by the stream (unbounded if None), applying backpressure
+/// to emitters when it is full. Subscriptions use the given options, unless they are subscribed
+/// with their own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventStreamOptions {
+    pub mailbox_capacity: Option<usize>,
+    pub subscription: SubscriptionOptions,
+}
+
+/// EventStreamMetrics counts the events handled by an event stream.
+#[derive(Debug, Default)]
+pub struct EventStreamMetrics {
+    emitted: AtomicU64,
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+    disconnected: AtomicU64,
+}
+
+impl EventStreamMetrics {
+    /// emitted returns the number of events emitted to the stream.
+    pub fn emitted(&self) -> u64 {
+        self.emitted.load(Ordering::Relaxed)
+    }
+
+    /// delivered returns the number of events delivered to listeners.
+    pub fn delivered(&self) -> u64 {
+        self.delivered.load(Ordering::Relaxed)
+    }
+
+    /// dropped returns the number of events dropped because a subscription's queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// disconnected returns the number of subscriptions removed because their listener
+    /// disconnected, or overflowed with the `Disconnect` policy.
+    pub fn disconnected(&self) -> u64 {
+        self.disconnected.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_emitted(&self) {
+        self.emitted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_disconnected(&self) {
+        self.disconnected.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Enqueued is the result of queueing an event for a subscription.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Enqueued {
+    Queued,
+    Dropped,
+    Overflowed,
+}
+
+/// Queue is the queue of events waiting to be delivered to a subscription's listener, which is
+/// shared with the actor delivering them.
+struct Queue<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + DeserializeOwned + Clone + Debug + PartialEq + Send + 'static,
+{
+    events: Mutex<VecDeque<Event<D>>>,
+    scheduled: AtomicBool,
+    connected: AtomicBool,
+}
+
+impl<D> Queue<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + DeserializeOwned + Clone + Debug + PartialEq + Send + 'static,
+{
+    fn events(&self) -> std::sync::MutexGuard<'_, VecDeque<Event<D>>> {
+        // the queue is never left in an inconsistent state, so a poisoned lock is still usable.
+        self.events.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Delivery delivers the queued events of a subscription to its listener, one at a time, so
+/// that a slow listener only delays its own events.
+pub(crate) struct Delivery<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + DeserializeOwned + Clone + Debug + PartialEq + Send + 'static,
+{
+    id: String,
+    channel: EventChannel<D>,
+    queue: Arc<Queue<D>>,
+    metrics: Arc<EventStreamMetrics>,
+}
+
+/// Deliver wakes a Delivery to deliver the queued events.
+pub(crate) struct Deliver;
+
+impl<D> Actor for Delivery<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + DeserializeOwned + Clone + Debug + PartialEq + Send + 'static,
+{
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {
+        info!(target = "Delivery stopped", id = self.id);
+    }
+}
+
+impl<D> Handler<Deliver> for Delivery<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + DeserializeOwned + Clone + Debug + PartialEq + Send + 'static,
+{
+    type Return = ();
+
+    async fn handle(&mut self, _: Deliver, ctx: &mut xtra::Context<Self>) -> Self::Return {
+        loop {
+            let event = self.queue.events().pop_front();
+            let event = match event {
+                Some(event) => event,
+                None => {
+                    self.queue.scheduled.store(false, Ordering::SeqCst);
+
+                    // an event may have been queued after the queue was found empty, but before
+                    // the delivery was unscheduled.
+                    match self.queue.events().is_empty()
+                        || self.queue.scheduled.swap(true, Ordering::SeqCst)
+                    {
+                        true => return,
+                        false => continue,
+                    }
+                }
+            };
+
+            trace!(id = self.id, "delivering event to listener");
+            match self.channel.send(event).await {
+                Ok(_) => {
+                    self.metrics.delivered.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(err) => {
+                    debug!(id = self.id, error = %err, "listener disconnected");
+                    self.queue.connected.store(false, Ordering::SeqCst);
+                    ctx.stop_self();
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Listener is a subscription's queue of events, and the actor delivering them to its
+/// listener.
+pub(crate) struct Listener<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + DeserializeOwned + Clone + Debug + PartialEq + Send + 'static,
+{
+    id: String,
+    options: SubscriptionOptions,
+    queue: Arc<Queue<D>>,
+    delivery: Address<Delivery<D>>,
+    metrics: Arc<EventStreamMetrics>,
+    dropped: u64,
+}
+
+impl<D> Debug for Listener<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + DeserializeOwned + Clone + Debug + PartialEq + Send + 'static,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Listener")
+            .field("id", &self.id)
+            .field("options", &self.options)
+            .field("dropped", &self.dropped)
+            .finish()
+    }
+}
+
+impl<D> Listener<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + DeserializeOwned + Clone + Debug + PartialEq + Send + 'static,
+{
+    /// new starts the delivery of events to the listener's channel.
+    pub(crate) fn new(
+        id: &str,
+        channel: EventChannel<D>,
+        options: SubscriptionOptions,
+        metrics: Arc<EventStreamMetrics>,
+    ) -> Self {
+        let queue = Arc::new(Queue {
+            events: Mutex::new(VecDeque::new()),
+            scheduled: AtomicBool::new(false),
+            connected: AtomicBool::new(true),
+        });
+
+        let delivery = spawn(Delivery {
+            id: id.to_string(),
+            channel,
+            queue: queue.clone(),
+            metrics: metrics.clone(),
+        });
+
+        Self {
+            id: id.to_string(),
+            options,
+            queue,
+            delivery,
+            metrics,
+            dropped: 0,
+        }
+    }
+
+    /// is_connected returns false once the listener has disconnected.
+    pub(crate) fn is_connected(&self) -> bool {
+        self.queue.connected.load(Ordering::SeqCst) && self.delivery.is_connected()
+    }
+
+    /// enqueue queues an event for delivery, applying the overflow policy if the queue is full.
+    pub(crate) async fn enqueue(&mut self, event: Event<D>) -> Enqueued {
+        let enqueued = {
+            let mut events = self.queue.events();
+            if events.len() < self.options.capacity {
+                events.push_back(event);
+                Enqueued::Queued
+            } else {
+                self.dropped += 1;
+                self.metrics.dropped.fetch_add(1, Ordering::Relaxed);
+
+                match self.options.overflow {
+                    OverflowPolicy::DropOldest => {
+                        events.pop_front();
+                        events.push_back(event);
+                        Enqueued::Dropped
+                    }
+                    OverflowPolicy::DropNewest => Enqueued::Dropped,
+                    OverflowPolicy::Disconnect => Enqueued::Overflowed,
+                }
+            }
+        };
+
+        if enqueued != Enqueued::Queued {
+            debug!(
+                id = self.id,
+                dropped = self.dropped,
+                overflow = ?self.options.overflow,
+                "subscription queue is full",
+            );
+        }
+
+        if enqueued != Enqueued::Overflowed && !self.queue.scheduled.swap(true, Ordering::SeqCst) {
+            // the delivery mailbox is unbounded, so this only waits for the wake to be queued.
+            if self.delivery.send(Deliver).detach().await.is_err() {
+                self.queue.connected.store(false, Ordering::SeqCst);
+            }
+        }
+
+        enqueued
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn spawn<A: Actor<Stop = ()>>(actor: A) -> Address<A> {
+    xtra::spawn_wasm_bindgen(actor, Mailbox::unbounded())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn<A: Actor<Stop = ()>>(actor: A) -> Address<A> {
+    xtra::spawn_tokio(actor, Mailbox::unbounded())
+}
+
+#[cfg(test)]
+mod test {
+    use xtra::prelude::MessageChannel;
+
+    use super::*;
+    use crate::{
+        descriptors::{records, Records},
+        events::MessageEvent,
+        Descriptor, Fields, MapValue,
+    };
+
+    fn test_event(ns: &str) -> Event<Descriptor> {
+        let evt = MessageEvent {
+            message: Message {
+                descriptor: Descriptor::Records(Records::Read(records::ReadDescriptor {
+                    message_timestamp: chrono::Utc::now(),
+                    filter: Default::default(),
+                })),
+                fields: Fields::Authorization(Default::default()),
+            },
+            initial_write: None,
+        };
+
+        (ns.to_string(), evt, MapValue::default())
+    }
+
+    /// Collector is a listener which returns the namespaces of the events it receives, once it
+    /// has received the expected number of events.
+    struct Collector(Vec<String>, usize);
+
+    impl Actor for Collector {
+        type Stop = Vec<String>;
+
+        async fn stopped(self) -> Self::Stop {
+            self.0
+        }
+    }
+
+    impl Handler<Event<Descriptor>> for Collector {
+        type Return = MessageEvent<Descriptor>;
+
+        async fn handle(
+            &mut self,
+            evt: Event<Descriptor>,
+            ctx: &mut xtra::Context<Self>,
+        ) -> Self::Return {
+            self.0.push(evt.0);
+            if self.0.len() == self.1 {
+                ctx.stop_self();
+            }
+
+            evt.1
+        }
+    }
+
+    #[tokio::test]
+    async fn test_overflow_policies() {
+        let tests = [
+            (
+                OverflowPolicy::DropOldest,
+                Enqueued::Dropped,
+                vec!["2".to_string(), "3".to_string()],
+            ),
+            (
+                OverflowPolicy::DropNewest,
+                Enqueued::Dropped,
+                vec!["1".to_string(), "2".to_string()],
+            ),
+            (
+                OverflowPolicy::Disconnect,
+                Enqueued::Overflowed,
+                vec!["1".to_string(), "2".to_string()],
+            ),
+        ];
+
+        for (overflow, expected, delivered) in tests {
+            let metrics = Arc::new(EventStreamMetrics::default());
+            let (addr, mailbox) = Mailbox::unbounded();
+            let options = SubscriptionOptions {
+                capacity: 2,
+                overflow,
+            };
+            let mut listener =
+                Listener::new("test", MessageChannel::new(addr), options, metrics.clone());
+
+            // the listener isn't running yet, so the events are queued until it is.
+            assert_eq!(listener.enqueue(test_event("1")).await, Enqueued::Queued);
+            assert_eq!(listener.enqueue(test_event("2")).await, Enqueued::Queued);
+            assert_eq!(listener.enqueue(test_event("3")).await, expected);
+
+            let received = tokio::spawn(xtra::run(mailbox, Collector(Vec::new(), 2)))
+                .await
+                .unwrap();
+            assert_eq!(received, delivered, "{:?}", overflow);
+            assert_eq!(metrics.dropped(), 1);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 
 use serde::{de::DeserializeOwned, Serialize};
 use xtra::{Address, Mailbox};
@@ -6,17 +6,26 @@ use xtra::{Address, Mailbox};
 use crate::{
     descriptors::MessageDescriptor,
     errors::{EventStreamError, StoreError},
-    MapValue, Message,
+    Filters, MapValue, Message,
 };
 use tracing::{instrument, trace};
 
-use super::{Emit, EventChannel, EventStream, MessageEvent, Shutdown, Subscribe, Subscription};
+use super::{
+    subscription::{EventSubscription, SubscriptionStream},
+    Emit, EventChannel, EventStream, EventStreamMetrics, EventStreamOptions, MessageEvent,
+    Shutdown, Subscribe, Subscription, SubscriptionOptions,
+};
 
 #[derive(Debug, Default)]
-pub struct EventStreamer<D>(Option<Address<EventStream<D>>>)
+pub struct EventStreamer<D>
 where
     Message<D>: Serialize + DeserializeOwned,
-    D: MessageDescriptor + Clone + Debug + PartialEq + Send + 'static;
+    D: MessageDescriptor + Clone + Debug + PartialEq + Send + 'static,
+{
+    addr: Option<Address<EventStream<D>>>,
+    options: EventStreamOptions,
+    metrics: Arc<EventStreamMetrics>,
+}
 
 impl<D> EventStreamer<D>
 where
@@ -24,16 +33,44 @@ where
     D: MessageDescriptor + Clone + Debug + PartialEq + Send + 'static,
 {
     pub fn new() -> Self {
-        Self(None)
+        Self::with_options(EventStreamOptions::default())
+    }
+
+    /// with_options creates an EventStreamer whose stream is opened with the given options.
+    pub fn with_options(options: EventStreamOptions) -> Self {
+        Self {
+            addr: None,
+            options,
+            metrics: Arc::default(),
+        }
+    }
+
+    /// metrics returns the metrics of the events handled by the stream.
+    pub fn metrics(&self) -> Arc<EventStreamMetrics> {
+        self.metrics.clone()
+    }
+
+    /// subscription_options returns the options subscriptions are queued with, unless they are
+    /// subscribed with their own.
+    pub fn subscription_options(&self) -> SubscriptionOptions {
+        self.options.subscription
+    }
+
+    /// mailbox creates the stream's mailbox, bounded by the configured capacity.
+    fn mailbox(&self) -> (Address<EventStream<D>>, Mailbox<EventStream<D>>) {
+        match self.options.mailbox_capacity {
+            Some(capacity) => Mailbox::bounded(capacity),
+            None => Mailbox::unbounded(),
+        }
     }
 
     #[cfg(target_arch = "wasm32")]
     #[instrument]
     pub async fn open(&mut self) {
         trace!("opening EventStreamer (wasm)");
-        self.0 = Some(xtra::spawn_wasm_bindgen(
-            EventStream::new(),
-            Mailbox::unbounded(),
+        self.addr = Some(xtra::spawn_wasm_bindgen(
+            EventStream::with_metrics(self.metrics.clone()),
+            self.mailbox(),
         ));
     }
 
@@ -41,19 +78,22 @@ where
     #[instrument]
     pub async fn open(&mut self) {
         trace!("opening EventStreamer (tokio)");
-        self.0 = Some(xtra::spawn_tokio(EventStream::new(), Mailbox::unbounded()));
+        self.addr = Some(xtra::spawn_tokio(
+            EventStream::with_metrics(self.metrics.clone()),
+            self.mailbox(),
+        ));
     }
 
     #[instrument]
     pub async fn close(&mut self) {
-        if let Some(addr) = self.0.take() {
+        if let Some(addr) = self.addr.take() {
             let _ = addr.send(Shutdown).await;
         }
     }
 
     #[instrument]
     pub async fn emit(&self, ns: &str, evt: MessageEvent<D>, indexes: MapValue) {
-        if let Some(addr) = &self.0 {
+        if let Some(addr) = &self.addr {
             let _ = addr
                 .send(Emit {
                     ns: ns.to_string(),
@@ -64,19 +104,56 @@ where
         }
     }
 
+    /// subscribe subscribes the listener to the events emitted in the namespace, whose indexes
+    /// match the given filters.
     #[instrument]
     pub async fn subscribe(
         &self,
         ns: &str,
         id: &str,
+        filters: Filters,
+        listener: EventChannel<D>,
+    ) -> Result<Subscription, EventStreamError> {
+        self.subscribe_with_options(ns, id, filters, self.options.subscription, listener)
+            .await
+    }
+
+    /// subscribe_stream subscribes to the events emitted in the namespace, whose indexes match
+    /// the given filters, returning the subscription as a stream of the events.
+    #[instrument]
+    pub async fn subscribe_stream(
+        &self,
+        ns: &str,
+        id: &str,
+        filters: Filters,
+    ) -> Result<EventSubscription<D>, EventStreamError> {
+        let (listener, receiver) = SubscriptionStream::new(id);
+        let sub = self
+            .subscribe(ns, id, filters, SubscriptionStream::channel(listener.run()))
+            .await?;
+
+        Ok(EventSubscription::new(sub, receiver))
+    }
+
+    /// subscribe_with_options subscribes the listener like `subscribe`, queueing its events with
+    /// the given options instead of the stream's.
+    #[instrument]
+    pub async fn subscribe_with_options(
+        &self,
+        ns: &str,
+        id: &str,
+        filters: Filters,
+        options: SubscriptionOptions,
         listener: EventChannel<D>,
     ) -> Result<Subscription, EventStreamError> {
-        if let Some(addr) = &self.0 {
+        if let Some(addr) = &self.addr {
             trace!("subscribing to event stream");
             let sub = addr
                 .send(Subscribe {
                     ns: ns.to_string(),
                     id: id.to_string(),
+                    filters,
+                    options,
                     listener,
                 })
                 .await?;
@@ -1,5 +1,11 @@
+pub mod delivery;
 pub mod emitter;
+pub mod replay;
 pub mod stream;
 pub mod subscription;
 
+pub use delivery::{
+    EventStreamMetrics, EventStreamOptions, OverflowPolicy, SubscriptionOptions,
+    DEFAULT_SUBSCRIPTION_CAPACITY,
+};
 pub use stream::*;
@@ -0,0 +1,368 @@
+use std::collections::{HashSet, VecDeque};
+
+use tracing::{debug, info, instrument, trace};
+use xtra::{Actor, Address, Handler, Mailbox};
+
+use crate::{errors::EventStreamError, fields::MessageFields, Descriptor, Fields};
+
+use super::{Event, EventChannel, MessageEvent, OverflowPolicy, SubscriptionOptions};
+
+/// Replay is an actor that holds back the live events of a subscription, while the events which
+/// were missed since a cursor are replayed to its listener from the event log. Once the replay
+/// is finished the held events are delivered, skipping any which were already replayed, and
+/// later events are delivered as they are emitted. The held events are bounded by the
+/// subscription's capacity, applying its overflow policy once it is full. The CIDs of the
+/// replayed events are only kept until the first live event which wasn't replayed, since any
+/// event emitted after it was logged after every replayed event.
+pub struct Replay {
+    id: String,
+    listener: EventChannel<Descriptor>,
+    options: SubscriptionOptions,
+    replaying: bool,
+    held: VecDeque<Event<Descriptor>>,
+    replayed: HashSet<String>,
+}
+
+/// Replayed is an event replayed from the event log, with the CID it is logged with.
+pub struct Replayed {
+    pub cid: String,
+    pub event: Event<Descriptor>,
+}
+
+/// Live ends the replay, delivering the held events and any later events to the listener.
+pub struct Live;
+
+impl Actor for Replay {
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {
+        info!(target = "Replay stopped", id = self.id);
+    }
+}
+
+impl Handler<Event<Descriptor>> for Replay {
+    type Return = MessageEvent<Descriptor>;
+
+    async fn handle(
+        &mut self,
+        evt: Event<Descriptor>,
+        ctx: &mut xtra::Context<Self>,
+    ) -> Self::Return {
+        let msg = evt.1.clone();
+        if self.replaying {
+            if self.held.len() >= self.options.capacity {
+                match self.options.overflow {
+                    OverflowPolicy::DropOldest => {
+                        debug!(
+                            id = self.id,
+                            "replay queue full, dropping oldest held event"
+                        );
+                        self.held.pop_front();
+                    }
+                    OverflowPolicy::DropNewest => {
+                        debug!(id = self.id, "replay queue full, dropping event");
+                        return msg;
+                    }
+                    OverflowPolicy::Disconnect => {
+                        debug!(id = self.id, "replay queue full, disconnecting");
+                        ctx.stop_self();
+                        return msg;
+                    }
+                }
+            }
+
+            trace!(id = self.id, "holding event until replay is finished");
+            self.held.push_back(evt);
+        } else if !self.was_replayed(&evt.1) && self.listener.send(evt).await.is_err() {
+            ctx.stop_self();
+        }
+
+        msg
+    }
+}
+
+impl Handler<Replayed> for Replay {
+    type Return = Result<(), EventStreamError>;
+
+    async fn handle(&mut self, replayed: Replayed, _: &mut xtra::Context<Self>) -> Self::Return {
+        trace!(id = self.id, cid = replayed.cid, "replaying event");
+        self.replayed.insert(replayed.cid);
+        self.listener.send(replayed.event).await?;
+
+        Ok(())
+    }
+}
+
+impl Handler<Live> for Replay {
+    type Return = Result<(), EventStreamError>;
+
+    async fn handle(&mut self, _: Live, _: &mut xtra::Context<Self>) -> Self::Return {
+        debug!(
+            id = self.id,
+            replayed = self.replayed.len(),
+            held = self.held.len(),
+            "replay finished"
+        );
+        self.replaying = false;
+
+        for evt in std::mem::take(&mut self.held) {
+            if self.was_replayed(&evt.1) {
+                continue;
+            }
+
+            self.listener.send(evt).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Replay {
+    pub fn new(id: &str, listener: EventChannel<Descriptor>, options: SubscriptionOptions) -> Self {
+        Self {
+            id: id.to_string(),
+            listener,
+            options,
+            replaying: true,
+            held: VecDeque::new(),
+            replayed: HashSet::new(),
+        }
+    }
+
+    /// mailbox creates the replay's mailbox, bounded by the subscription's capacity, so that
+    /// the stream's delivery of live events waits on a replay which isn't keeping up.
+    fn mailbox(&self) -> (Address<Self>, Mailbox<Self>) {
+        Mailbox::bounded(self.options.capacity.max(1))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn run(self) -> Address<Self> {
+        trace!("starting actor (wasm)");
+        let mailbox = self.mailbox();
+        xtra::spawn_wasm_bindgen(self, mailbox)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run(self) -> Address<Self> {
+        trace!("starting actor (tokio)");
+        let mailbox = self.mailbox();
+        xtra::spawn_tokio(self, mailbox)
+    }
+
+    /// was_replayed returns whether the event was already replayed. Events logged before the
+    /// replay read the event log may also be emitted after the subscription was made, and each
+    /// is only emitted once, so its CID is forgotten once it is matched. The first event which
+    /// wasn't replayed is past the end of the replay, so the remaining CIDs are forgotten too.
+    fn was_replayed(&mut self, evt: &MessageEvent<Descriptor>) -> bool {
+        if self.replayed.is_empty() {
+            return false;
+        }
+
+        let replayed = event_cid(evt).is_some_and(|cid| self.replayed.remove(&cid));
+        if !replayed {
+            trace!(
+                id = self.id,
+                forgotten = self.replayed.len(),
+                "live events are past the replay"
+            );
+            self.replayed.clear();
+            self.replayed.shrink_to_fit();
+        }
+
+        replayed
+    }
+
+    #[instrument]
+    pub fn channel(addr: Address<Self>) -> EventChannel<Descriptor> {
+        trace!("adding Replay to EventChannel");
+        EventChannel::new(addr)
+    }
+}
+
+/// event_cid returns the CID an event's message is logged with in the event log. RecordsWrite
+/// events carry the record's initial write alongside the message, and the message may carry its
+/// encoded data, neither of which are part of the logged message.
+fn event_cid(evt: &MessageEvent<Descriptor>) -> Option<String> {
+    let mut message = evt.message.clone();
+    if let Fields::InitialWriteField(fields) = message.fields {
+        message.fields = Fields::Write(fields.write_fields);
+    }
+    message.fields.strip_encoded_data();
+
+    message.cid().ok().map(|cid| cid.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        descriptors::{records, Records},
+        MapValue, Message,
+    };
+
+    fn test_evt(timestamp: i64) -> Event<Descriptor> {
+        let evt = MessageEvent {
+            message: Message {
+                descriptor: Descriptor::Records(Records::Read(records::ReadDescriptor {
+                    message_timestamp: chrono::DateTime::from_timestamp(timestamp, 0).unwrap(),
+                    filter: Default::default(),
+                })),
+                fields: Fields::Authorization(Default::default()),
+            },
+            initial_write: None,
+        };
+
+        ("ns".to_string(), evt, MapValue::default())
+    }
+
+    /// Collector is a listener which returns the events it receives, once it has received the
+    /// expected number of events.
+    struct Collector(Vec<MessageEvent<Descriptor>>, usize);
+
+    impl Actor for Collector {
+        type Stop = Vec<MessageEvent<Descriptor>>;
+
+        async fn stopped(self) -> Self::Stop {
+            self.0
+        }
+    }
+
+    impl Handler<Event<Descriptor>> for Collector {
+        type Return = MessageEvent<Descriptor>;
+
+        async fn handle(
+            &mut self,
+            evt: Event<Descriptor>,
+            ctx: &mut xtra::Context<Self>,
+        ) -> Self::Return {
+            self.0.push(evt.1.clone());
+            if self.0.len() == self.1 {
+                ctx.stop_self();
+            }
+
+            evt.1
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay() {
+        let (addr, mailbox) = Mailbox::unbounded();
+        let collector = tokio::spawn(xtra::run(mailbox, Collector(Vec::new(), 4)));
+        let replay = Replay::new("test", EventChannel::new(addr), Default::default()).run();
+
+        // live events are held while replaying, including an event which is also replayed.
+        replay.send(test_evt(2)).await.unwrap();
+        replay.send(test_evt(3)).await.unwrap();
+
+        for timestamp in [1, 2] {
+            let evt = test_evt(timestamp);
+            let cid = event_cid(&evt.1).unwrap();
+            replay
+                .send(Replayed { cid, event: evt })
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
+        replay.send(Live).await.unwrap().unwrap();
+        replay.send(test_evt(4)).await.unwrap();
+
+        let received = collector.await.unwrap();
+        let expected = [1, 2, 3, 4].map(|timestamp| test_evt(timestamp).1);
+        assert_eq!(received, expected);
+    }
+
+    async fn replay_and_go_live(replay: &Address<Replay>, timestamps: &[i64]) {
+        for &timestamp in timestamps {
+            let evt = test_evt(timestamp);
+            let cid = event_cid(&evt.1).unwrap();
+            replay
+                .send(Replayed { cid, event: evt })
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
+        replay.send(Live).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_live_duplicates() {
+        let (addr, mailbox) = Mailbox::unbounded();
+        let collector = tokio::spawn(xtra::run(mailbox, Collector(Vec::new(), 3)));
+        let replay = Replay::new("test", EventChannel::new(addr), Default::default()).run();
+
+        replay_and_go_live(&replay, &[1, 2]).await;
+
+        // replayed events which are only emitted once the replay is live are still skipped.
+        replay.send(test_evt(2)).await.unwrap();
+        replay.send(test_evt(1)).await.unwrap();
+        replay.send(test_evt(3)).await.unwrap();
+
+        let received = collector.await.unwrap();
+        let expected = [1, 2, 3].map(|timestamp| test_evt(timestamp).1);
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test]
+    async fn test_replay_forgets_replayed() {
+        let (addr, mailbox) = Mailbox::unbounded();
+        let collector = tokio::spawn(xtra::run(mailbox, Collector(Vec::new(), 4)));
+        let replay = Replay::new("test", EventChannel::new(addr), Default::default()).run();
+
+        replay_and_go_live(&replay, &[1, 2]).await;
+
+        // once a live event which wasn't replayed is delivered, the replayed events are no
+        // longer tracked, and any later event is delivered.
+        replay.send(test_evt(3)).await.unwrap();
+        replay.send(test_evt(2)).await.unwrap();
+
+        let received = collector.await.unwrap();
+        let expected = [1, 2, 3, 2].map(|timestamp| test_evt(timestamp).1);
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test]
+    async fn test_replay_overflow() {
+        for (overflow, held) in [
+            (OverflowPolicy::DropOldest, 3),
+            (OverflowPolicy::DropNewest, 2),
+        ] {
+            let (addr, mailbox) = Mailbox::unbounded();
+            let collector = tokio::spawn(xtra::run(mailbox, Collector(Vec::new(), 3)));
+            let options = SubscriptionOptions {
+                capacity: 1,
+                overflow,
+            };
+            let replay = Replay::new("test", EventChannel::new(addr), options).run();
+
+            replay.send(test_evt(2)).await.unwrap();
+            replay.send(test_evt(3)).await.unwrap();
+            replay_and_go_live(&replay, &[1]).await;
+            replay.send(test_evt(4)).await.unwrap();
+
+            let received = collector.await.unwrap();
+            let expected = [1, held, 4].map(|timestamp| test_evt(timestamp).1);
+            assert_eq!(received, expected, "{:?}", overflow);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_overflow_disconnect() {
+        let (addr, mailbox) = Mailbox::unbounded();
+        tokio::spawn(xtra::run(mailbox, Collector(Vec::new(), 1)));
+        let options = SubscriptionOptions {
+            capacity: 1,
+            overflow: OverflowPolicy::Disconnect,
+        };
+        let replay = Replay::new("test", EventChannel::new(addr), options).run();
+
+        replay.send(test_evt(2)).await.unwrap();
+        replay.send(test_evt(3)).await.unwrap();
+
+        // the replay stops once its held events overflow, failing the rest of the replay.
+        let evt = test_evt(1);
+        let cid = event_cid(&evt.1).unwrap();
+        assert!(replay.send(Replayed { cid, event: evt }).await.is_err());
+    }
+}
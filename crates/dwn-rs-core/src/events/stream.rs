@@ -1,6 +1,5 @@
-use std::{collections::BTreeMap, fmt::Debug, future::Future, pin::Pin};
+use std::{collections::BTreeMap, fmt::Debug, future::Future, pin::Pin, sync::Arc};
 
-use futures_util::future;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tracing::{debug, info, instrument, trace, Instrument};
 use xtra::{prelude::MessageChannel, Actor, Handler};
@@ -8,9 +7,12 @@ use xtra::{prelude::MessageChannel, Actor, Handler};
 use crate::{
     descriptors::{records, MessageDescriptor},
     errors::EventStreamError,
-    Descriptor, MapValue, Message,
+    indexes::split_tags,
+    Descriptor, Filters, MapValue, Message,
 };
 
+use super::delivery::{Enqueued, EventStreamMetrics, Listener, SubscriptionOptions};
+
 pub type Event<D> = (String, MessageEvent<D>, MapValue);
 
 pub type EventChannel<D> = MessageChannel<Event<D>, MessageEvent<D>, xtra::refcount::Strong>;
@@ -21,7 +23,19 @@ where
     Message<D>: Serialize + DeserializeOwned,
     D: MessageDescriptor + DeserializeOwned + Clone + Debug + PartialEq + Send + 'static,
 {
-    listeners: BTreeMap<(String, String), EventChannel<D>>,
+    listeners: BTreeMap<(String, String), Subscriber<D>>,
+    metrics: Arc<EventStreamMetrics>,
+}
+
+/// Subscriber is a subscription's listener, and the filters the events sent to it must match.
+#[derive(Debug)]
+struct Subscriber<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + DeserializeOwned + Clone + Debug + PartialEq + Send + 'static,
+{
+    listener: Listener<D>,
+    filters: Filters,
 }
 
 impl<D> EventStream<D>
@@ -30,10 +44,35 @@ where
     D: MessageDescriptor + DeserializeOwned + Clone + Debug + PartialEq + Send + 'static,
 {
     pub fn new() -> Self {
+        Self::with_metrics(Arc::default())
+    }
+
+    /// with_metrics creates an EventStream which counts its events in the given metrics.
+    pub fn with_metrics(metrics: Arc<EventStreamMetrics>) -> Self {
         EventStream {
             listeners: BTreeMap::new(),
+            metrics,
         }
     }
+
+    /// metrics returns the metrics of the events handled by the stream.
+    pub fn metrics(&self) -> Arc<EventStreamMetrics> {
+        self.metrics.clone()
+    }
+
+    /// remove_disconnected removes the subscriptions whose listener has disconnected.
+    fn remove_disconnected(&mut self) {
+        let metrics = self.metrics.clone();
+        self.listeners.retain(|(ns, id), subscriber| {
+            let connected = subscriber.listener.is_connected();
+            if !connected {
+                debug!(ns, id, "removing disconnected subscription");
+                metrics.record_disconnected();
+            }
+
+            connected
+        });
+    }
 }
 
 impl<D> Default for EventStream<D>
@@ -65,6 +104,8 @@ where
 {
     pub ns: String,
     pub id: String,
+    pub filters: Filters,
+    pub options: SubscriptionOptions,
     pub listener: EventChannel<D>,
 }
 
@@ -136,34 +177,40 @@ where
 
     async fn handle(&mut self, msg: Emit<D>, _ctx: &mut xtra::Context<Self>) -> Self::Return {
         debug!("Emitting event");
-        future::join_all(
-            self.listeners
-                .iter()
-                .filter_map(
-                    |((listener_ns, _), listener)| match listener_ns == &msg.ns {
-                        true => Some(listener),
-                        false => None,
-                    },
-                )
-                .map(|listener| {
-                    let ns = msg.ns.clone();
-                    let evt = msg.evt.clone();
-                    let indexes = msg.indexes.clone();
-                    async move {
-                        trace!(
-                            ns = ?ns,
-                            listener = ?listener.clone(),
-                            "sending event to listener",
-                        );
-                        listener
-                            .send((ns.clone(), evt.clone(), indexes.clone()))
-                            .await
-                            .expect("Failed to send message");
-                    }
-                }),
-        )
-        .instrument(tracing::debug_span!("emit"))
-        .await;
+        self.metrics.record_emitted();
+        self.remove_disconnected();
+
+        // the emitted indexes include the tags of the message, flattened as `tag.` indexes.
+        let (indexes, tags) = split_tags(msg.indexes.clone());
+
+        let mut overflowed = Vec::new();
+        for ((ns, id), subscriber) in self.listeners.iter_mut() {
+            if ns != &msg.ns || !subscriber.filters.matches(&indexes, &tags) {
+                continue;
+            }
+
+            trace!(ns = ?ns, id = ?id, "sending event to listener");
+            let event = (msg.ns.clone(), msg.evt.clone(), msg.indexes.clone());
+            if subscriber
+                .listener
+                .enqueue(event)
+                .instrument(tracing::debug_span!("emit"))
+                .await
+                == Enqueued::Overflowed
+            {
+                overflowed.push((ns.clone(), id.clone()));
+            }
+        }
+
+        for key in overflowed {
+            debug!(
+                ns = key.0,
+                id = key.1,
+                "disconnecting overflowed subscription"
+            );
+            self.listeners.remove(&key);
+            self.metrics.record_disconnected();
+        }
     }
 }
 
@@ -178,7 +225,10 @@ where
         debug!("handling event subscription");
         let ns = msg.ns;
         let id = msg.id;
-        let listener = msg.listener;
+        let subscriber = Subscriber {
+            listener: Listener::new(&id, msg.listener, msg.options, self.metrics.clone()),
+            filters: msg.filters,
+        };
         let addr = _ctx.mailbox().address().try_upgrade().unwrap();
 
         let sub = Subscription {
@@ -186,7 +236,7 @@ where
             close: Box::new(make_close_task(ns.clone(), id.clone(), addr)),
         };
 
-        self.listeners.insert((ns, id), listener);
+        self.listeners.insert((ns, id), subscriber);
         sub
     }
 }
@@ -257,7 +307,7 @@ where
 
         let fut = async move {
             trace!("closing event subscription task");
-            addr.clone().send(close).await.unwrap();
+            addr.clone().send(close).await?;
             Ok(())
         };
 
@@ -270,36 +320,18 @@ mod test {
     use tracing_test::traced_test;
     use xtra::{spawn_tokio, Mailbox};
 
-    use crate::{descriptors::Records, Fields};
+    use super::*;
+    use crate::{
+        descriptors::Records,
+        filters::{Filter, FilterKey, ValueFilter},
+        Fields, Value,
+    };
 
     #[traced_test]
     #[tokio::test]
     async fn test_event_stream() {
         use super::*;
 
-        fn test_evt() -> MessageEvent<Descriptor> {
-            let now = chrono::DateTime::<chrono::Utc>::MIN_UTC.naive_utc();
-            MessageEvent {
-                message: Message {
-                    descriptor: Descriptor::Records(Records::Read(records::ReadDescriptor {
-                        message_timestamp: chrono::DateTime::from_naive_utc_and_offset(
-                            now,
-                            chrono::Utc,
-                        ),
-                        filter: Default::default(),
-                    })),
-                    fields: Fields::Authorization(Default::default()),
-                },
-                initial_write: None,
-            }
-        }
-        fn test_ns() -> String {
-            "ns".to_string()
-        }
-        fn test_indexes() -> MapValue {
-            MapValue::default()
-        }
-
         struct MessageReturner<
             D: MessageDescriptor + DeserializeOwned + Clone + Debug + PartialEq + Send + 'static,
         >(Option<MessageEvent<D>>)
@@ -350,6 +382,8 @@ mod test {
             .send(Subscribe {
                 ns: test_ns(),
                 id: sub_id.to_string(),
+                filters: Filters::default(),
+                options: SubscriptionOptions::default(),
                 listener: MessageChannel::new(child_addr),
             })
             .instrument(tracing::info_span!("subscribe"))
@@ -366,13 +400,180 @@ mod test {
             .await;
         assert!(emit.is_ok());
 
+        // events are delivered asynchronously, so wait for the listener before closing.
+        let s = child.await;
+
         let f = (sub.close)().await;
         assert!(f.is_ok());
 
-        let s = child.await;
         assert!(s.is_ok());
         let opt_msg = s.unwrap();
         assert!(opt_msg.is_some());
         assert_eq!(opt_msg.unwrap(), test_evt());
     }
+
+    fn test_evt() -> MessageEvent<Descriptor> {
+        let now = chrono::DateTime::<chrono::Utc>::MIN_UTC.naive_utc();
+        MessageEvent {
+            message: Message {
+                descriptor: Descriptor::Records(Records::Read(records::ReadDescriptor {
+                    message_timestamp: chrono::DateTime::from_naive_utc_and_offset(
+                        now,
+                        chrono::Utc,
+                    ),
+                    filter: Default::default(),
+                })),
+                fields: Fields::Authorization(Default::default()),
+            },
+            initial_write: None,
+        }
+    }
+
+    fn test_ns() -> String {
+        "ns".to_string()
+    }
+
+    fn test_indexes() -> MapValue {
+        MapValue::default()
+    }
+
+    /// EventReturner is a listener which returns the first event it receives when it stops.
+    struct EventReturner(Option<Event<Descriptor>>);
+
+    impl Actor for EventReturner {
+        type Stop = Option<Event<Descriptor>>;
+
+        async fn stopped(self) -> Self::Stop {
+            self.0
+        }
+    }
+
+    impl Handler<Event<Descriptor>> for EventReturner {
+        type Return = MessageEvent<Descriptor>;
+
+        async fn handle(
+            &mut self,
+            evt: Event<Descriptor>,
+            _ctx: &mut xtra::Context<Self>,
+        ) -> Self::Return {
+            trace!("EventReturner handling event");
+            self.0 = Some(evt.clone());
+            _ctx.stop_self();
+
+            evt.1
+        }
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn test_filtered_event_stream() {
+        let addr = spawn_tokio(EventStream::new(), Mailbox::unbounded());
+
+        let filters = |key: FilterKey, value: &str| {
+            Filters::from(ValueFilter::from([(
+                key,
+                Filter::Equal(Value::String(value.to_string())),
+            )]))
+        };
+        let subscribers = [
+            ("chat", filters(FilterKey::Index("protocol".into()), "chat")),
+            (
+                "social",
+                filters(FilterKey::Index("protocol".into()), "social"),
+            ),
+            ("draft", filters(FilterKey::Tag("status".into()), "draft")),
+        ];
+
+        let mut children = Vec::new();
+        for (id, filters) in subscribers {
+            let (child_addr, child_mailbox) = Mailbox::unbounded();
+            let child = tokio::spawn(xtra::run(child_mailbox, EventReturner(None)));
+
+            let sub = addr
+                .send(Subscribe {
+                    ns: test_ns(),
+                    id: id.to_string(),
+                    filters,
+                    options: SubscriptionOptions::default(),
+                    listener: MessageChannel::new(child_addr),
+                })
+                .await
+                .unwrap();
+            children.push((id, sub, child));
+        }
+
+        let indexes = MapValue::from([
+            ("protocol".to_string(), Value::String("chat".to_string())),
+            ("tag.status".to_string(), Value::String("draft".to_string())),
+        ]);
+        addr.send(Emit {
+            ns: test_ns(),
+            evt: test_evt(),
+            indexes: indexes.clone(),
+        })
+        .await
+        .unwrap();
+
+        for (id, sub, child) in children {
+            match id {
+                "social" => {
+                    // closing the subscription drops the listener's channel, stopping it.
+                    (sub.close)().await.unwrap();
+                    let received = child.await.unwrap();
+                    assert!(received.is_none(), "{} received the event", id);
+                }
+                _ => {
+                    let (_, msg, received_indexes) = child.await.unwrap().unwrap();
+                    assert_eq!(msg, test_evt());
+                    assert_eq!(received_indexes, indexes);
+                    (sub.close)().await.unwrap();
+                }
+            }
+        }
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn test_disconnected_listener() {
+        let metrics = Arc::new(EventStreamMetrics::default());
+        let addr = spawn_tokio(
+            EventStream::with_metrics(metrics.clone()),
+            Mailbox::unbounded(),
+        );
+
+        // the listener's mailbox is dropped, so it is disconnected before any event is sent.
+        let (child_addr, child_mailbox) = Mailbox::<EventReturner>::unbounded();
+        drop(child_mailbox);
+
+        addr.send(Subscribe {
+            ns: test_ns(),
+            id: "dead".to_string(),
+            filters: Filters::default(),
+            options: SubscriptionOptions::default(),
+            listener: MessageChannel::new(child_addr),
+        })
+        .await
+        .unwrap();
+
+        // delivery is asynchronous, so keep emitting until the listener is found disconnected.
+        for _ in 0..100 {
+            if metrics.disconnected() > 0 {
+                break;
+            }
+
+            addr.send(Emit {
+                ns: test_ns(),
+                evt: test_evt(),
+                indexes: test_indexes(),
+            })
+            .await
+            .unwrap();
+            tokio::task::yield_now().await;
+        }
+
+        assert!(addr.is_connected());
+        assert_eq!(metrics.disconnected(), 1);
+        assert_eq!(metrics.delivered(), 0);
+        assert!(metrics.emitted() > 0);
+    }
 }
@@ -1,12 +1,18 @@
-use std::fmt::Debug;
+use std::{
+    fmt::Debug,
+    pin::Pin,
+    task::{Context, Poll},
+};
 
+use futures_channel::mpsc;
+use futures_util::{future, FutureExt, Stream, StreamExt};
 use serde::{de::DeserializeOwned, Serialize};
-use tracing::{info, instrument, trace};
+use tracing::{debug, info, instrument, trace};
 use xtra::{Actor, Address, Handler};
 
-use crate::{descriptors::MessageDescriptor, MapValue, Message};
+use crate::{descriptors::MessageDescriptor, errors::EventStreamError, MapValue, Message};
 
-use super::{Event, EventChannel, MessageEvent};
+use super::{Event, EventChannel, MessageEvent, Subscription, SubscriptionID};
 
 pub type HandleFn<D> = fn(String, MessageEvent<D>, MapValue);
 pub type SubscriptionFnAddress<D> = Address<SubscriptionFn<D>>;
@@ -82,3 +88,234 @@ where
         EventChannel::new(addr)
     }
 }
+
+/// SubscriptionStream is an actor that forwards the events of a subscription to an
+/// `EventSubscription`, waiting for each event to be taken from the stream before accepting the
+/// next. Events emitted while the stream's consumer isn't keeping up are queued by the
+/// subscription, under its overflow policy.
+pub struct SubscriptionStream<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + Clone + Debug + PartialEq + Send + 'static,
+{
+    id: String,
+    sender: mpsc::Sender<MessageEvent<D>>,
+}
+
+impl<D> Actor for SubscriptionStream<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + Clone + Debug + PartialEq + Send + 'static,
+{
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {
+        info!(target = "SubscriptionStream stopped", id = self.id);
+    }
+}
+
+impl<D> Handler<Event<D>> for SubscriptionStream<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + Clone + Debug + PartialEq + Send + 'static,
+{
+    type Return = MessageEvent<D>;
+
+    async fn handle(&mut self, evt: Event<D>, ctx: &mut xtra::Context<Self>) -> Self::Return {
+        trace!("SubscriptionStream handling event");
+        let sent = match future::poll_fn(|cx| self.sender.poll_ready(cx)).await {
+            Ok(()) => self.sender.start_send(evt.1.clone()),
+            Err(err) => Err(err),
+        };
+
+        // the stream was dropped, so stop listening for events. The subscription is then
+        // removed as a disconnected listener, if it wasn't already closed.
+        if sent.is_err() {
+            debug!(id = self.id, "SubscriptionStream receiver dropped");
+            ctx.stop_self();
+        }
+
+        evt.1
+    }
+}
+
+impl<D> SubscriptionStream<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + Clone + Debug + PartialEq + Send + 'static,
+{
+    /// new creates the actor, and the receiving end of the events it forwards.
+    pub fn new(id: &str) -> (Self, mpsc::Receiver<MessageEvent<D>>) {
+        // the channel holds one event per sender, so the actor waits for each event to be
+        // received before handling the next.
+        let (sender, receiver) = mpsc::channel(0);
+
+        (
+            Self {
+                id: id.to_string(),
+                sender,
+            },
+            receiver,
+        )
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn run(self) -> Address<Self> {
+        trace!("starting actor (wasm)");
+        xtra::spawn_wasm_bindgen(self, xtra::Mailbox::unbounded())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run(self) -> Address<Self> {
+        trace!("starting actor (tokio)");
+        xtra::spawn_tokio(self, xtra::Mailbox::unbounded())
+    }
+
+    #[instrument]
+    pub fn channel(addr: Address<Self>) -> EventChannel<D> {
+        trace!("adding SubscriptionStream to EventChannel");
+        EventChannel::new(addr)
+    }
+}
+
+/// EventSubscription is a subscription whose events are received as a `Stream`. Dropping the
+/// EventSubscription closes the subscription.
+pub struct EventSubscription<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + Clone + Debug + PartialEq + Send + 'static,
+{
+    subscription: Subscription,
+    receiver: mpsc::Receiver<MessageEvent<D>>,
+    closed: bool,
+}
+
+impl<D> EventSubscription<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + Clone + Debug + PartialEq + Send + 'static,
+{
+    pub fn new(subscription: Subscription, receiver: mpsc::Receiver<MessageEvent<D>>) -> Self {
+        Self {
+            subscription,
+            receiver,
+            closed: false,
+        }
+    }
+
+    pub fn subscription_id(&self) -> &SubscriptionID {
+        &self.subscription.subscription_id
+    }
+
+    /// close closes the subscription, waiting for it to be removed from the event stream.
+    pub async fn close(mut self) -> Result<(), EventStreamError> {
+        self.closed = true;
+        (self.subscription.close)().await
+    }
+}
+
+impl<D> Stream for EventSubscription<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + Clone + Debug + PartialEq + Send + 'static,
+{
+    type Item = MessageEvent<D>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+impl<D> Drop for EventSubscription<D>
+where
+    Message<D>: Serialize + DeserializeOwned,
+    D: MessageDescriptor + Clone + Debug + PartialEq + Send + 'static,
+{
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+
+        // the close message is sent without waiting for it to be handled, as drop can't await.
+        // Should the event stream's mailbox be full, the subscription is instead removed once
+        // its listener stops when the receiver is dropped.
+        trace!("closing dropped EventSubscription");
+        self.receiver.close();
+        let _ = (self.subscription.close)().now_or_never();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        descriptors::{records, Records},
+        events::emitter::EventStreamer,
+        Descriptor, Fields, Filters,
+    };
+
+    fn test_evt() -> MessageEvent<Descriptor> {
+        MessageEvent {
+            message: Message {
+                descriptor: Descriptor::Records(Records::Read(records::ReadDescriptor {
+                    message_timestamp: chrono::Utc::now(),
+                    filter: Default::default(),
+                })),
+                fields: Fields::Authorization(Default::default()),
+            },
+            initial_write: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_subscription() {
+        let mut streamer = EventStreamer::<Descriptor>::new();
+        streamer.open().await;
+        let metrics = streamer.metrics();
+
+        let mut sub = streamer
+            .subscribe_stream("ns", "test", Filters::default())
+            .await
+            .unwrap();
+        assert_eq!(sub.subscription_id().id, "test");
+
+        let evts = [test_evt(), test_evt()];
+        for evt in evts.iter() {
+            streamer.emit("ns", evt.clone(), MapValue::default()).await;
+        }
+        streamer
+            .emit("other", test_evt(), MapValue::default())
+            .await;
+
+        for evt in evts.iter() {
+            assert_eq!(sub.next().await.as_ref(), Some(evt));
+        }
+
+        // dropping the subscription closes it, so later events aren't delivered.
+        drop(sub);
+        streamer.emit("ns", test_evt(), MapValue::default()).await;
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+
+        assert_eq!(metrics.emitted(), 4);
+        assert_eq!(metrics.delivered(), 2);
+        assert_eq!(metrics.disconnected(), 0);
+
+        streamer.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_event_subscription_close() {
+        let mut streamer = EventStreamer::<Descriptor>::new();
+        streamer.open().await;
+
+        let sub = streamer
+            .subscribe_stream("ns", "test", Filters::default())
+            .await
+            .unwrap();
+        assert!(sub.close().await.is_ok());
+
+        streamer.close().await;
+    }
+}
@@ -1,4 +1,4 @@
-use std::ops::Bound;
+use std::{cmp::Ordering, ops::Bound};
 
 use serde::{Deserialize, Serialize};
 
@@ -485,6 +485,86 @@ impl<T: Default + TryFrom<Value>> From<Filter<Value>> for Filter<T> {
     }
 }
 
+impl Filter<Value> {
+    /// matches returns true if the indexed value satisfies the filter. A missing (or null) value
+    /// never matches. Dates are compared with RFC 3339 strings, as filters parsed from JSON hold
+    /// dates as strings.
+    pub fn matches(&self, value: Option<&Value>) -> bool {
+        let value = match value {
+            None | Some(Value::Null) => return false,
+            Some(value) => value,
+        };
+
+        match self {
+            Filter::Equal(expected) => equals(value, expected),
+            Filter::OneOf(expected) => expected.iter().any(|expected| equals(value, expected)),
+            Filter::Prefix(prefix) => match (value, prefix) {
+                (Value::String(value), Value::String(prefix)) => value.starts_with(prefix),
+                (Value::Cid(value), Value::String(prefix)) => value.to_string().starts_with(prefix),
+                _ => false,
+            },
+            Filter::Range(RangeFilter::Numeric(lower, upper))
+            | Filter::Range(RangeFilter::Criterion(lower, upper)) => {
+                let lower = match lower {
+                    Bound::Included(bound) => {
+                        matches!(
+                            compare(value, bound),
+                            Some(Ordering::Greater | Ordering::Equal)
+                        )
+                    }
+                    Bound::Excluded(bound) => compare(value, bound) == Some(Ordering::Greater),
+                    Bound::Unbounded => true,
+                };
+                let upper = match upper {
+                    Bound::Included(bound) => {
+                        matches!(
+                            compare(value, bound),
+                            Some(Ordering::Less | Ordering::Equal)
+                        )
+                    }
+                    Bound::Excluded(bound) => compare(value, bound) == Some(Ordering::Less),
+                    Bound::Unbounded => true,
+                };
+
+                lower && upper
+            }
+        }
+    }
+}
+
+fn equals(value: &Value, expected: &Value) -> bool {
+    match compare(value, expected) {
+        Some(ordering) => ordering == Ordering::Equal,
+        None => value == expected,
+    }
+}
+
+/// compare orders two values of the same (or a comparable) type, returning None for values which
+/// cannot be ordered against each other.
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Number(a), Value::Number(b)) => Some(a.cmp(b)),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+        (Value::Number(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+        (Value::Float(a), Value::Number(b)) => a.partial_cmp(&(*b as f64)),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::DateTime(a), Value::DateTime(b)) => Some(a.cmp(b)),
+        (Value::DateTime(a), Value::String(b)) => parse_datetime(b).map(|b| a.cmp(&b)),
+        (Value::String(a), Value::DateTime(b)) => parse_datetime(a).map(|a| a.cmp(b)),
+        (Value::Cid(a), Value::Cid(b)) => Some(a.cmp(b)),
+        (Value::Cid(a), Value::String(b)) => Some(a.to_string().cmp(b)),
+        (Value::String(a), Value::Cid(b)) => Some(a.cmp(&b.to_string())),
+        _ => None,
+    }
+}
+
+fn parse_datetime(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|date| date.with_timezone(&chrono::Utc))
+}
+
 impl<T: Into<String>> From<T> for Filter<String> {
     fn from(value: T) -> Self {
         Filter::Equal(value.into())
@@ -717,4 +797,111 @@ mod test {
             Filter::Prefix("test".to_string())
         );
     }
+
+    #[test]
+    fn test_filter_matches() {
+        let now = chrono::Utc::now();
+        let string = |s: &str| Value::String(s.to_string());
+
+        let tests = vec![
+            (Filter::Equal(string("a")), Some(string("a")), true),
+            (Filter::Equal(string("a")), Some(string("b")), false),
+            (Filter::Equal(string("a")), None, false),
+            (
+                Filter::Equal(Value::Bool(true)),
+                Some(Value::Bool(true)),
+                true,
+            ),
+            (
+                Filter::Equal(Value::Number(1)),
+                Some(Value::Float(1.0)),
+                true,
+            ),
+            (
+                Filter::Equal(string(&now.to_rfc3339())),
+                Some(Value::DateTime(now)),
+                true,
+            ),
+            (
+                Filter::OneOf(vec![string("a"), string("b")]),
+                Some(string("b")),
+                true,
+            ),
+            (
+                Filter::OneOf(vec![string("a"), string("b")]),
+                Some(string("c")),
+                false,
+            ),
+            (Filter::Prefix(string("ab")), Some(string("abc")), true),
+            (Filter::Prefix(string("ab")), Some(string("ba")), false),
+            (Filter::Prefix(string("ab")), Some(Value::Number(1)), false),
+            (
+                Filter::Range(RangeFilter::Numeric(
+                    Bound::Included(Value::Number(1)),
+                    Bound::Excluded(Value::Number(3)),
+                )),
+                Some(Value::Number(1)),
+                true,
+            ),
+            (
+                Filter::Range(RangeFilter::Numeric(
+                    Bound::Included(Value::Number(1)),
+                    Bound::Excluded(Value::Number(3)),
+                )),
+                Some(Value::Number(3)),
+                false,
+            ),
+            (
+                Filter::Range(RangeFilter::Numeric(
+                    Bound::Excluded(Value::Number(1)),
+                    Bound::Unbounded,
+                )),
+                Some(Value::Number(1)),
+                false,
+            ),
+            (
+                Filter::Range(RangeFilter::Numeric(Bound::Unbounded, Bound::Unbounded)),
+                Some(Value::Null),
+                false,
+            ),
+            (
+                Filter::Range(RangeFilter::Criterion(
+                    Bound::Included(string("2024-01-01T00:00:00Z")),
+                    Bound::Included(string("2024-12-31T00:00:00Z")),
+                )),
+                Some(Value::DateTime(
+                    chrono::DateTime::from_timestamp(1_718_000_000, 0).unwrap(),
+                )),
+                true,
+            ),
+            (
+                Filter::Range(RangeFilter::Criterion(
+                    Bound::Included(string("2024-01-01T00:00:00Z")),
+                    Bound::Unbounded,
+                )),
+                Some(Value::DateTime(
+                    chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+                )),
+                false,
+            ),
+            (
+                Filter::Range(RangeFilter::Numeric(
+                    Bound::Included(Value::Number(1)),
+                    Bound::Unbounded,
+                )),
+                Some(string("2")),
+                false,
+            ),
+        ];
+
+        for (filter, value, expected) in tests {
+            assert_eq!(
+                filter.matches(value.as_ref()),
+                expected,
+                "{:?} {:?}",
+                filter,
+                value
+            );
+        }
+    }
 }
@@ -1,7 +1,7 @@
 use std::{collections::BTreeMap, fmt::Display};
 
 use super::filter::Filter;
-use crate::Value;
+use crate::{MapValue, Value};
 
 use serde::{Deserialize, Serialize};
 
@@ -49,6 +49,31 @@ pub struct Filters {
     pub(crate) set: FilterSet<FilterKey>,
 }
 
+impl Filters {
+    /// matches returns true if a message with the given indexes and tags satisfies the filters,
+    /// without a message store. A message matches if it satisfies any of the filter sets, and
+    /// satisfies a filter set if it satisfies every filter of the set. Empty filter sets are
+    /// ignored, as they are when the filters are executed by a message store, so filters without
+    /// any non-empty set match every message.
+    pub fn matches(&self, indexes: &MapValue, tags: &MapValue) -> bool {
+        let mut sets = self
+            .set
+            .iter()
+            .filter(|filter| !filter.is_empty())
+            .peekable();
+        if sets.peek().is_none() {
+            return true;
+        }
+
+        sets.any(|filter| {
+            filter.iter().all(|(key, filter)| match key {
+                FilterKey::Index(index) => filter.matches(indexes.get(index)),
+                FilterKey::Tag(tag) => filter.matches(tags.get(tag)),
+            })
+        })
+    }
+}
+
 impl From<Filters> for FilterSet<Alias> {
     fn from(filters: Filters) -> Self {
         filters
@@ -233,4 +258,64 @@ mod tests {
 
         assert_eq!(iter.next(), None);
     }
+
+    #[test]
+    fn test_filters_matches() {
+        let indexes = MapValue::from([
+            (
+                "interface".to_string(),
+                Value::String("Records".to_string()),
+            ),
+            ("dataSize".to_string(), Value::Number(10)),
+        ]);
+        let tags = MapValue::from([("status".to_string(), Value::String("draft".to_string()))]);
+
+        let index = |key: &str, value: Value| (FilterKey::Index(key.into()), Filter::Equal(value));
+        let records = index("interface", Value::String("Records".to_string()));
+        let protocols = index("interface", Value::String("Protocols".to_string()));
+        let draft = (
+            FilterKey::Tag("status".into()),
+            Filter::Equal(Value::String("draft".to_string())),
+        );
+
+        let tests = vec![
+            (Filters::default(), true),
+            (Filters::from(vec![ValueFilter::new()]), true),
+            (Filters::from(ValueFilter::from([records.clone()])), true),
+            (Filters::from(ValueFilter::from([protocols.clone()])), false),
+            (
+                Filters::from(ValueFilter::from([records.clone(), draft.clone()])),
+                true,
+            ),
+            (
+                Filters::from(ValueFilter::from([
+                    records.clone(),
+                    index("dataSize", Value::Number(11)),
+                ])),
+                false,
+            ),
+            (
+                Filters::from(vec![
+                    ValueFilter::from([protocols.clone()]),
+                    ValueFilter::from([draft.clone()]),
+                ]),
+                true,
+            ),
+            (
+                Filters::from(vec![ValueFilter::new(), ValueFilter::from([protocols])]),
+                false,
+            ),
+            (
+                Filters::from(ValueFilter::from([(
+                    FilterKey::Index("status".into()),
+                    Filter::Equal(Value::String("draft".to_string())),
+                )])),
+                false,
+            ),
+        ];
+
+        for (filters, expected) in tests {
+            assert_eq!(filters.matches(&indexes, &tags), expected, "{:?}", filters);
+        }
+    }
 }
@@ -1,12 +1,20 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, ops::Bound};
 
+use chrono::{DateTime, Utc};
 use cid::Cid;
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 
-use crate::Value;
+use crate::{
+    indexes::{
+        ATTESTER, AUTHOR, CONTEXT_ID, DATA_CID, DATA_FORMAT, DATA_SIZE, DATE_CREATED,
+        DATE_PUBLISHED, INTERFACE, MESSAGE_TIMESTAMP, METHOD, PARENT_ID, PROTOCOL, PROTOCOL_PATH,
+        PUBLISHED, RECIPIENT, RECORD_ID, SCHEMA,
+    },
+    Value,
+};
 
-use super::{Filter, RangeFilter};
+use super::{Filter, FilterKey, Filters, RangeFilter, ValueFilter};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 #[skip_serializing_none]
@@ -41,6 +49,129 @@ pub struct Records {
     pub date_updated: Option<RangeFilter<String>>,
 }
 
+impl From<Records> for ValueFilter<FilterKey> {
+    fn from(records: Records) -> Self {
+        let mut filter = ValueFilter::new();
+        let mut insert = |key: &str, value: Filter<Value>| {
+            filter.insert(FilterKey::Index(key.to_string()), value);
+        };
+
+        if let Some(author) = records.author {
+            insert(AUTHOR, one_of(author));
+        }
+        if let Some(attester) = records.attester {
+            insert(ATTESTER, Filter::Equal(Value::String(attester)));
+        }
+        if let Some(recipient) = records.recipient {
+            insert(RECIPIENT, one_of(recipient));
+        }
+        if let Some(protocol) = records.protocol {
+            insert(PROTOCOL, Filter::Equal(Value::String(protocol)));
+        }
+        if let Some(protocol_path) = records.protocol_path {
+            insert(PROTOCOL_PATH, Filter::Equal(Value::String(protocol_path)));
+        }
+        if let Some(context_id) = records.context_id {
+            // a context ID matches the record with that context, and all of its descendants.
+            insert(CONTEXT_ID, Filter::Prefix(Value::String(context_id)));
+        }
+        if let Some(schema) = records.schema {
+            insert(SCHEMA, Filter::Equal(Value::String(schema)));
+        }
+        if let Some(record_id) = records.record_id {
+            insert(RECORD_ID, Filter::Equal(Value::String(record_id)));
+        }
+        if let Some(parent_id) = records.parent_id {
+            insert(PARENT_ID, Filter::Equal(Value::String(parent_id)));
+        }
+        if let Some(data_format) = records.data_format {
+            insert(DATA_FORMAT, Filter::Equal(Value::String(data_format)));
+        }
+        if let Some(data_cid) = records.data_cid {
+            insert(DATA_CID, Filter::Equal(Value::String(data_cid.to_string())));
+        }
+        if let Some(data_size) = records.data_size {
+            insert(
+                DATA_SIZE,
+                Filter::Range(map_range(data_size, |size| Value::Number(size as i64))),
+            );
+        }
+        if let Some(date_created) = records.date_created {
+            insert(DATE_CREATED, Filter::Range(map_range(date_created, date)));
+        }
+        if let Some(date_updated) = records.date_updated {
+            insert(
+                MESSAGE_TIMESTAMP,
+                Filter::Range(map_range(date_updated, date)),
+            );
+        }
+
+        // filtering on the date a record was published implies that the record is published.
+        let published = match records.date_published {
+            Some(date_published) => {
+                insert(
+                    DATE_PUBLISHED,
+                    Filter::Range(map_range(date_published, date)),
+                );
+                Some(records.published.unwrap_or(true))
+            }
+            None => records.published,
+        };
+        if let Some(published) = published {
+            insert(PUBLISHED, Filter::Equal(Value::Bool(published)));
+        }
+
+        for (tag, value) in records.tags.unwrap_or_default() {
+            filter.insert(FilterKey::Tag(tag), value);
+        }
+
+        filter
+    }
+}
+
+impl From<Records> for Filters {
+    fn from(records: Records) -> Self {
+        Filters::from(ValueFilter::<FilterKey>::from(records))
+    }
+}
+
+// one_of returns an equality filter for a single value, or a one-of filter for many values.
+fn one_of(mut values: Vec<String>) -> Filter<Value> {
+    match values.len() {
+        1 => Filter::Equal(Value::String(values.remove(0))),
+        _ => Filter::OneOf(values.into_iter().map(Value::String).collect()),
+    }
+}
+
+// date returns the value for a date filter, which is indexed as a datetime when it can be
+// parsed.
+fn date(value: String) -> Value {
+    match DateTime::parse_from_rfc3339(&value) {
+        Ok(date) => Value::DateTime(date.with_timezone(&Utc)),
+        Err(_) => Value::String(value),
+    }
+}
+
+fn map_range<T, F>(range: RangeFilter<T>, f: F) -> RangeFilter<Value>
+where
+    F: Fn(T) -> Value,
+{
+    let map_bound = |bound: Bound<T>| match bound {
+        Bound::Included(v) => Bound::Included(f(v)),
+        Bound::Excluded(v) => Bound::Excluded(f(v)),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+
+    match range {
+        RangeFilter::Numeric(lower, upper) => {
+            RangeFilter::Numeric(map_bound(lower), map_bound(upper))
+        }
+        RangeFilter::Criterion(lower, upper) => {
+            RangeFilter::Criterion(map_bound(lower), map_bound(upper))
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[skip_serializing_none()]
 pub struct Messages {
@@ -50,3 +181,157 @@ pub struct Messages {
     #[serde(rename = "messageTimestamp")]
     pub message_timestamp: Option<chrono::DateTime<chrono::Utc>>,
 }
+
+impl From<Messages> for ValueFilter<FilterKey> {
+    fn from(messages: Messages) -> Self {
+        let mut filter = ValueFilter::new();
+
+        let equal = [
+            (INTERFACE, messages.interface),
+            (METHOD, messages.method),
+            (PROTOCOL, messages.protocol),
+        ];
+        for (key, value) in equal {
+            if let Some(value) = value {
+                filter.insert(
+                    FilterKey::Index(key.to_string()),
+                    Filter::Equal(Value::String(value)),
+                );
+            }
+        }
+
+        // messages are matched from the given timestamp onwards.
+        if let Some(message_timestamp) = messages.message_timestamp {
+            filter.insert(
+                FilterKey::Index(MESSAGE_TIMESTAMP.to_string()),
+                Filter::Range(RangeFilter::Criterion(
+                    Bound::Included(Value::DateTime(message_timestamp)),
+                    Bound::Unbounded,
+                )),
+            );
+        }
+
+        filter
+    }
+}
+
+impl From<Messages> for Filters {
+    fn from(messages: Messages) -> Self {
+        Filters::from(ValueFilter::<FilterKey>::from(messages))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_records_into_filters() {
+        let records = Records {
+            author: Some(vec!["did:example:alice".to_string()]),
+            recipient: Some(vec![
+                "did:example:bob".to_string(),
+                "did:example:carol".to_string(),
+            ]),
+            protocol_path: Some("thread".to_string()),
+            tags: Some(BTreeMap::from([(
+                "status".to_string(),
+                Filter::Equal(Value::String("draft".to_string())),
+            )])),
+            data_size: Some(RangeFilter::Numeric(Bound::Included(10), Bound::Unbounded)),
+            date_updated: Some(RangeFilter::Criterion(
+                Bound::Excluded("2024-01-01T00:00:00.000000Z".to_string()),
+                Bound::Unbounded,
+            )),
+            date_published: Some(RangeFilter::Criterion(
+                Bound::Unbounded,
+                Bound::Included("not a date".to_string()),
+            )),
+            ..Default::default()
+        };
+
+        let timestamp = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let expected = ValueFilter::from([
+            (
+                FilterKey::Index("author".to_string()),
+                Filter::Equal(Value::String("did:example:alice".to_string())),
+            ),
+            (
+                FilterKey::Index("recipient".to_string()),
+                Filter::OneOf(vec![
+                    Value::String("did:example:bob".to_string()),
+                    Value::String("did:example:carol".to_string()),
+                ]),
+            ),
+            (
+                FilterKey::Index("protocolPath".to_string()),
+                Filter::Equal(Value::String("thread".to_string())),
+            ),
+            (
+                FilterKey::Index("dataSize".to_string()),
+                Filter::Range(RangeFilter::Numeric(
+                    Bound::Included(Value::Number(10)),
+                    Bound::Unbounded,
+                )),
+            ),
+            (
+                FilterKey::Index("messageTimestamp".to_string()),
+                Filter::Range(RangeFilter::Criterion(
+                    Bound::Excluded(Value::DateTime(timestamp)),
+                    Bound::Unbounded,
+                )),
+            ),
+            (
+                FilterKey::Index("datePublished".to_string()),
+                Filter::Range(RangeFilter::Criterion(
+                    Bound::Unbounded,
+                    Bound::Included(Value::String("not a date".to_string())),
+                )),
+            ),
+            (
+                FilterKey::Index("published".to_string()),
+                Filter::Equal(Value::Bool(true)),
+            ),
+            (
+                FilterKey::Tag("status".to_string()),
+                Filter::Equal(Value::String("draft".to_string())),
+            ),
+        ]);
+
+        assert_eq!(ValueFilter::<FilterKey>::from(records.clone()), expected);
+        assert_eq!(Filters::from(records), Filters::from(expected));
+    }
+
+    #[test]
+    fn test_messages_into_filters() {
+        let timestamp = Utc::now();
+        let messages = Messages {
+            interface: Some("Records".to_string()),
+            method: None,
+            protocol: Some("http://example.com/".to_string()),
+            message_timestamp: Some(timestamp),
+        };
+
+        let expected = ValueFilter::from([
+            (
+                FilterKey::Index("interface".to_string()),
+                Filter::Equal(Value::String("Records".to_string())),
+            ),
+            (
+                FilterKey::Index("protocol".to_string()),
+                Filter::Equal(Value::String("http://example.com/".to_string())),
+            ),
+            (
+                FilterKey::Index("messageTimestamp".to_string()),
+                Filter::Range(RangeFilter::Criterion(
+                    Bound::Included(Value::DateTime(timestamp)),
+                    Bound::Unbounded,
+                )),
+            ),
+        ]);
+
+        assert_eq!(Filters::from(messages), Filters::from(expected));
+    }
+}
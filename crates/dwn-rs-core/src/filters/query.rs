@@ -7,6 +7,7 @@ use serde_repr::{Deserialize_repr, Serialize_repr};
 use serde_with::{serde_as, DisplayFromStr};
 
 use crate::filters::{errors, Filters};
+use crate::indexes::{DATE_CREATED, DATE_PUBLISHED, MESSAGE_TIMESTAMP};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct Pagination {
@@ -115,20 +116,20 @@ impl Ordorable for MessageSort {
         match self {
             MessageSort::DateCreated(direction) => {
                 vec![
-                    ("dateCreated", direction.to_bool()),
+                    (DATE_CREATED, direction.to_bool()),
                     ("cid", direction.to_bool()),
                 ]
             }
 
             MessageSort::DatePublished(direction) => {
                 vec![
-                    ("datePublished", direction.to_bool()),
+                    (DATE_PUBLISHED, direction.to_bool()),
                     ("cid", direction.to_bool()),
                 ]
             }
             MessageSort::Timestamp(direction) => {
                 vec![
-                    ("messageTimestamp", direction.to_bool()),
+                    (MESSAGE_TIMESTAMP, direction.to_bool()),
                     ("cid", direction.to_bool()),
                 ]
             }
@@ -0,0 +1,366 @@
+use cid::Cid;
+use tracing::instrument;
+use xtra::Address;
+
+use crate::{
+    auth::{Authorization, GrantAuthorizer, KeyResolver},
+    descriptors::{
+        MessagesQueryDescriptor, MessagesReadDescriptor, MessagesSubscribeDescriptor, Records,
+    },
+    errors::{EventStreamError, HandlerError, MessageStoreError, StoreError},
+    fields::{MessageFields, WriteFields},
+    filters::{Cursor, Filters},
+    handlers::{message_cid, Signers},
+    indexes::MessageIndexes,
+    replay::{Live, Replay, Replayed},
+    replies::{
+        messages::{Query, Read, ReadEntry},
+        Status, Subscribe,
+    },
+    stores::{DataStore, EventLog, MessageStore, ResumableTaskStore},
+    Descriptor, Dwn, Event, EventChannel, Message, MessageEvent, MessageReply, Reply, Subscription,
+};
+
+impl<MS, DS, EL, RT, R> Dwn<MS, DS, EL, RT, R>
+where
+    MS: MessageStore,
+    DS: DataStore,
+    EL: EventLog,
+    RT: ResumableTaskStore,
+    R: KeyResolver,
+{
+    /// handle_messages_read handles a MessagesRead message, returning the stored message with the
+    /// given CID, along with its data if the message is a RecordsWrite.
+    #[instrument(skip(self, message, signers))]
+    pub(crate) async fn handle_messages_read(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+        signers: Signers,
+    ) -> Result<MessageReply, HandlerError> {
+        let read: Message<MessagesReadDescriptor> = message.clone().try_into_message()?;
+        let cid = read
+            .descriptor
+            .message_cid
+            .ok_or_else(|| HandlerError::InvalidMessage("messageCid is required".to_string()))?;
+
+        let mut stored = self
+            .message_store
+            .get::<Descriptor>(tenant, &cid.to_string())
+            .await
+            .map_err(|err| match err {
+                MessageStoreError::StoreError(StoreError::NotFound) => HandlerError::NotFound,
+                err => err.into(),
+            })?;
+        self.authorize_messages_read(tenant, &read.fields, &message.descriptor, &stored, signers)
+            .await?;
+
+        let encoded_data = stored.fields.encoded_data();
+        let data = match &stored.descriptor {
+            Descriptor::Records(Records::Write(descriptor)) => {
+                let fields = WriteFields::from(stored.fields.clone());
+                match fields.record_id {
+                    Some(record_id) => match self
+                        .record_data(tenant, &record_id, descriptor, encoded_data)
+                        .await
+                    {
+                        Ok(data) => Some(data),
+                        // a write may be stored without its data.
+                        Err(HandlerError::NotFound) => None,
+                        Err(err) => return Err(err),
+                    },
+                    None => None,
+                }
+            }
+            _ => None,
+        };
+
+        let reply = MessageReply::new(
+            Status::ok(),
+            Reply::MessageRead(Read {
+                entry: Some(ReadEntry {
+                    cid,
+                    message: Some(stored),
+                }),
+            }),
+        );
+
+        Ok(match data {
+            Some(data) => reply.with_data(data),
+            None => reply,
+        })
+    }
+
+    /// handle_messages_query handles a MessagesQuery message, returning the CIDs of the events
+    /// in the event log which match the query filters, after the query cursor.
+    #[instrument(skip(self, message, signers))]
+    pub(crate) async fn handle_messages_query(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+        signers: Signers,
+    ) -> Result<MessageReply, HandlerError> {
+        let query: Message<MessagesQueryDescriptor> = message.clone().try_into_message()?;
+        self.authorize_messages(tenant, &query.fields, &message.descriptor, signers)
+            .await?;
+
+        let cursor = query.descriptor.cursor;
+        let events = match query.descriptor.filters.is_empty() {
+            true => self.event_log.get_events(tenant, cursor).await?,
+            false => {
+                let filters = query.descriptor.filters.into_iter().collect::<Filters>();
+                self.event_log.query_events(tenant, filters, cursor).await?
+            }
+        };
+
+        let entries = events
+            .items
+            .iter()
+            .map(|cid| {
+                Cid::try_from(cid.as_str()).map_err(|err| {
+                    HandlerError::InvalidMessage(format!("invalid event CID {}: {}", cid, err))
+                })
+            })
+            .collect::<Result<Vec<Cid>, HandlerError>>()?;
+
+        Ok(MessageReply::new(
+            Status::ok(),
+            Reply::MessageQuery(Query {
+                entries: Some(entries),
+                cursor: events.cursor,
+            }),
+        ))
+    }
+
+    /// handle_messages_subscribe handles a MessagesSubscribe message, subscribing the listener
+    /// to the events emitted for the tenant. If a cursor is given, the events logged after the
+    /// cursor are replayed to the listener before it receives live events.
+    #[instrument(skip(self, message, signers, listener))]
+    pub(crate) async fn handle_messages_subscribe(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+        signers: Signers,
+        cursor: Option<Cursor>,
+        listener: EventChannel<Descriptor>,
+    ) -> Result<MessageReply, HandlerError> {
+        let id = message_cid(&message)?.to_string();
+        let subscribe: Message<MessagesSubscribeDescriptor> = message.clone().try_into_message()?;
+        self.authorize_messages(tenant, &subscribe.fields, &message.descriptor, signers)
+            .await?;
+
+        let filters = subscribe
+            .descriptor
+            .filters
+            .into_iter()
+            .collect::<Filters>();
+        let subscription = match cursor {
+            Some(cursor) => {
+                self.subscribe_from(tenant, &id, filters, cursor, listener)
+                    .await?
+            }
+            None => {
+                self.event_stream
+                    .subscribe(tenant, &id, filters, listener)
+                    .await?
+            }
+        };
+
+        Ok(MessageReply::new(
+            Status::ok(),
+            Reply::Subscribe(Subscribe {
+                subscription: Some(subscription.subscription_id.clone()),
+            }),
+        )
+        .with_subscription(subscription))
+    }
+
+    /// subscribe_from subscribes the listener to the events emitted for the tenant, replaying
+    /// the events logged after the cursor first. Events emitted during the replay are held back
+    /// until it finishes, so that none are missed, and those which were replayed are skipped.
+    /// The held events are bounded like the subscription's own queue.
+    async fn subscribe_from(
+        &self,
+        tenant: &str,
+        id: &str,
+        filters: Filters,
+        cursor: Cursor,
+        listener: EventChannel<Descriptor>,
+    ) -> Result<Subscription, HandlerError> {
+        let options = self.event_stream.subscription_options();
+        let replay = Replay::new(id, listener, options).run();
+        let subscription = self
+            .event_stream
+            .subscribe(tenant, id, filters.clone(), Replay::channel(replay.clone()))
+            .await?;
+
+        if let Err(err) = self.replay_events(tenant, filters, cursor, &replay).await {
+            (subscription.close)().await?;
+            return Err(err);
+        }
+
+        Ok(subscription)
+    }
+
+    /// replay_events sends the events logged after the cursor which match the filters to the
+    /// replay, paging through the event log until there are no more events, and then switches
+    /// the replay to live events.
+    async fn replay_events(
+        &self,
+        tenant: &str,
+        filters: Filters,
+        mut cursor: Cursor,
+        replay: &Address<Replay>,
+    ) -> Result<(), HandlerError> {
+        loop {
+            let events = self
+                .event_log
+                .query_events(tenant, filters.clone(), Some(cursor.clone()))
+                .await?;
+            if events.items.is_empty() {
+                break;
+            }
+
+            for cid in events.items {
+                let message = match self.message_store.get::<Descriptor>(tenant, &cid).await {
+                    Ok(message) => message,
+                    // the message was deleted after it was logged.
+                    Err(MessageStoreError::StoreError(StoreError::NotFound)) => continue,
+                    Err(err) => return Err(err.into()),
+                };
+
+                let event = self.replay_event(tenant, message).await?;
+                replay
+                    .send(Replayed { cid, event })
+                    .await
+                    .map_err(EventStreamError::from)??;
+            }
+
+            match events.cursor {
+                Some(next) if next != cursor => cursor = next,
+                _ => break,
+            }
+        }
+
+        replay.send(Live).await.map_err(EventStreamError::from)??;
+        Ok(())
+    }
+
+    /// replay_event creates the event for a message replayed from the event log, as it was
+    /// emitted when the message was processed. Records messages carry the record's initial
+    /// write.
+    async fn replay_event(
+        &self,
+        tenant: &str,
+        mut message: Message<Descriptor>,
+    ) -> Result<Event<Descriptor>, HandlerError> {
+        message.fields.strip_encoded_data();
+        let key_values = message.key_values()?;
+
+        let record_id = match &message.descriptor {
+            Descriptor::Records(Records::Delete(descriptor)) => Some(descriptor.record_id.clone()),
+            // the initial write of a record is emitted without an initial write.
+            Descriptor::Records(Records::Write(descriptor))
+                if descriptor.message_timestamp != descriptor.date_created =>
+            {
+                WriteFields::from(message.fields.clone()).record_id
+            }
+            _ => None,
+        };
+        let initial_write = match record_id {
+            Some(record_id) => self.record_initial_write(tenant, &record_id).await?,
+            None => None,
+        };
+
+        Ok((
+            tenant.to_string(),
+            MessageEvent {
+                message,
+                initial_write,
+            },
+            key_values,
+        ))
+    }
+
+    /// authorize_messages_read authorizes a MessagesRead of the stored message. Grants scoped to
+    /// a protocol only allow reading the messages of that protocol, which for Records messages is
+    /// the protocol of the record.
+    async fn authorize_messages_read(
+        &self,
+        tenant: &str,
+        authorization: &Authorization,
+        descriptor: &Descriptor,
+        stored: &Message<Descriptor>,
+        signers: Signers,
+    ) -> Result<(), HandlerError> {
+        let record = match &stored.descriptor {
+            Descriptor::Records(Records::Write(_)) => Some(stored.clone().try_into_message()?),
+            Descriptor::Records(Records::Delete(delete)) => {
+                self.record_initial_write(tenant, &delete.record_id).await?
+            }
+            _ => None,
+        };
+
+        let granted = GrantAuthorizer::new(&self.message_store, tenant)
+            .with_target(&stored.descriptor)
+            .authorize_message(authorization, descriptor, record.as_ref())
+            .await?;
+
+        match granted {
+            true => Ok(()),
+            false => authorize_tenant(tenant, signers.author),
+        }
+    }
+
+    /// authorize_messages authorizes a Messages interface message, which must either be authored
+    /// by the tenant or invoke a permission grant of the tenant.
+    async fn authorize_messages(
+        &self,
+        tenant: &str,
+        authorization: &Authorization,
+        descriptor: &Descriptor,
+        signers: Signers,
+    ) -> Result<(), HandlerError> {
+        let granted = GrantAuthorizer::new(&self.message_store, tenant)
+            .authorize_message(authorization, descriptor, None)
+            .await?;
+
+        match granted {
+            true => Ok(()),
+            false => authorize_tenant(tenant, signers.author),
+        }
+    }
+}
+
+/// authorize_tenant checks that the author of a Messages interface message is the tenant.
+fn authorize_tenant(tenant: &str, author: Option<String>) -> Result<(), HandlerError> {
+    match author {
+        Some(author) if author == tenant => Ok(()),
+        Some(_) => Err(HandlerError::Unauthorized(
+            "Messages interface methods must be authored by the tenant".to_string(),
+        )),
+        None => Err(HandlerError::Unauthorized(
+            "Messages interface methods must be signed".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_authorize_tenant() {
+        let tenant = "did:example:alice";
+
+        assert!(authorize_tenant(tenant, Some(tenant.to_string())).is_ok());
+        assert!(matches!(
+            authorize_tenant(tenant, Some("did:example:bob".to_string())),
+            Err(HandlerError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            authorize_tenant(tenant, None),
+            Err(HandlerError::Unauthorized(_))
+        ));
+    }
+}
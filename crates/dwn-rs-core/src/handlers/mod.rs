@@ -0,0 +1,161 @@
+//! Handlers for each of the DWN interface methods supported by [`crate::Dwn`]. Each handler is
+//! implemented on the `Dwn` itself, so that it has access to the stores and event stream.
+mod messages;
+mod protocols;
+mod records;
+
+use cid::Cid;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    auth::KeyResolver,
+    descriptors::Records,
+    errors::{HandlerError, MessageStoreError},
+};
+use crate::{
+    stores::{DataStore, EventLog, MessageStore, ResumableTaskStore},
+    Descriptor, Dwn, Fields, Message,
+};
+
+/// TASK_TIMEOUT is the number of seconds a resumable task is held for before it is retried.
+pub(crate) const TASK_TIMEOUT: u64 = 60;
+
+/// ResumableTask is a task registered with the resumable task store while a handler performs
+/// work which must be completed, even if the DWN stops part way through.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) enum ResumableTask {
+    RecordsDelete {
+        tenant: String,
+        message: Message<Descriptor>,
+    },
+}
+
+/// RESUME_BATCH_SIZE is the number of resumable tasks grabbed at a time when resuming tasks.
+const RESUME_BATCH_SIZE: u64 = 100;
+
+/// Signers are the DIDs of the author and owner of a message, once their signatures have been
+/// verified. Handlers use these, rather than reading the (unverified) signers of the message.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct Signers {
+    pub(crate) author: Option<String>,
+    pub(crate) owner: Option<String>,
+}
+
+impl<MS, DS, EL, RT, R> Dwn<MS, DS, EL, RT, R>
+where
+    MS: MessageStore,
+    DS: DataStore,
+    EL: EventLog,
+    RT: ResumableTaskStore,
+    R: KeyResolver,
+{
+    /// verify_message verifies the signatures of a message before it is handled, using the
+    /// resolver of the DWN: the author signature (if the message is signed), the owner signature
+    /// and, for a RecordsWrite, its attestation. Any delegated grants used to sign the message
+    /// are verified too.
+    pub(crate) async fn verify_message(
+        &self,
+        message: &Message<Descriptor>,
+    ) -> Result<Signers, HandlerError> {
+        let authorization = message.fields.authorization();
+
+        let author = match authorization.signer()? {
+            Some(_) => {
+                let (author, _) = authorization
+                    .verify(&message.descriptor, &self.resolver)
+                    .await?;
+                Some(author)
+            }
+            None => None,
+        };
+        let owner = authorization
+            .verify_owner(&message.descriptor, &self.resolver)
+            .await?
+            .map(|(owner, _)| owner);
+
+        if let Descriptor::Records(Records::Write(ref descriptor)) = message.descriptor {
+            let fields = match message.fields {
+                Fields::Write(ref fields) => Some(fields),
+                Fields::InitialWriteField(ref fields) => Some(&fields.write_fields),
+                Fields::Authorization(_) => None,
+            };
+            if let Some(fields) = fields {
+                fields
+                    .verify_attestation(descriptor, &self.resolver)
+                    .await?;
+            }
+        }
+
+        Ok(Signers { author, owner })
+    }
+
+    /// resume_tasks performs any resumable tasks which were not completed. Tasks which fail are
+    /// left in the task store, and are retried once their timeout expires.
+    pub(crate) async fn resume_tasks(&self) -> Result<(), HandlerError> {
+        loop {
+            let tasks = self
+                .task_store
+                .grab::<ResumableTask>(RESUME_BATCH_SIZE)
+                .await?;
+            if tasks.is_empty() {
+                return Ok(());
+            }
+
+            for task in tasks {
+                let result = match task.task {
+                    ResumableTask::RecordsDelete { tenant, message } => {
+                        self.perform_records_delete(&tenant, message).await
+                    }
+                };
+
+                match result {
+                    // a record which no longer exists has nothing left to delete.
+                    Ok(()) | Err(HandlerError::NotFound) => {
+                        self.task_store.delete(&task.id.to_string()).await?
+                    }
+                    Err(err) => warn!(task = %task.id, error = %err, "unable to resume task"),
+                }
+            }
+        }
+    }
+}
+
+/// message_cid returns the CID of a message as it is stored in the message store, which is
+/// without any encoded data.
+pub(crate) fn message_cid(message: &Message<Descriptor>) -> Result<Cid, HandlerError> {
+    Ok(message.stored_cid().map_err(MessageStoreError::from)?)
+}
+
+/// is_newer returns true if message `a` is newer than message `b`. Messages are compared by
+/// their `messageTimestamp`, and ties are broken by comparing the message CIDs.
+pub(crate) fn is_newer(
+    a: &Message<Descriptor>,
+    b: &Message<Descriptor>,
+) -> Result<bool, HandlerError> {
+    let (a_timestamp, b_timestamp) = (
+        a.descriptor.message_timestamp(),
+        b.descriptor.message_timestamp(),
+    );
+    if a_timestamp != b_timestamp {
+        return Ok(a_timestamp > b_timestamp);
+    }
+
+    Ok(message_cid(a)?.to_string() > message_cid(b)?.to_string())
+}
+
+/// newest_message returns the newest message of the given messages, if any.
+pub(crate) fn newest_message(
+    messages: &[Message<Descriptor>],
+) -> Result<Option<&Message<Descriptor>>, HandlerError> {
+    let mut newest: Option<&Message<Descriptor>> = None;
+
+    for message in messages {
+        match newest {
+            Some(current) if !is_newer(message, current)? => {}
+            _ => newest = Some(message),
+        }
+    }
+
+    Ok(newest)
+}
@@ -0,0 +1,198 @@
+use tracing::{debug, instrument};
+
+use crate::{
+    auth::{GrantAuthorizer, KeyResolver},
+    descriptors::{ConfigureDescriptor, ProtocolQueryDescriptor, CONFIGURE, PROTOCOLS},
+    errors::HandlerError,
+    filters::{Filter, FilterKey, Filters, MessageSort, SortDirection, ValueFilter},
+    handlers::{is_newer, message_cid, Signers},
+    indexes::{MessageIndexes, INTERFACE, METHOD, PROTOCOL, PUBLISHED},
+    protocols::Definition,
+    replies::{protocols::Query, Empty, Status},
+    stores::{DataStore, EventLog, MessageStore, ResumableTaskStore},
+    Descriptor, Dwn, MapValue, Message, MessageEvent, MessageReply, Reply, Value,
+};
+
+impl<MS, DS, EL, RT, R> Dwn<MS, DS, EL, RT, R>
+where
+    MS: MessageStore,
+    DS: DataStore,
+    EL: EventLog,
+    RT: ResumableTaskStore,
+    R: KeyResolver,
+{
+    /// handle_protocols_configure handles a ProtocolsConfigure message. A protocol configuration
+    /// replaces any existing configuration of the same protocol, if it is newer.
+    #[instrument(skip(self, message, signers))]
+    pub(crate) async fn handle_protocols_configure(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+        signers: Signers,
+    ) -> Result<MessageReply, HandlerError> {
+        let configure: Message<ConfigureDescriptor> = message.clone().try_into_message()?;
+        let author = signers.author.ok_or_else(|| {
+            HandlerError::Unauthorized("ProtocolsConfigure must be signed".to_string())
+        })?;
+
+        let granted = GrantAuthorizer::new(&self.message_store, tenant)
+            .authorize_message(&configure.fields, &message.descriptor, None)
+            .await?;
+        if author != tenant && !granted {
+            return Err(HandlerError::Unauthorized(
+                "ProtocolsConfigure must be authored by the tenant".to_string(),
+            ));
+        }
+
+        let protocol = &configure.descriptor.definition.protocol;
+        let existing = self
+            .protocol_configurations(tenant, Some(protocol), false)
+            .await?;
+
+        for previous in &existing {
+            if !is_newer(&message, previous)? {
+                return Err(HandlerError::Conflict(
+                    "a newer ProtocolsConfigure exists for the protocol".to_string(),
+                ));
+            }
+        }
+
+        let cid = message_cid(&message)?.to_string();
+        let indexes = configure.indexes()?;
+
+        self.message_store
+            .put(tenant, message.clone(), indexes.clone(), MapValue::new())
+            .await?;
+        self.event_log
+            .append(tenant, &cid, indexes.clone(), MapValue::new())
+            .await?;
+
+        let mut replaced = Vec::with_capacity(existing.len());
+        for previous in &existing {
+            let previous_cid = message_cid(previous)?.to_string();
+            self.message_store.delete(tenant, &previous_cid).await?;
+            replaced.push(previous_cid);
+        }
+        if !replaced.is_empty() {
+            let cids = replaced.iter().map(String::as_str).collect::<Vec<&str>>();
+            self.event_log.delete(tenant, &cids).await?;
+        }
+
+        let event = MessageEvent {
+            message,
+            initial_write: None,
+        };
+        self.event_stream.emit(tenant, event, indexes).await;
+
+        debug!(cid = %cid, protocol = %protocol, "configured protocol");
+
+        Ok(MessageReply::new(
+            Status::accepted(),
+            Reply::Empty(Empty {}),
+        ))
+    }
+
+    /// handle_protocols_query handles a ProtocolsQuery message. The tenant, and those they have
+    /// granted the query to, may query all of their protocol configurations, while other
+    /// requesters only see published protocols.
+    #[instrument(skip(self, message, signers))]
+    pub(crate) async fn handle_protocols_query(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+        signers: Signers,
+    ) -> Result<MessageReply, HandlerError> {
+        let query: Message<ProtocolQueryDescriptor> = message.clone().try_into_message()?;
+        let requester = signers.author;
+
+        // grants invoked by the query (including a delegated grant of the tenant's) are
+        // authorized before deciding what the requester may see.
+        let granted = GrantAuthorizer::new(&self.message_store, tenant)
+            .authorize_message(&query.fields, &message.descriptor, None)
+            .await?;
+
+        let protocol = query
+            .descriptor
+            .filter
+            .as_ref()
+            .and_then(|filter| filter.protocol.as_deref());
+        let published_only = requester.as_deref() != Some(tenant) && !granted;
+
+        let entries = self
+            .protocol_configurations(tenant, protocol, published_only)
+            .await?
+            .into_iter()
+            .map(|message| message.try_into_message())
+            .collect::<Result<Vec<Message<ConfigureDescriptor>>, _>>()?;
+
+        Ok(MessageReply::new(
+            Status::ok(),
+            Reply::ProtocolsQuery(Query {
+                entries: Some(entries),
+            }),
+        ))
+    }
+
+    /// protocol_definition returns the definition of a protocol configured by the tenant. The
+    /// protocol must be configured for records of the protocol to be written.
+    pub(crate) async fn protocol_definition(
+        &self,
+        tenant: &str,
+        protocol: &str,
+    ) -> Result<Definition, HandlerError> {
+        let configuration = self
+            .protocol_configurations(tenant, Some(protocol), false)
+            .await?
+            .pop()
+            .ok_or_else(|| {
+                HandlerError::InvalidMessage(format!("protocol {} is not configured", protocol))
+            })?;
+        let configuration: Message<ConfigureDescriptor> = configuration.try_into_message()?;
+
+        Ok(configuration.descriptor.definition)
+    }
+
+    /// protocol_configurations returns the stored ProtocolsConfigure messages, optionally for
+    /// only the given protocol, or only published protocols.
+    async fn protocol_configurations(
+        &self,
+        tenant: &str,
+        protocol: Option<&str>,
+        published_only: bool,
+    ) -> Result<Vec<Message<Descriptor>>, HandlerError> {
+        let mut filter = ValueFilter::<FilterKey>::from([
+            (
+                FilterKey::Index(INTERFACE.to_string()),
+                Filter::Equal(Value::String(PROTOCOLS.to_string())),
+            ),
+            (
+                FilterKey::Index(METHOD.to_string()),
+                Filter::Equal(Value::String(CONFIGURE.to_string())),
+            ),
+        ]);
+        if let Some(protocol) = protocol {
+            filter.insert(
+                FilterKey::Index(PROTOCOL.to_string()),
+                Filter::Equal(Value::String(protocol.to_string())),
+            );
+        }
+        if published_only {
+            filter.insert(
+                FilterKey::Index(PUBLISHED.to_string()),
+                Filter::Equal(Value::Bool(true)),
+            );
+        }
+
+        let messages = self
+            .message_store
+            .query::<Descriptor>(
+                tenant,
+                Filters::from(filter),
+                Some(MessageSort::Timestamp(SortDirection::Ascending)),
+                None,
+            )
+            .await?;
+
+        Ok(messages.items)
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::BTreeSet;
+
+use tracing::{debug, instrument};
+
+use crate::{
+    auth::{GrantAuthorizer, KeyResolver, ProtocolAuthorizer},
+    descriptors::{DeleteDescriptor, Records, RecordsWriteDescriptor, RECORDS},
+    errors::HandlerError,
+    fields::{MessageFields, WriteFields},
+    filters::{Filter, FilterKey, Filters, ValueFilter},
+    handlers::{is_newer, message_cid, newest_message, ResumableTask, Signers, TASK_TIMEOUT},
+    indexes::{flatten_tags, MessageIndexes, INTERFACE, PARENT_ID},
+    replies::{Empty, Status},
+    stores::{DataStore, EventLog, MessageStore, ResumableTaskStore},
+    Descriptor, Dwn, Message, MessageEvent, MessageReply, Reply, Value,
+};
+
+use super::{
+    delete_indexes, initial_write, write_descriptor, write_indexes, MAX_ENCODED_DATA_SIZE,
+};
+
+impl<MS, DS, EL, RT, R> Dwn<MS, DS, EL, RT, R>
+where
+    MS: MessageStore,
+    DS: DataStore,
+    EL: EventLog,
+    RT: ResumableTaskStore,
+    R: KeyResolver,
+{
+    /// handle_records_delete handles a RecordsDelete message. The delete is stored as a
+    /// tombstone for the record, and all other messages and data for the record are removed,
+    /// except for the initial write. When `prune` is set, all descendants of the record are
+    /// removed as well.
+    #[instrument(skip(self, message, signers))]
+    pub(crate) async fn handle_records_delete(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+        signers: Signers,
+    ) -> Result<MessageReply, HandlerError> {
+        let delete: Message<DeleteDescriptor> = message.clone().try_into_message()?;
+        let author = signers.author.ok_or_else(|| {
+            HandlerError::Unauthorized("RecordsDelete must be signed".to_string())
+        })?;
+
+        let existing = self
+            .record_messages(tenant, &delete.descriptor.record_id)
+            .await?;
+        match newest_message(&existing)? {
+            None => return Err(HandlerError::NotFound),
+            Some(newest) if write_descriptor(newest).is_none() => {
+                return Err(HandlerError::NotFound)
+            }
+            Some(newest) if !is_newer(&message, newest)? => {
+                return Err(HandlerError::Conflict(
+                    "a newer message exists for the record".to_string(),
+                ))
+            }
+            Some(_) => {}
+        }
+
+        let initial: Message<RecordsWriteDescriptor> = initial_write(&existing)
+            .ok_or(HandlerError::NotFound)?
+            .clone()
+            .try_into_message()?;
+        let granted = GrantAuthorizer::new(&self.message_store, tenant)
+            .authorize_message(&delete.fields, &message.descriptor, Some(&initial))
+            .await?;
+
+        // authors other than the tenant may only delete records which a protocol (or a
+        // permission grant of the tenant) allows them to.
+        if author != tenant && !granted {
+            let protocol = initial.descriptor.protocol.as_deref().ok_or_else(|| {
+                HandlerError::Unauthorized(
+                    "RecordsDelete must be authored by the tenant".to_string(),
+                )
+            })?;
+            let definition = self.protocol_definition(tenant, protocol).await?;
+
+            ProtocolAuthorizer::new(&self.message_store, tenant, &definition)
+                .authorize_delete(&delete, &initial)
+                .await?;
+        }
+
+        // the delete is registered as a resumable task, so that it is completed when the DWN is
+        // next opened if it is interrupted.
+        let task = self
+            .task_store
+            .register(
+                ResumableTask::RecordsDelete {
+                    tenant: tenant.to_string(),
+                    message: message.clone(),
+                },
+                TASK_TIMEOUT,
+            )
+            .await?;
+
+        self.perform_records_delete(tenant, message).await?;
+        self.task_store.delete(&task.id.to_string()).await?;
+
+        Ok(MessageReply::new(
+            Status::accepted(),
+            Reply::Empty(Empty {}),
+        ))
+    }
+
+    /// perform_records_delete stores the tombstone for a record, and removes the superseded
+    /// messages and data of the record (and its descendants, when pruning). It may be run more
+    /// than once for the same delete, when resuming an interrupted delete.
+    pub(crate) async fn perform_records_delete(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+    ) -> Result<(), HandlerError> {
+        let delete: Message<DeleteDescriptor> = message.clone().try_into_message()?;
+        let record_id = &delete.descriptor.record_id;
+
+        let existing = self.record_messages(tenant, record_id).await?;
+        let mut initial = initial_write(&existing)
+            .ok_or(HandlerError::NotFound)?
+            .clone();
+        initial.fields.strip_encoded_data();
+        let initial: Message<RecordsWriteDescriptor> = initial.try_into_message()?;
+
+        if delete.descriptor.prune {
+            self.prune_descendants(tenant, record_id).await?;
+        }
+
+        let cid = message_cid(&message)?.to_string();
+        let mut superseded = Vec::new();
+        let mut stored = false;
+        for previous in &existing {
+            if message_cid(previous)?.to_string() == cid {
+                stored = true;
+            } else {
+                superseded.push(previous.clone());
+            }
+        }
+
+        let indexes = delete_indexes(&delete, &initial)?;
+        let tags = initial.tags();
+        if !stored {
+            self.message_store
+                .put(tenant, message.clone(), indexes.clone(), tags.clone())
+                .await?;
+            self.event_log
+                .append(tenant, &cid, indexes.clone(), tags.clone())
+                .await?;
+        }
+
+        self.purge_messages(tenant, record_id, &superseded, Some(&initial))
+            .await?;
+
+        let event = MessageEvent {
+            message,
+            initial_write: Some(initial),
+        };
+        let mut key_values = indexes;
+        key_values.extend(flatten_tags(tags));
+        self.event_stream.emit(tenant, event, key_values).await;
+
+        debug!(cid = %cid, record_id = %record_id, "deleted record");
+
+        Ok(())
+    }
+
+    /// prune_descendants removes all messages and data for the descendants of a record, which
+    /// are the records whose `parentId` is the record or one of its descendants.
+    async fn prune_descendants(&self, tenant: &str, record_id: &str) -> Result<(), HandlerError> {
+        let mut parents = vec![record_id.to_string()];
+        let mut descendants = Vec::new();
+
+        while let Some(parent) = parents.pop() {
+            for child in self.child_record_ids(tenant, &parent).await? {
+                parents.push(child.clone());
+                descendants.push(child);
+            }
+        }
+
+        // each descendant is found after its parent, so removing them in reverse removes the
+        // deepest records first.
+        for descendant in descendants.iter().rev() {
+            let messages = self.record_messages(tenant, descendant).await?;
+            self.purge_messages(tenant, descendant, &messages, None)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// child_record_ids returns the IDs of the records whose parent is the given record.
+    async fn child_record_ids(
+        &self,
+        tenant: &str,
+        record_id: &str,
+    ) -> Result<BTreeSet<String>, HandlerError> {
+        let filter = ValueFilter::<FilterKey>::from([
+            (
+                FilterKey::Index(INTERFACE.to_string()),
+                Filter::Equal(Value::String(RECORDS.to_string())),
+            ),
+            (
+                FilterKey::Index(PARENT_ID.to_string()),
+                Filter::Equal(Value::String(record_id.to_string())),
+            ),
+        ]);
+
+        let children = self
+            .message_store
+            .query::<Descriptor>(tenant, Filters::from(filter), None, None)
+            .await?;
+
+        Ok(children
+            .items
+            .into_iter()
+            .filter_map(|message| match message.descriptor {
+                Descriptor::Records(Records::Write(_)) => {
+                    WriteFields::from(message.fields).record_id
+                }
+                Descriptor::Records(Records::Delete(delete)) => Some(delete.record_id),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// purge_messages removes the given messages of a record from the message store and event
+    /// log, along with any record data held in the data store. The initial write, if given, is
+    /// kept but is re-indexed as it is no longer the latest state of the record.
+    async fn purge_messages(
+        &self,
+        tenant: &str,
+        record_id: &str,
+        messages: &[Message<Descriptor>],
+        initial: Option<&Message<RecordsWriteDescriptor>>,
+    ) -> Result<(), HandlerError> {
+        let mut purged = Vec::new();
+        let mut data_cids = BTreeSet::new();
+
+        for message in messages {
+            let cid = message_cid(message)?.to_string();
+            self.message_store.delete(tenant, &cid).await?;
+
+            let descriptor = write_descriptor(message);
+            if let Some(descriptor) = descriptor {
+                if descriptor.data_size > MAX_ENCODED_DATA_SIZE {
+                    data_cids.insert(descriptor.data_cid.as_str());
+                }
+            }
+
+            let is_initial = descriptor
+                .is_some_and(|descriptor| descriptor.message_timestamp == descriptor.date_created);
+            match initial {
+                Some(initial) if is_initial => {
+                    let mut message = message.clone();
+                    message.fields.strip_encoded_data();
+
+                    let indexes = write_indexes(initial, false)?;
+                    let tags = initial.tags();
+
+                    self.message_store
+                        .put(tenant, message, indexes, tags)
+                        .await?;
+                }
+                _ => purged.push(cid),
+            }
+        }
+
+        for data_cid in data_cids {
+            self.data_store.delete(tenant, record_id, data_cid).await?;
+        }
+
+        if !purged.is_empty() {
+            let cids = purged.iter().map(String::as_str).collect::<Vec<&str>>();
+            self.event_log.delete(tenant, &cids).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,266 @@
+mod delete;
+mod query;
+mod read;
+mod write;
+
+use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD as base64url};
+use futures_util::stream;
+
+use crate::{
+    auth::KeyResolver,
+    descriptors::{DeleteDescriptor, Records, RecordsWriteDescriptor, RECORDS},
+    errors::{DataStoreError, HandlerError, StoreError},
+    fields::MessageFields,
+    filters::{Filter, FilterKey, Filters, MessageSort, SortDirection, ValueFilter},
+    indexes::{MessageIndexes, AUTHOR, INTERFACE, IS_LATEST_BASE_STATE, RECORD_ID},
+    stores::{DataStore, EventLog, GetDataResults, MessageStore, ResumableTaskStore},
+    Descriptor, Dwn, MapValue, Message, Value,
+};
+
+/// MAX_ENCODED_DATA_SIZE is the largest record data size (in bytes) which is stored encoded
+/// alongside the message in the message store, rather than in the data store.
+pub(crate) const MAX_ENCODED_DATA_SIZE: u64 = 30_000;
+
+impl<MS, DS, EL, RT, R> Dwn<MS, DS, EL, RT, R>
+where
+    MS: MessageStore,
+    DS: DataStore,
+    EL: EventLog,
+    RT: ResumableTaskStore,
+    R: KeyResolver,
+{
+    /// record_messages returns all of the RecordsWrite and RecordsDelete messages stored for
+    /// the given record, ordered by `messageTimestamp`.
+    pub(crate) async fn record_messages(
+        &self,
+        tenant: &str,
+        record_id: &str,
+    ) -> Result<Vec<Message<Descriptor>>, HandlerError> {
+        let filter = ValueFilter::<FilterKey>::from([
+            (
+                FilterKey::Index(INTERFACE.to_string()),
+                Filter::Equal(Value::String(RECORDS.to_string())),
+            ),
+            (
+                FilterKey::Index(RECORD_ID.to_string()),
+                Filter::Equal(Value::String(record_id.to_string())),
+            ),
+        ]);
+
+        let messages = self
+            .message_store
+            .query::<Descriptor>(
+                tenant,
+                Filters::from(filter),
+                Some(MessageSort::Timestamp(SortDirection::Ascending)),
+                None,
+            )
+            .await?;
+
+        Ok(messages.items)
+    }
+
+    /// record_data returns the data of a RecordsWrite, from the data encoded with the stored
+    /// message if there is any, and otherwise from the data store.
+    pub(crate) async fn record_data(
+        &self,
+        tenant: &str,
+        record_id: &str,
+        descriptor: &RecordsWriteDescriptor,
+        encoded_data: Option<Value>,
+    ) -> Result<GetDataResults, HandlerError> {
+        match encoded_data {
+            Some(Value::String(encoded)) => {
+                let data = base64url.decode(encoded).map_err(|err| {
+                    HandlerError::InvalidMessage(format!("unable to decode record data: {}", err))
+                })?;
+
+                Ok(GetDataResults {
+                    size: data.len(),
+                    data: Box::pin(stream::iter(data)),
+                })
+            }
+            _ => self
+                .data_store
+                .get(tenant, record_id, &descriptor.data_cid)
+                .await
+                .map_err(|err| match err {
+                    DataStoreError::StoreError(StoreError::NotFound) => HandlerError::NotFound,
+                    err => err.into(),
+                }),
+        }
+    }
+
+    /// record_initial_write returns the initial RecordsWrite of the given record, without any
+    /// encoded data.
+    pub(crate) async fn record_initial_write(
+        &self,
+        tenant: &str,
+        record_id: &str,
+    ) -> Result<Option<Message<RecordsWriteDescriptor>>, HandlerError> {
+        let messages = self.record_messages(tenant, record_id).await?;
+
+        match initial_write(&messages) {
+            Some(initial) => {
+                let mut initial = initial.clone();
+                initial.fields.strip_encoded_data();
+
+                Ok(Some(initial.try_into_message()?))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// write_descriptor returns the RecordsWrite descriptor of the message, if the message is a
+/// RecordsWrite.
+pub(crate) fn write_descriptor(message: &Message<Descriptor>) -> Option<&RecordsWriteDescriptor> {
+    match &message.descriptor {
+        Descriptor::Records(Records::Write(descriptor)) => Some(descriptor),
+        _ => None,
+    }
+}
+
+/// initial_write returns the initial RecordsWrite of a record from the record's messages. The
+/// initial write is the write whose `messageTimestamp` is the `dateCreated` of the record.
+pub(crate) fn initial_write(messages: &[Message<Descriptor>]) -> Option<&Message<Descriptor>> {
+    messages.iter().find(|message| {
+        write_descriptor(message)
+            .is_some_and(|descriptor| descriptor.message_timestamp == descriptor.date_created)
+    })
+}
+
+/// write_indexes returns the indexes a RecordsWrite is stored with in the message store and
+/// event log.
+pub(crate) fn write_indexes(
+    message: &Message<RecordsWriteDescriptor>,
+    is_latest_base_state: bool,
+) -> Result<MapValue, HandlerError> {
+    let mut indexes = message.indexes()?;
+    indexes.insert(
+        IS_LATEST_BASE_STATE.to_string(),
+        Value::Bool(is_latest_base_state),
+    );
+
+    Ok(indexes)
+}
+
+/// delete_indexes returns the indexes a RecordsDelete is stored with. A RecordsDelete is indexed
+/// with the properties of the record's initial write, so that it is matched by the same filters
+/// as the record it deletes.
+pub(crate) fn delete_indexes(
+    message: &Message<DeleteDescriptor>,
+    initial: &Message<RecordsWriteDescriptor>,
+) -> Result<MapValue, HandlerError> {
+    let mut indexes = write_indexes(initial, true)?;
+    // the author of a RecordsDelete is the author of the delete, rather than of the record.
+    indexes.remove(AUTHOR);
+    indexes.extend(message.indexes()?);
+
+    Ok(indexes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        descriptors::DELETE,
+        fields::WriteFields,
+        indexes::{DATE_CREATED, MESSAGE_TIMESTAMP, METHOD, PRUNE, SCHEMA},
+    };
+
+    #[test]
+    fn test_write_indexes() {
+        let now = chrono::Utc::now();
+        let message = Message {
+            descriptor: RecordsWriteDescriptor {
+                protocol: Some("http://example.com/".to_string()),
+                protocol_path: Some("thread".to_string()),
+                data_cid: "cid".to_string(),
+                data_size: 42,
+                date_created: now,
+                message_timestamp: now,
+                data_format: "application/json".to_string(),
+                ..Default::default()
+            },
+            fields: WriteFields {
+                record_id: Some("record".to_string()),
+                ..Default::default()
+            },
+        };
+
+        let mut expected = message.indexes().unwrap();
+        expected.insert(IS_LATEST_BASE_STATE.to_string(), Value::Bool(true));
+        assert_eq!(write_indexes(&message, true).unwrap(), expected);
+
+        expected.insert(IS_LATEST_BASE_STATE.to_string(), Value::Bool(false));
+        assert_eq!(write_indexes(&message, false).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_initial_write() {
+        let created = chrono::Utc::now();
+        let write = |message_timestamp| Message {
+            descriptor: Descriptor::Records(Records::Write(RecordsWriteDescriptor {
+                date_created: created,
+                message_timestamp,
+                ..Default::default()
+            })),
+            fields: crate::Fields::default(),
+        };
+
+        let messages = vec![
+            write(created + chrono::Duration::seconds(1)),
+            write(created),
+        ];
+
+        assert_eq!(initial_write(&messages), Some(&messages[1]));
+        assert_eq!(initial_write(&messages[..1]), None);
+    }
+
+    #[test]
+    fn test_delete_indexes() {
+        let created = chrono::Utc::now();
+        let deleted = created + chrono::Duration::seconds(1);
+
+        let initial = Message {
+            descriptor: RecordsWriteDescriptor {
+                schema: Some("http://example.com/schema".to_string()),
+                date_created: created,
+                message_timestamp: created,
+                ..Default::default()
+            },
+            fields: WriteFields {
+                record_id: Some("record".to_string()),
+                ..Default::default()
+            },
+        };
+        let delete = Message {
+            descriptor: DeleteDescriptor {
+                record_id: "record".to_string(),
+                message_timestamp: deleted,
+                prune: true,
+            },
+            fields: Default::default(),
+        };
+
+        let indexes = delete_indexes(&delete, &initial).unwrap();
+
+        let expected = [
+            (METHOD, Value::String(DELETE.to_string())),
+            (RECORD_ID, Value::String("record".to_string())),
+            (MESSAGE_TIMESTAMP, Value::DateTime(deleted)),
+            (DATE_CREATED, Value::DateTime(created)),
+            (
+                SCHEMA,
+                Value::String("http://example.com/schema".to_string()),
+            ),
+            (IS_LATEST_BASE_STATE, Value::Bool(true)),
+            (PRUNE, Value::Bool(true)),
+        ];
+        for (key, value) in expected {
+            assert_eq!(indexes.get(key), Some(&value), "{}", key);
+        }
+        assert_eq!(indexes.get(AUTHOR), None);
+    }
+}
@@ -0,0 +1,302 @@
+use tracing::instrument;
+
+use crate::{
+    auth::{GrantAuthorizer, KeyResolver, ProtocolAuthorizer},
+    descriptors::{DateSort, RecordsQueryDescriptor, RecordsWriteDescriptor, RECORDS, WRITE},
+    errors::HandlerError,
+    fields::MessageFields,
+    filters::{Filter, FilterKey, Filters, MessageSort, ValueFilter},
+    handlers::Signers,
+    indexes::{AUTHOR, INTERFACE, IS_LATEST_BASE_STATE, METHOD, PUBLISHED, RECIPIENT},
+    protocols::Can,
+    replies::{
+        records::{Query, QueryEntry},
+        Status,
+    },
+    stores::{DataStore, EventLog, MessageStore, ResumableTaskStore},
+    Descriptor, Dwn, Message, MessageReply, Reply, Value,
+};
+
+impl<MS, DS, EL, RT, R> Dwn<MS, DS, EL, RT, R>
+where
+    MS: MessageStore,
+    DS: DataStore,
+    EL: EventLog,
+    RT: ResumableTaskStore,
+    R: KeyResolver,
+{
+    /// handle_records_query handles a RecordsQuery message, returning the latest state of each
+    /// record matching the query filter. The tenant may query all of their records, while
+    /// other requesters only see published records, and records they authored or received,
+    /// unless they invoke a permission grant or protocol role which allows them to query the
+    /// records.
+    #[instrument(skip(self, message, signers))]
+    pub(crate) async fn handle_records_query(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+        signers: Signers,
+    ) -> Result<MessageReply, HandlerError> {
+        let query: Message<RecordsQueryDescriptor> = message.clone().try_into_message()?;
+        let requester = signers.author;
+
+        let granted = GrantAuthorizer::new(&self.message_store, tenant)
+            .authorize_message(&query.fields, &message.descriptor, None)
+            .await?;
+
+        let role = query.fields.protocol_role()?;
+        let invoked_role = match (&requester, role) {
+            (Some(requester), Some(role)) if !granted && requester != tenant => {
+                self.authorize_query_role(tenant, &query.descriptor, requester, &role)
+                    .await?;
+                true
+            }
+            _ => false,
+        };
+
+        let Some(filters) = query_filters(
+            &query.descriptor,
+            tenant,
+            requester.as_deref(),
+            granted || invoked_role,
+        ) else {
+            return Ok(MessageReply::new(
+                Status::ok(),
+                Reply::RecordsQuery(Query {
+                    entries: Some(vec![]),
+                    cursor: None,
+                }),
+            ));
+        };
+        let sort = MessageSort::from(
+            query
+                .descriptor
+                .date_sort
+                .clone()
+                .unwrap_or(DateSort::CreatedAscending),
+        );
+
+        let results = self
+            .message_store
+            .query::<Descriptor>(
+                tenant,
+                filters,
+                Some(sort),
+                query.descriptor.pagination.clone(),
+            )
+            .await?;
+
+        let mut entries = Vec::with_capacity(results.items.len());
+        for message in results.items {
+            entries.push(self.query_entry(tenant, message).await?);
+        }
+
+        Ok(MessageReply::new(
+            Status::ok(),
+            Reply::RecordsQuery(Query {
+                entries: Some(entries),
+                cursor: results.cursor,
+            }),
+        ))
+    }
+
+    /// authorize_query_role authorizes a requester other than the tenant to query the records at
+    /// the protocol path of the query filter, by invoking a protocol role.
+    async fn authorize_query_role(
+        &self,
+        tenant: &str,
+        descriptor: &RecordsQueryDescriptor,
+        requester: &str,
+        role: &str,
+    ) -> Result<(), HandlerError> {
+        let filter = &descriptor.filter;
+        let (Some(protocol), Some(protocol_path)) = (&filter.protocol, &filter.protocol_path)
+        else {
+            return Err(HandlerError::InvalidMessage(
+                "RecordsQuery invoking a protocol role must filter by protocol and protocolPath"
+                    .to_string(),
+            ));
+        };
+        let definition = self.protocol_definition(tenant, protocol).await?;
+
+        ProtocolAuthorizer::new(&self.message_store, tenant, &definition)
+            .authorize_query(
+                protocol_path,
+                filter.context_id.as_deref(),
+                requester,
+                Some(role),
+                Can::Query,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// query_entry builds the reply entry for a RecordsWrite matched by a query, including the
+    /// initial write of the record when the matched write is not itself the initial write.
+    async fn query_entry(
+        &self,
+        tenant: &str,
+        mut message: Message<Descriptor>,
+    ) -> Result<QueryEntry, HandlerError> {
+        let encoded_data = match message.fields.encoded_data() {
+            Some(Value::String(encoded)) => Some(encoded),
+            _ => None,
+        };
+
+        let write: Message<RecordsWriteDescriptor> = message.try_into_message()?;
+        let initial_write = match &write.fields.record_id {
+            Some(record_id)
+                if write.descriptor.message_timestamp != write.descriptor.date_created =>
+            {
+                self.record_initial_write(tenant, record_id).await?
+            }
+            _ => None,
+        };
+
+        Ok(QueryEntry {
+            initial_write,
+            encoded_data,
+            message: write,
+        })
+    }
+}
+
+/// query_filters returns the message store filters for a RecordsQuery. Only the latest state of
+/// each record is matched, and requesters other than the tenant are limited to the records
+/// they are allowed to see, unless they have invoked a permission grant or protocol role
+/// authorized for the query. No filters are returned if the requester can't see any records
+/// matching the query.
+fn query_filters(
+    descriptor: &RecordsQueryDescriptor,
+    tenant: &str,
+    requester: Option<&str>,
+    authorized: bool,
+) -> Option<Filters> {
+    let records = descriptor.filter.clone();
+
+    let mut base = ValueFilter::<FilterKey>::from(records.clone());
+    base.extend([
+        (
+            FilterKey::Index(INTERFACE.to_string()),
+            Filter::Equal(Value::String(RECORDS.to_string())),
+        ),
+        (
+            FilterKey::Index(METHOD.to_string()),
+            Filter::Equal(Value::String(WRITE.to_string())),
+        ),
+        (
+            FilterKey::Index(IS_LATEST_BASE_STATE.to_string()),
+            Filter::Equal(Value::Bool(true)),
+        ),
+    ]);
+
+    if requester == Some(tenant) || authorized {
+        return Some(Filters::from(base));
+    }
+
+    let narrow = |key: &str, value: Value| {
+        let mut filter = base.clone();
+        filter.insert(FilterKey::Index(key.to_string()), Filter::Equal(value));
+        filter
+    };
+
+    let mut set = Vec::new();
+    if records.published != Some(false) {
+        set.push(narrow(PUBLISHED, Value::Bool(true)));
+    }
+
+    if let Some(requester) = requester {
+        let includes = |dids: &Option<Vec<String>>| match dids {
+            Some(dids) => dids.iter().any(|did| did == requester),
+            None => true,
+        };
+
+        if includes(&records.author) {
+            set.push(narrow(AUTHOR, Value::String(requester.to_string())));
+        }
+        if includes(&records.recipient) {
+            set.push(narrow(RECIPIENT, Value::String(requester.to_string())));
+        }
+    }
+
+    match set.is_empty() {
+        true => None,
+        false => Some(Filters::from(set)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::filters::Records;
+
+    #[test]
+    fn test_query_filters() {
+        let descriptor = RecordsQueryDescriptor {
+            message_timestamp: chrono::Utc::now(),
+            filter: Records {
+                schema: Some("http://example.com/schema".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let base = ValueFilter::from([
+            (
+                FilterKey::Index("schema".to_string()),
+                Filter::Equal(Value::String("http://example.com/schema".to_string())),
+            ),
+            (
+                FilterKey::Index("interface".to_string()),
+                Filter::Equal(Value::String(RECORDS.to_string())),
+            ),
+            (
+                FilterKey::Index("method".to_string()),
+                Filter::Equal(Value::String(WRITE.to_string())),
+            ),
+            (
+                FilterKey::Index("isLatestBaseState".to_string()),
+                Filter::Equal(Value::Bool(true)),
+            ),
+        ]);
+        let with = |key: &str, value: Value| {
+            let mut filter = base.clone();
+            filter.insert(FilterKey::Index(key.to_string()), Filter::Equal(value));
+            filter
+        };
+
+        let tenant = "did:example:alice";
+        let bob = "did:example:bob";
+
+        assert_eq!(
+            query_filters(&descriptor, tenant, Some(tenant), false),
+            Some(Filters::from(base.clone()))
+        );
+        assert_eq!(
+            query_filters(&descriptor, tenant, Some(bob), true),
+            Some(Filters::from(base.clone()))
+        );
+        assert_eq!(
+            query_filters(&descriptor, tenant, None, false),
+            Some(Filters::from(vec![with("published", Value::Bool(true))]))
+        );
+        assert_eq!(
+            query_filters(&descriptor, tenant, Some(bob), false),
+            Some(Filters::from(vec![
+                with("published", Value::Bool(true)),
+                with("author", Value::String(bob.to_string())),
+                with("recipient", Value::String(bob.to_string())),
+            ]))
+        );
+
+        let unpublished = RecordsQueryDescriptor {
+            filter: Records {
+                published: Some(false),
+                ..Default::default()
+            },
+            ..descriptor
+        };
+        assert_eq!(query_filters(&unpublished, tenant, None, false), None);
+    }
+}
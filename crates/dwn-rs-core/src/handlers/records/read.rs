@@ -0,0 +1,200 @@
+use tracing::instrument;
+
+use crate::{
+    auth::{GrantAuthorizer, KeyResolver, ProtocolAuthorizer},
+    descriptors::{DeleteDescriptor, ReadDescriptor, Records, RecordsWriteDescriptor, RECORDS},
+    errors::HandlerError,
+    fields::MessageFields,
+    filters::{Filter, FilterKey, Filters, ValueFilter},
+    handlers::Signers,
+    indexes::{INTERFACE, IS_LATEST_BASE_STATE},
+    replies::{
+        records::{Read, ReadEntry},
+        Status,
+    },
+    stores::{DataStore, EventLog, MessageStore, ResumableTaskStore},
+    Descriptor, Dwn, Message, MessageReply, Reply, Value,
+};
+
+impl<MS, DS, EL, RT, R> Dwn<MS, DS, EL, RT, R>
+where
+    MS: MessageStore,
+    DS: DataStore,
+    EL: EventLog,
+    RT: ResumableTaskStore,
+    R: KeyResolver,
+{
+    /// handle_records_read handles a RecordsRead message, returning the latest state of the
+    /// single record matching the read filter along with the record data. Reading a deleted
+    /// record returns the RecordsDelete which tombstoned it, if the requester may read the
+    /// record.
+    #[instrument(skip(self, message, signers))]
+    pub(crate) async fn handle_records_read(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+        signers: Signers,
+    ) -> Result<MessageReply, HandlerError> {
+        let read: Message<ReadDescriptor> = message.clone().try_into_message()?;
+        let requester = signers.author;
+
+        let mut filter = ValueFilter::<FilterKey>::from(read.descriptor.filter.clone());
+        filter.extend([
+            (
+                FilterKey::Index(INTERFACE.to_string()),
+                Filter::Equal(Value::String(RECORDS.to_string())),
+            ),
+            (
+                FilterKey::Index(IS_LATEST_BASE_STATE.to_string()),
+                Filter::Equal(Value::Bool(true)),
+            ),
+        ]);
+
+        let mut matched = self
+            .message_store
+            .query::<Descriptor>(tenant, Filters::from(filter), None, None)
+            .await?
+            .items;
+
+        let mut latest = match matched.len() {
+            0 => return Err(HandlerError::NotFound),
+            1 => matched.remove(0),
+            _ => {
+                return Err(HandlerError::InvalidMessage(
+                    "RecordsRead filter matches more than one record".to_string(),
+                ))
+            }
+        };
+
+        let encoded_data = latest.fields.encoded_data();
+
+        // a deleted record is authorized against its initial write, so that the tombstone is
+        // only returned to those who could read the record before it was deleted.
+        let (write, delete) = match latest.descriptor {
+            Descriptor::Records(Records::Delete(_)) => {
+                let delete: Message<DeleteDescriptor> = latest.try_into_message()?;
+                let initial_write = self
+                    .record_initial_write(tenant, &delete.descriptor.record_id)
+                    .await?
+                    .ok_or(HandlerError::NotFound)?;
+
+                (initial_write, Some(delete))
+            }
+            _ => (latest.try_into_message()?, None),
+        };
+
+        let granted = GrantAuthorizer::new(&self.message_store, tenant)
+            .authorize_message(&read.fields, &message.descriptor, Some(&write))
+            .await?;
+        if !granted && !can_read(&write, tenant, requester.as_deref())? {
+            // a non-public record of a protocol may be read by those the protocol allows to.
+            let (Some(requester), Some(protocol)) = (&requester, &write.descriptor.protocol) else {
+                return Err(HandlerError::Unauthorized(
+                    "RecordsRead of a non-public record".to_string(),
+                ));
+            };
+            let definition = self.protocol_definition(tenant, protocol).await?;
+            let role = read.fields.protocol_role()?;
+
+            ProtocolAuthorizer::new(&self.message_store, tenant, &definition)
+                .authorize_read(&write, requester, role.as_deref())
+                .await?;
+        }
+
+        if let Some(delete) = delete {
+            return Ok(MessageReply::new(
+                Status::new(404, "Not Found"),
+                Reply::RecordsRead(Read {
+                    entry: Some(ReadEntry {
+                        records_write: None,
+                        records_delete: Some(delete),
+                        initial_write: Some(write),
+                    }),
+                }),
+            ));
+        }
+
+        let record_id = write
+            .fields
+            .record_id
+            .clone()
+            .ok_or_else(|| HandlerError::InvalidMessage("recordId is required".to_string()))?;
+
+        let initial_write =
+            match write.descriptor.message_timestamp == write.descriptor.date_created {
+                true => None,
+                false => self.record_initial_write(tenant, &record_id).await?,
+            };
+
+        let data = self
+            .record_data(tenant, &record_id, &write.descriptor, encoded_data)
+            .await?;
+
+        Ok(MessageReply::new(
+            Status::ok(),
+            Reply::RecordsRead(Read {
+                entry: Some(ReadEntry {
+                    records_write: Some(write),
+                    records_delete: None,
+                    initial_write,
+                }),
+            }),
+        )
+        .with_data(data))
+    }
+}
+
+/// can_read returns true if the requester may read the record. Published records may be read
+/// by anyone, while other records may only be read by the tenant, and the record's author or
+/// recipient.
+fn can_read(
+    write: &Message<RecordsWriteDescriptor>,
+    tenant: &str,
+    requester: Option<&str>,
+) -> Result<bool, HandlerError> {
+    if write.descriptor.published.unwrap_or_default() {
+        return Ok(true);
+    }
+
+    let Some(requester) = requester else {
+        return Ok(false);
+    };
+
+    let author = write.fields.authorization.author()?;
+
+    Ok(requester == tenant
+        || author.as_deref() == Some(requester)
+        || write.descriptor.recipient.as_deref() == Some(requester))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_can_read() {
+        let tenant = "did:example:alice";
+        let bob = "did:example:bob";
+
+        let mut write = Message::<RecordsWriteDescriptor> {
+            descriptor: RecordsWriteDescriptor {
+                published: Some(false),
+                ..Default::default()
+            },
+            fields: Default::default(),
+        };
+
+        assert!(can_read(&write, tenant, Some(tenant)).unwrap());
+        assert!(!can_read(&write, tenant, Some(bob)).unwrap());
+        assert!(!can_read(&write, tenant, None).unwrap());
+
+        write.descriptor.recipient = Some(bob.to_string());
+        assert!(can_read(&write, tenant, Some(bob)).unwrap());
+        assert!(!can_read(&write, tenant, None).unwrap());
+
+        write.descriptor.recipient = None;
+        write.descriptor.published = Some(true);
+        assert!(can_read(&write, tenant, Some(bob)).unwrap());
+        assert!(can_read(&write, tenant, None).unwrap());
+    }
+}
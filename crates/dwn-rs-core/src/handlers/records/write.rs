@@ -0,0 +1,497 @@
+use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD as base64url};
+use bytes::{Bytes, BytesMut};
+use futures_util::{stream, Stream, StreamExt};
+use tracing::{debug, instrument};
+
+use crate::{
+    auth::{GrantAuthorizer, KeyResolver, ProtocolAuthorizer},
+    cid::generate_cid,
+    descriptors::RecordsWriteDescriptor,
+    errors::{HandlerError, MessageStoreError},
+    fields::{InitialWriteField, MessageFields},
+    handlers::{is_newer, message_cid, newest_message, Signers},
+    indexes::{
+        flatten_tags, MessageIndexes, CONTEXT_ID, DATE_CREATED, PARENT_ID, PROTOCOL, PROTOCOL_PATH,
+        RECIPIENT, SCHEMA,
+    },
+    permissions::PERMISSIONS_PROTOCOL,
+    replies::{Empty, Status},
+    stores::{DataStore, EventLog, MessageStore, ResumableTaskStore},
+    Descriptor, Dwn, Fields, Message, MessageEvent, MessageReply, Reply, Value,
+};
+
+use super::{initial_write, write_descriptor, write_indexes, MAX_ENCODED_DATA_SIZE};
+
+impl<MS, DS, EL, RT, R> Dwn<MS, DS, EL, RT, R>
+where
+    MS: MessageStore,
+    DS: DataStore,
+    EL: EventLog,
+    RT: ResumableTaskStore,
+    R: KeyResolver,
+{
+    /// handle_records_write handles a RecordsWrite message. The initial write of a record
+    /// defines its immutable properties, and each later write for the record replaces the
+    /// previous latest write if it is newer.
+    #[instrument(skip(self, message, signers, data))]
+    pub(crate) async fn handle_records_write<S>(
+        &self,
+        tenant: &str,
+        message: Message<Descriptor>,
+        signers: Signers,
+        data: Option<S>,
+    ) -> Result<MessageReply, HandlerError>
+    where
+        S: Stream<Item = Bytes> + Send + Unpin,
+    {
+        let write: Message<RecordsWriteDescriptor> = message.clone().try_into_message()?;
+        let descriptor = &write.descriptor;
+
+        let record_id = write
+            .fields
+            .record_id
+            .clone()
+            .ok_or_else(|| HandlerError::InvalidMessage("recordId is required".to_string()))?;
+        let author = signers
+            .author
+            .ok_or_else(|| HandlerError::Unauthorized("RecordsWrite must be signed".to_string()))?;
+
+        let existing = self.record_messages(tenant, &record_id).await?;
+        let initial = match initial_write(&existing) {
+            Some(initial) => {
+                let initial: Message<RecordsWriteDescriptor> =
+                    initial.clone().try_into_message()?;
+                verify_immutable_properties(&initial, &write)?;
+
+                Some(initial)
+            }
+            None => {
+                if descriptor.message_timestamp != descriptor.date_created {
+                    return Err(HandlerError::InvalidMessage(
+                        "initial RecordsWrite must have a messageTimestamp matching dateCreated"
+                            .to_string(),
+                    ));
+                }
+                if !write.is_initial_write()? {
+                    return Err(HandlerError::InvalidMessage(
+                        "initial RecordsWrite must have a recordId matching its entryId"
+                            .to_string(),
+                    ));
+                }
+
+                let parent = match descriptor.parent_id {
+                    Some(ref parent_id) => self.record_initial_write(tenant, parent_id).await?,
+                    None => None,
+                };
+                write.verify_context_id(
+                    parent
+                        .as_ref()
+                        .and_then(|parent| parent.fields.context_id.as_deref()),
+                )?;
+
+                None
+            }
+        };
+
+        let granted = GrantAuthorizer::new(&self.message_store, tenant)
+            .authorize_message(
+                &write.fields.authorization,
+                &message.descriptor,
+                Some(&write),
+            )
+            .await?;
+
+        // records of a protocol must follow its structure, whoever authors them. The permissions
+        // protocol is built in, rather than configured by the tenant.
+        let definition = match descriptor.protocol.as_deref() {
+            Some(protocol) if protocol != PERMISSIONS_PROTOCOL => {
+                let definition = self.protocol_definition(tenant, protocol).await?;
+                ProtocolAuthorizer::new(&self.message_store, tenant, &definition)
+                    .verify_write(&write, initial.as_ref())
+                    .await?;
+
+                Some(definition)
+            }
+            _ => None,
+        };
+
+        // authors other than the tenant may only write records which a protocol (or a permission
+        // grant of the tenant) allows them to, unless the tenant has signed the record as its
+        // owner to keep a copy of it.
+        if author != tenant && signers.owner.as_deref() != Some(tenant) && !granted {
+            let definition = definition.ok_or_else(|| {
+                HandlerError::Unauthorized(
+                    "RecordsWrite must be authored by the tenant".to_string(),
+                )
+            })?;
+
+            ProtocolAuthorizer::new(&self.message_store, tenant, &definition)
+                .authorize_write(&write, initial.as_ref())
+                .await?;
+        }
+
+        let newest = newest_message(&existing)?;
+        if let Some(newest) = newest {
+            if write_descriptor(newest).is_none() {
+                return Err(HandlerError::InvalidMessage(
+                    "RecordsWrite is not allowed after a RecordsDelete".to_string(),
+                ));
+            }
+
+            if !is_newer(&message, newest)? {
+                return Err(HandlerError::Conflict(
+                    "a newer RecordsWrite exists for the record".to_string(),
+                ));
+            }
+        }
+
+        // the stored message carries the record data when it is small enough to be encoded,
+        // so any encoded data sent with the message itself is discarded.
+        let mut stored = message.clone();
+        stored.fields.strip_encoded_data();
+
+        let mut is_latest_base_state = true;
+        let mut new_data: Option<Bytes> = None;
+        match data {
+            Some(data) => {
+                let data = read_data(data, descriptor).await?;
+
+                if descriptor.data_size <= MAX_ENCODED_DATA_SIZE {
+                    stored
+                        .fields
+                        .encode_data(Value::String(base64url.encode(&data)));
+                } else {
+                    new_data = Some(data);
+                }
+            }
+            None => match newest.and_then(write_descriptor) {
+                // the data is unchanged from the newest write, so it is reused. The newest write
+                // may be an initial write stored without its data though, which has none to reuse.
+                Some(previous) if previous.data_cid == descriptor.data_cid => {
+                    let encoded = newest.cloned().and_then(|mut m| m.fields.encoded_data());
+                    match self
+                        .record_data(tenant, &record_id, previous, encoded.clone())
+                        .await
+                    {
+                        Ok(_) => {}
+                        Err(HandlerError::NotFound) => {
+                            return Err(HandlerError::InvalidMessage(
+                                "data is required, as the record has no data stored to reuse"
+                                    .to_string(),
+                            ))
+                        }
+                        Err(err) => return Err(err),
+                    }
+
+                    if let Some(encoded) = encoded {
+                        stored.fields.encode_data(encoded);
+                    }
+                }
+                // an initial write may be stored without its data, but it does not become
+                // the latest state of the record until data is written.
+                None if initial.is_none() => is_latest_base_state = false,
+                _ => {
+                    return Err(HandlerError::InvalidMessage(
+                        "data is required when the dataCid of a record changes".to_string(),
+                    ))
+                }
+            },
+        }
+
+        if let Some(data) = new_data {
+            self.data_store
+                .put(
+                    tenant,
+                    &record_id,
+                    &descriptor.data_cid,
+                    stream::iter(vec![data]),
+                )
+                .await?;
+        }
+
+        let cid = message_cid(&message)?;
+        let indexes = write_indexes(&write, is_latest_base_state)?;
+        let tags = write.tags();
+
+        self.message_store
+            .put(tenant, stored, indexes.clone(), tags.clone())
+            .await?;
+        self.event_log
+            .append(tenant, &cid.to_string(), indexes.clone(), tags.clone())
+            .await?;
+
+        // the data of the writes this write supersedes is only removed once the write (and
+        // its data) is stored, so that the record always has data to return.
+        if is_latest_base_state {
+            self.delete_superseded_data(tenant, &record_id, descriptor, &existing)
+                .await?;
+            self.prune_superseded_writes(tenant, &message, &existing)
+                .await?;
+        }
+
+        let event = MessageEvent {
+            message: with_initial_write(message, initial.clone()),
+            initial_write: initial,
+        };
+        let mut key_values = indexes;
+        key_values.extend(flatten_tags(tags));
+        self.event_stream.emit(tenant, event, key_values).await;
+
+        debug!(cid = %cid, record_id = %record_id, "accepted RecordsWrite");
+
+        Ok(MessageReply::new(
+            Status::accepted(),
+            Reply::Empty(Empty {}),
+        ))
+    }
+
+    /// delete_superseded_data removes data stored for the existing writes of a record, when
+    /// that data is replaced by a new write with a different `dataCid`.
+    async fn delete_superseded_data(
+        &self,
+        tenant: &str,
+        record_id: &str,
+        descriptor: &RecordsWriteDescriptor,
+        existing: &[Message<Descriptor>],
+    ) -> Result<(), HandlerError> {
+        let mut deleted: Vec<&str> = Vec::new();
+
+        for previous in existing.iter().filter_map(write_descriptor) {
+            if previous.data_cid == descriptor.data_cid
+                || previous.data_size <= MAX_ENCODED_DATA_SIZE
+                || deleted.contains(&previous.data_cid.as_str())
+            {
+                continue;
+            }
+
+            self.data_store
+                .delete(tenant, record_id, &previous.data_cid)
+                .await?;
+            deleted.push(&previous.data_cid);
+        }
+
+        Ok(())
+    }
+
+    /// prune_superseded_writes removes the writes of a record which are superseded by the
+    /// newest write from the message store and event log. The initial write is kept, as it
+    /// defines the immutable properties of the record, but is no longer the latest state.
+    async fn prune_superseded_writes(
+        &self,
+        tenant: &str,
+        newest: &Message<Descriptor>,
+        existing: &[Message<Descriptor>],
+    ) -> Result<(), HandlerError> {
+        let initial = initial_write(existing);
+        let mut pruned = Vec::new();
+
+        for previous in existing {
+            if write_descriptor(previous).is_none() || !is_newer(newest, previous)? {
+                continue;
+            }
+
+            let cid = message_cid(previous)?.to_string();
+            self.message_store.delete(tenant, &cid).await?;
+
+            if Some(previous) == initial {
+                // re-index the initial write, as it is no longer the latest state.
+                let write: Message<RecordsWriteDescriptor> = previous.clone().try_into_message()?;
+                let indexes = write_indexes(&write, false)?;
+                let tags = write.tags();
+
+                self.message_store
+                    .put(tenant, previous.clone(), indexes, tags)
+                    .await?;
+            } else {
+                pruned.push(cid);
+            }
+        }
+
+        if !pruned.is_empty() {
+            let cids = pruned.iter().map(String::as_str).collect::<Vec<&str>>();
+            self.event_log.delete(tenant, &cids).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// read_data reads the record data from the stream, and checks it against the `dataCid` and
+/// `dataSize` of the descriptor. Reading stops as soon as the data is larger than `dataSize`.
+async fn read_data<S>(
+    mut data: S,
+    descriptor: &RecordsWriteDescriptor,
+) -> Result<Bytes, HandlerError>
+where
+    S: Stream<Item = Bytes> + Unpin,
+{
+    let mut buf = BytesMut::new();
+    while let Some(chunk) = data.next().await {
+        buf.extend_from_slice(&chunk);
+
+        if buf.len() as u64 > descriptor.data_size {
+            return Err(HandlerError::InvalidMessage(format!(
+                "data is larger than dataSize {}",
+                descriptor.data_size
+            )));
+        }
+    }
+
+    if buf.len() as u64 != descriptor.data_size {
+        return Err(HandlerError::InvalidMessage(format!(
+            "data size {} does not match dataSize {}",
+            buf.len(),
+            descriptor.data_size
+        )));
+    }
+
+    let data_cid = generate_cid(&buf).map_err(MessageStoreError::from)?;
+    if data_cid.to_string() != descriptor.data_cid {
+        return Err(HandlerError::InvalidMessage(format!(
+            "data CID {} does not match dataCid {}",
+            data_cid, descriptor.data_cid
+        )));
+    }
+
+    Ok(buf.freeze())
+}
+
+/// verify_immutable_properties checks that a RecordsWrite doesn't change any of the properties
+/// set by the initial write of the record.
+fn verify_immutable_properties(
+    initial: &Message<RecordsWriteDescriptor>,
+    write: &Message<RecordsWriteDescriptor>,
+) -> Result<(), HandlerError> {
+    let (a, b) = (&initial.descriptor, &write.descriptor);
+
+    let changed = [
+        (PROTOCOL, a.protocol != b.protocol),
+        (PROTOCOL_PATH, a.protocol_path != b.protocol_path),
+        (RECIPIENT, a.recipient != b.recipient),
+        (SCHEMA, a.schema != b.schema),
+        (PARENT_ID, a.parent_id != b.parent_id),
+        (DATE_CREATED, a.date_created != b.date_created),
+        (
+            CONTEXT_ID,
+            initial.fields.context_id != write.fields.context_id,
+        ),
+    ];
+
+    match changed.iter().find(|(_, is_changed)| *is_changed) {
+        Some((property, _)) => Err(HandlerError::InvalidMessage(format!(
+            "{} is immutable, and cannot be changed from the initial write",
+            property
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// with_initial_write returns the message with the initial write of the record included in
+/// its fields, when the message is not itself the initial write.
+fn with_initial_write(
+    message: Message<Descriptor>,
+    initial: Option<Message<RecordsWriteDescriptor>>,
+) -> Message<Descriptor> {
+    match initial {
+        Some(initial) => Message {
+            descriptor: message.descriptor,
+            fields: Fields::InitialWriteField(InitialWriteField {
+                write_fields: message.fields.into(),
+                initial_write: Some(Box::new(initial)),
+            }),
+        },
+        None => message,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fields::WriteFields;
+
+    fn write(data: &[u8]) -> Message<RecordsWriteDescriptor> {
+        let now = chrono::Utc::now();
+
+        Message {
+            descriptor: RecordsWriteDescriptor {
+                data_cid: generate_cid(data).unwrap().to_string(),
+                data_size: data.len() as u64,
+                date_created: now,
+                message_timestamp: now,
+                ..Default::default()
+            },
+            fields: WriteFields {
+                record_id: Some("record".to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_data() {
+        let data = b"hello world";
+        let descriptor = write(data).descriptor;
+
+        let chunks = stream::iter(vec![
+            Bytes::from_static(b"hello "),
+            Bytes::from_static(b"world"),
+        ]);
+        let read = read_data(chunks, &descriptor).await.unwrap();
+        assert_eq!(read, Bytes::from_static(data));
+
+        let wrong_size = stream::iter(vec![Bytes::from_static(b"hello")]);
+        assert!(matches!(
+            read_data(wrong_size, &descriptor).await,
+            Err(HandlerError::InvalidMessage(_))
+        ));
+
+        // data larger than dataSize is rejected without reading the rest of the stream.
+        let endless = stream::repeat(Bytes::from_static(b"hello"));
+        assert!(matches!(
+            read_data(endless, &descriptor).await,
+            Err(HandlerError::InvalidMessage(_))
+        ));
+
+        let wrong_cid = stream::iter(vec![Bytes::from_static(b"hello there")]);
+        assert!(matches!(
+            read_data(wrong_cid, &descriptor).await,
+            Err(HandlerError::InvalidMessage(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_immutable_properties() {
+        let initial = write(b"hello world");
+
+        let mut update = initial.clone();
+        update.descriptor.message_timestamp += chrono::Duration::seconds(1);
+        update.descriptor.published = Some(true);
+        update.descriptor.tags = Some(Default::default());
+        assert!(verify_immutable_properties(&initial, &update).is_ok());
+
+        let mut update = initial.clone();
+        update.descriptor.schema = Some("http://example.com/schema".to_string());
+        assert!(verify_immutable_properties(&initial, &update).is_err());
+
+        let mut update = initial.clone();
+        update.fields.context_id = Some("context".to_string());
+        assert!(verify_immutable_properties(&initial, &update).is_err());
+    }
+
+    #[test]
+    fn test_with_initial_write() {
+        let initial = write(b"hello world");
+        let message = initial.clone().into_generic();
+
+        assert_eq!(with_initial_write(message.clone(), None), message);
+
+        let with_initial = with_initial_write(message, Some(initial.clone()));
+        match with_initial.fields {
+            Fields::InitialWriteField(fields) => {
+                assert_eq!(fields.write_fields, initial.fields);
+                assert_eq!(fields.initial_write, Some(Box::new(initial)));
+            }
+            _ => panic!("expected initial write fields"),
+        }
+    }
+}
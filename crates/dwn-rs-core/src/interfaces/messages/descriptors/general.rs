@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::Fields;
@@ -22,11 +23,35 @@ pub enum Descriptor {
     Messages(Messages),
 }
 
+impl Descriptor {
+    /// message_timestamp returns the `messageTimestamp` of the underlying descriptor.
+    pub fn message_timestamp(&self) -> &DateTime<Utc> {
+        match self {
+            Descriptor::Records(records) => match records {
+                Records::Read(d) => &d.message_timestamp,
+                Records::Query(d) => &d.message_timestamp,
+                Records::Write(d) => &d.message_timestamp,
+                Records::Delete(d) => &d.message_timestamp,
+                Records::Subscribe(d) => &d.message_timestamp,
+            },
+            Descriptor::Protocols(protocols) => match protocols {
+                Protocols::Configure(d) => &d.message_timestamp,
+                Protocols::Query(d) => &d.message_timestamp,
+            },
+            Descriptor::Messages(messages) => match messages {
+                Messages::Read(d) => &d.message_timestamp,
+                Messages::Query(d) => &d.message_timestamp,
+                Messages::Subscribe(d) => &d.message_timestamp,
+            },
+        }
+    }
+}
+
 impl MessageValidator for Descriptor {
     fn validate(&self) -> Result<(), ValidationError> {
         match self {
             Descriptor::Records(_) => Ok(()),
-            Descriptor::Protocols(_) => Ok(()),
+            Descriptor::Protocols(protocols) => protocols.validate(),
             Descriptor::Messages(_) => Ok(()),
         }
     }
@@ -51,6 +76,10 @@ impl MessageDescriptor for Descriptor {
             Descriptor::Messages(messages) => messages.method(),
         }
     }
+
+    fn message_timestamp(&self) -> Option<&DateTime<Utc>> {
+        Some(Descriptor::message_timestamp(self))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -92,6 +121,16 @@ impl MessageDescriptor for Records {
             Records::Subscribe(_) => SUBSCRIBE,
         }
     }
+
+    fn message_timestamp(&self) -> Option<&DateTime<Utc>> {
+        match self {
+            Records::Read(d) => d.message_timestamp(),
+            Records::Query(d) => d.message_timestamp(),
+            Records::Write(d) => d.message_timestamp(),
+            Records::Delete(d) => d.message_timestamp(),
+            Records::Subscribe(d) => d.message_timestamp(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -104,7 +143,7 @@ pub enum Protocols {
 impl MessageValidator for Protocols {
     fn validate(&self) -> Result<(), ValidationError> {
         match self {
-            Protocols::Configure(_) => Ok(()),
+            Protocols::Configure(configure) => configure.validate(),
             Protocols::Query(_) => Ok(()),
         }
     }
@@ -124,6 +163,13 @@ impl MessageDescriptor for Protocols {
             Protocols::Query(_) => QUERY,
         }
     }
+
+    fn message_timestamp(&self) -> Option<&DateTime<Utc>> {
+        match self {
+            Protocols::Configure(d) => d.message_timestamp(),
+            Protocols::Query(d) => d.message_timestamp(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -159,8 +205,60 @@ impl MessageDescriptor for Messages {
             Messages::Subscribe(_) => SUBSCRIBE,
         }
     }
+
+    fn message_timestamp(&self) -> Option<&DateTime<Utc>> {
+        match self {
+            Messages::Read(d) => d.message_timestamp(),
+            Messages::Query(d) => d.message_timestamp(),
+            Messages::Subscribe(d) => d.message_timestamp(),
+        }
+    }
 }
 
+// descriptor_conversions implements the conversions between the generic `Descriptor`, and
+// each of the concrete descriptor types.
+macro_rules! descriptor_conversions {
+    ($($interface:ident::$variant:ident($descriptor:ty)),* $(,)?) => {
+        $(
+            impl From<$descriptor> for Descriptor {
+                fn from(descriptor: $descriptor) -> Self {
+                    Descriptor::$interface($interface::$variant(descriptor))
+                }
+            }
+
+            impl TryFrom<Descriptor> for $descriptor {
+                type Error = ValidationError;
+
+                fn try_from(descriptor: Descriptor) -> Result<Self, Self::Error> {
+                    match descriptor {
+                        Descriptor::$interface($interface::$variant(descriptor)) => Ok(descriptor),
+                        other => Err(ValidationError {
+                            message: format!(
+                                "unexpected {}{} descriptor",
+                                other.interface(),
+                                other.method()
+                            ),
+                        }),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+descriptor_conversions!(
+    Records::Read(ReadDescriptor),
+    Records::Query(RecordsQueryDescriptor),
+    Records::Write(RecordsWriteDescriptor),
+    Records::Delete(DeleteDescriptor),
+    Records::Subscribe(SubscribeDescriptor),
+    Protocols::Configure(ConfigureDescriptor),
+    Protocols::Query(ProtocolQueryDescriptor),
+    Messages::Read(MessagesReadDescriptor),
+    Messages::Query(MessagesQueryDescriptor),
+    Messages::Subscribe(MessagesSubscribeDescriptor),
+);
+
 #[cfg(test)]
 mod test {
     use serde_json::json;
@@ -183,4 +281,38 @@ mod test {
 
         assert_eq!(serialized, expected);
     }
+
+    #[test]
+    fn test_descriptor_conversions() {
+        use super::*;
+
+        let desc = DeleteDescriptor {
+            message_timestamp: chrono::Utc::now(),
+            record_id: "record".to_string(),
+            prune: false,
+        };
+
+        let generic: Descriptor = desc.clone().into();
+        assert_eq!(generic, Descriptor::Records(Records::Delete(desc.clone())));
+        assert_eq!(generic.message_timestamp(), &desc.message_timestamp);
+        assert_eq!(DeleteDescriptor::try_from(generic.clone()).unwrap(), desc);
+        assert!(RecordsWriteDescriptor::try_from(generic).is_err());
+    }
+
+    #[test]
+    fn test_descriptor_deserialize_method() {
+        use super::*;
+
+        let query = RecordsQueryDescriptor {
+            message_timestamp: chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            filter: RecordsFilter::default(),
+            pagination: None,
+            date_sort: None,
+        };
+
+        // a RecordsQuery descriptor is also a valid RecordsRead descriptor by shape, so the
+        // interface and method must be used to select the variant
+        let de: Descriptor = serde_json::from_value(json!(&query)).unwrap();
+        assert_eq!(de, Descriptor::Records(Records::Query(query)));
+    }
 }
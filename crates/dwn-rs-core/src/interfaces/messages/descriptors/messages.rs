@@ -1,5 +1,5 @@
 use crate::auth::Authorization;
-use crate::descriptors::MessageDescriptor;
+use crate::descriptors::{MessageDescriptor, ValidationError};
 use crate::filters::message_filters::Messages as MessagesFilter;
 use crate::interfaces::messages::descriptors::{MESSAGES, QUERY, READ, SUBSCRIBE};
 use cid::Cid;
@@ -13,16 +13,33 @@ pub struct ReadParameters {
     #[serde(rename = "messageCid")]
     pub message_cid: Cid,
     #[serde(rename = "messageTimestamp")]
-    pub message_timestamp: chrono::DateTime<chrono::Utc>,
+    pub message_timestamp: Option<chrono::DateTime<chrono::Utc>>,
     #[serde(rename = "permissionGrantId")]
     pub permission_grant_id: Option<String>,
 }
 
-impl MessageValidator for ReadParameters {}
+impl MessageValidator for ReadParameters {
+    fn validate(&self) -> Result<(), ValidationError> {
+        Ok(())
+    }
+}
 
 impl MessageParameters for ReadParameters {
     type Descriptor = ReadDescriptor;
     type Fields = Authorization;
+
+    async fn build(&self) -> Result<(Self::Descriptor, Option<Self::Fields>), ValidationError> {
+        let descriptor = ReadDescriptor {
+            message_timestamp: self.message_timestamp.unwrap_or_else(chrono::Utc::now),
+            message_cid: Some(self.message_cid),
+        };
+
+        Ok((descriptor, None))
+    }
+
+    fn permission_grant_id(&self) -> Option<String> {
+        self.permission_grant_id.clone()
+    }
 }
 
 #[descriptor(interface = MESSAGES, method = READ, fields = crate::auth::Authorization, parameters = ReadParameters)]
@@ -41,16 +58,34 @@ pub struct QueryParameters {
     pub filters: Option<MessagesFilter>,
     pub cursor: Option<crate::Cursor>,
     #[serde(rename = "messageTimestamp")]
-    pub message_timestamp: chrono::DateTime<chrono::Utc>,
+    pub message_timestamp: Option<chrono::DateTime<chrono::Utc>>,
     #[serde(rename = "permissionGrantId")]
     pub permission_grant_id: Option<String>,
 }
 
-impl MessageValidator for QueryParameters {}
+impl MessageValidator for QueryParameters {
+    fn validate(&self) -> Result<(), ValidationError> {
+        Ok(())
+    }
+}
 
 impl MessageParameters for QueryParameters {
     type Descriptor = QueryDescriptor;
     type Fields = Authorization;
+
+    async fn build(&self) -> Result<(Self::Descriptor, Option<Self::Fields>), ValidationError> {
+        let descriptor = QueryDescriptor {
+            message_timestamp: self.message_timestamp.unwrap_or_else(chrono::Utc::now),
+            filters: self.filters.clone().map(Into::into).into_iter().collect(),
+            cursor: self.cursor.clone(),
+        };
+
+        Ok((descriptor, None))
+    }
+
+    fn permission_grant_id(&self) -> Option<String> {
+        self.permission_grant_id.clone()
+    }
 }
 
 #[descriptor(interface = MESSAGES, method = QUERY, fields = crate::auth::Authorization, parameters = QueryParameters)]
@@ -70,16 +105,33 @@ pub struct QueryDescriptor {
 pub struct SubscribeParameters {
     pub filters: Option<MessagesFilter>,
     #[serde(rename = "messageTimestamp")]
-    pub message_timestamp: chrono::DateTime<chrono::Utc>,
+    pub message_timestamp: Option<chrono::DateTime<chrono::Utc>>,
     #[serde(rename = "permissionGrantId")]
     pub permission_grant_id: Option<String>,
 }
 
-impl MessageValidator for SubscribeParameters {}
+impl MessageValidator for SubscribeParameters {
+    fn validate(&self) -> Result<(), ValidationError> {
+        Ok(())
+    }
+}
 
 impl MessageParameters for SubscribeParameters {
     type Descriptor = SubscribeDescriptor;
     type Fields = Authorization;
+
+    async fn build(&self) -> Result<(Self::Descriptor, Option<Self::Fields>), ValidationError> {
+        let descriptor = SubscribeDescriptor {
+            message_timestamp: self.message_timestamp.unwrap_or_else(chrono::Utc::now),
+            filters: self.filters.clone().map(Into::into).into_iter().collect(),
+        };
+
+        Ok((descriptor, None))
+    }
+
+    fn permission_grant_id(&self) -> Option<String> {
+        self.permission_grant_id.clone()
+    }
 }
 
 #[descriptor(interface = MESSAGES, method = SUBSCRIBE, fields = crate::auth::Authorization, parameters = SubscribeParameters)]
@@ -185,4 +237,26 @@ mod test {
             descriptor
         );
     }
+
+    #[tokio::test]
+    async fn test_query_parameters_build() {
+        let filter = MessagesFilter {
+            interface: Some("Records".to_string()),
+            method: Some("Write".to_string()),
+            protocol: None,
+            message_timestamp: None,
+        };
+        let parameters = QueryParameters {
+            filters: Some(filter.clone()),
+            ..Default::default()
+        };
+
+        let (descriptor, fields) = parameters.build().await.unwrap();
+        assert!(fields.is_none());
+        assert_eq!(descriptor.filters, vec![crate::Filters::from(filter)]);
+        assert!(descriptor.cursor.is_none());
+
+        let (descriptor, _) = QueryParameters::default().build().await.unwrap();
+        assert!(descriptor.filters.is_empty());
+    }
 }
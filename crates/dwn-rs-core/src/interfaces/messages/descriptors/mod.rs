@@ -61,6 +61,16 @@ pub trait MessageParameters {
     fn protocol_rule(&self) -> Option<String> {
         None
     }
+
+    /// complete_fields sets the fields of the message which depend on its author, once the
+    /// message has been signed.
+    fn complete_fields(
+        &self,
+        _descriptor: &Self::Descriptor,
+        _fields: &mut Self::Fields,
+    ) -> Result<(), ValidationError> {
+        Ok(())
+    }
 }
 
 impl MessageParameters for () {
@@ -87,6 +97,12 @@ pub trait MessageDescriptor: Serialize + DeserializeOwned + PartialEq {
     fn interface(&self) -> &'static str;
     fn method(&self) -> &'static str;
 
+    /// message_timestamp returns the `messageTimestamp` of the descriptor, or `None` if the
+    /// descriptor has no timestamp.
+    fn message_timestamp(&self) -> Option<&chrono::DateTime<chrono::Utc>> {
+        None
+    }
+
     fn cid(&self) -> cid::Cid {
         generate_cid_from_serialized(self)
             .expect("Failed to generate CID from serialized message descriptor")
@@ -3,9 +3,9 @@ use serde_with::skip_serializing_none;
 use ssi_dids_core::DIDBuf;
 
 use crate::auth::Authorization;
-use crate::descriptors::MessageDescriptor;
+use crate::descriptors::{MessageDescriptor, ValidationError};
 use crate::interfaces::messages::descriptors::{CONFIGURE, PROTOCOLS, QUERY};
-use crate::{protocols, Message};
+use crate::{normalize_url, protocols, Message};
 use dwn_rs_message_derive::descriptor;
 
 use super::{MessageParameters, MessageValidator, RecordsWriteDescriptor};
@@ -21,11 +21,43 @@ pub struct ConfigureParameters {
     pub delegated_grant: Option<Message<RecordsWriteDescriptor>>,
 }
 
-impl MessageValidator for ConfigureDescriptor {}
+impl MessageValidator for ConfigureDescriptor {
+    fn validate(&self) -> Result<(), ValidationError> {
+        self.definition.validate()
+    }
+}
 
 impl MessageParameters for ConfigureParameters {
     type Descriptor = ConfigureDescriptor;
     type Fields = Authorization;
+
+    async fn build(&self) -> Result<(Self::Descriptor, Option<Self::Fields>), ValidationError> {
+        let mut definition = self.definition.clone();
+        definition.protocol = normalize_protocol(&definition.protocol)?;
+        for protocol_type in definition.types.values_mut() {
+            protocol_type.schema = protocol_type
+                .schema
+                .take()
+                .map(|schema| normalize_protocol(&schema))
+                .transpose()?;
+        }
+
+        let descriptor = ConfigureDescriptor {
+            message_timestamp: self.message_timestamp.unwrap_or_else(chrono::Utc::now),
+            definition,
+        };
+        descriptor.validate()?;
+
+        Ok((descriptor, None))
+    }
+
+    fn delegated_grant(&self) -> Option<Message<RecordsWriteDescriptor>> {
+        self.delegated_grant.clone()
+    }
+
+    fn permission_grant_id(&self) -> Option<String> {
+        self.permission_grant_id.clone()
+    }
 }
 
 #[descriptor(interface = PROTOCOLS, method = CONFIGURE, fields = crate::auth::Authorization, parameters = ConfigureParameters)]
@@ -39,21 +71,46 @@ pub struct ConfigureDescriptor {
 pub struct QueryParameters {
     pub filter: Option<QueryFilterParameters>,
     #[serde(rename = "messageTimestamp")]
-    pub message_timestamp: chrono::DateTime<chrono::Utc>,
+    pub message_timestamp: Option<chrono::DateTime<chrono::Utc>>,
     #[serde(rename = "permissionGrantId")]
     pub permission_grant_id: Option<String>,
 }
 
-impl MessageValidator for QueryParameters {}
+impl MessageValidator for QueryParameters {
+    fn validate(&self) -> Result<(), ValidationError> {
+        Ok(())
+    }
+}
 
 impl MessageParameters for QueryParameters {
     type Descriptor = QueryDescriptor;
     type Fields = Authorization;
+
+    async fn build(&self) -> Result<(Self::Descriptor, Option<Self::Fields>), ValidationError> {
+        let filter = match &self.filter {
+            Some(filter) => Some(QueryFilter {
+                protocol: Some(normalize_protocol(&filter.protocol)?),
+                recipient: None,
+            }),
+            None => None,
+        };
+
+        let descriptor = QueryDescriptor {
+            message_timestamp: self.message_timestamp.unwrap_or_else(chrono::Utc::now),
+            filter,
+        };
+
+        Ok((descriptor, None))
+    }
+
+    fn permission_grant_id(&self) -> Option<String> {
+        self.permission_grant_id.clone()
+    }
 }
 
 #[descriptor(interface = PROTOCOLS , method = QUERY, fields = crate::auth::Authorization, parameters = QueryParameters)]
 pub struct QueryDescriptor {
-    #[serde(rename = "message_timestamp")]
+    #[serde(rename = "messageTimestamp")]
     pub message_timestamp: chrono::DateTime<chrono::Utc>,
     pub filter: Option<QueryFilter>,
 }
@@ -70,6 +127,14 @@ pub struct QueryFilter {
     pub recipient: Option<DIDBuf>,
 }
 
+// normalize_protocol normalizes a protocol (or schema) URL, failing validation if it is not a
+// valid URL.
+fn normalize_protocol(url: &str) -> Result<String, ValidationError> {
+    normalize_url(url).map_err(|err| ValidationError {
+        message: format!("invalid protocol URL {}: {}", url, err),
+    })
+}
+
 #[cfg(test)]
 mod test {
     use std::collections::BTreeMap;
@@ -112,6 +177,80 @@ mod test {
         );
     }
 
+    #[tokio::test]
+    async fn test_configure_parameters_build() {
+        let parameters = ConfigureParameters {
+            definition: protocols::Definition {
+                protocol: "https://example.com/protocol?query#fragment".to_string(),
+                published: true,
+                types: BTreeMap::from([(
+                    "post".to_string(),
+                    protocols::Type {
+                        schema: Some("https://example.com/post".to_string()),
+                        data_formats: None,
+                    },
+                )]),
+                structure: BTreeMap::new(),
+            },
+            ..Default::default()
+        };
+
+        let (descriptor, fields) = parameters.build().await.unwrap();
+        assert!(fields.is_none());
+        assert_eq!(
+            descriptor.definition.protocol,
+            "https://example.com/protocol"
+        );
+        assert_eq!(
+            descriptor.definition.types["post"].schema,
+            Some("https://example.com/post".to_string())
+        );
+
+        let invalid = ConfigureParameters {
+            definition: protocols::Definition {
+                protocol: "not a url".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(invalid.build().await.is_err());
+
+        let undeclared = ConfigureParameters {
+            definition: protocols::Definition {
+                protocol: "https://example.com/protocol".to_string(),
+                structure: BTreeMap::from([("post".to_string(), Default::default())]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(undeclared.build().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_query_parameters_build() {
+        let message_timestamp = Utc::now();
+        let parameters = QueryParameters {
+            filter: Some(QueryFilterParameters {
+                protocol: "https://example.com/protocol".to_string(),
+            }),
+            message_timestamp: Some(message_timestamp),
+            ..Default::default()
+        };
+
+        let (descriptor, _) = parameters.build().await.unwrap();
+        assert_eq!(descriptor.message_timestamp, message_timestamp);
+        assert_eq!(
+            descriptor.filter,
+            Some(QueryFilter {
+                protocol: Some("https://example.com/protocol".to_string()),
+                recipient: None,
+            })
+        );
+
+        let json = serde_json::to_value(&descriptor).unwrap();
+        assert!(json.get("messageTimestamp").is_some());
+    }
+
     #[test]
     fn test_protocol_definition() {
         let protocol = "example".to_string();
@@ -3,13 +3,16 @@ use crate::auth::Authorization;
 use crate::descriptors::{MessageDescriptor, ValidationError};
 use crate::encryption::asymmetric::publickey::PublicKey;
 use crate::encryption::{
-    DerivationScheme, Encryption, KeyEncryption, KeyEncryptionAlgorithm,
+    DerivationPath, DerivationScheme, Encryption, KeyEncryption, KeyEncryptionAlgorithm,
     KeyEncryptionAlgorithmAsymmetric, KeyEncryptionAlgorithmSymmetric,
 };
 use crate::fields::WriteFields;
 use crate::filters::message_filters::Records as RecordsFilter;
 use crate::interfaces::messages::descriptors::{DELETE, QUERY, READ, RECORDS, SUBSCRIBE, WRITE};
-use crate::{normalize_url, MapValue, Message, Pagination};
+use crate::{
+    cid::generate_cid_from_serialized, normalize_url, MapValue, Message, MessageSort, Pagination,
+    SortDirection,
+};
 
 use dwn_rs_message_derive::descriptor;
 
@@ -85,6 +88,8 @@ pub struct QueryParameters {
     #[serde(rename = "dateSort")]
     pub date_sort: Option<DateSort>,
     pub pagination: Option<Pagination>,
+    #[serde(rename = "permissionGrantId")]
+    pub permission_grant_id: Option<String>,
     #[serde(rename = "protocolRole")]
     pub protocol_role: Option<String>,
     #[serde(rename = "delegatedGrant")]
@@ -138,6 +143,10 @@ impl MessageParameters for QueryParameters {
         self.delegated_grant.clone()
     }
 
+    fn permission_grant_id(&self) -> Option<String> {
+        self.permission_grant_id.clone()
+    }
+
     fn protocol_rule(&self) -> Option<String> {
         self.protocol_role.clone()
     }
@@ -172,12 +181,23 @@ pub enum DateSort {
     PublishedDescending,
 }
 
+impl From<DateSort> for MessageSort {
+    fn from(sort: DateSort) -> Self {
+        match sort {
+            DateSort::CreatedAscending => MessageSort::DateCreated(SortDirection::Ascending),
+            DateSort::CreatedDescending => MessageSort::DateCreated(SortDirection::Descending),
+            DateSort::PublishedAscending => MessageSort::DatePublished(SortDirection::Ascending),
+            DateSort::PublishedDescending => MessageSort::DatePublished(SortDirection::Descending),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct EncryptionInput {
     pub algorithm: Option<KeyEncryptionAlgorithm>,
     #[serde(rename = "initializationVector")]
     pub initialization_vector: Vec<u8>,
-    key: Vec<u8>,
+    pub key: Vec<u8>,
     #[serde(rename = "keyEncryptionInput")]
     pub key_encryption_input: Vec<KeyEncryptionInput>,
 }
@@ -228,6 +248,17 @@ pub struct WriteParameters {
     pub permission_grant_id: Option<String>,
 }
 
+impl WriteParameters {
+    /// derivation_context_id returns the context ID the `protocolContext` derivation path of the
+    /// record is derived from. The path of a new root record can't be derived until its record
+    /// ID is known, which is after it is signed.
+    pub(crate) fn derivation_context_id(&self) -> Option<&str> {
+        self.parent_context_id
+            .as_deref()
+            .or(self.record_id.as_deref())
+    }
+}
+
 impl MessageValidator for WriteParameters {
     fn validate(&self) -> Result<(), super::ValidationError> {
         if self.protocol.is_none() && self.protocol_path.is_some()
@@ -341,43 +372,66 @@ impl MessageParameters for WriteParameters {
             let key_encryption = encryption_input
                 .key_encryption_input
                 .iter()
-                .map(
-                    |input| -> Result<KeyEncryption, crate::encryption::asymmetric::Error> {
-                        let jwk = PublicKey::try_from(input.public_key.to_public())?;
-                        let key_enc_output = jwk.encrypt(&encryption_input.key)?;
-
-                        let key = base64url
-                            .encode(key_enc_output.ciphertext.as_slice())
-                            .to_string();
-                        let initialization_vector = base64url
-                            .encode(key_enc_output.nonce.as_slice())
-                            .to_string();
-                        let ephemeral_public_key =
-                            PublicKey::from_bytes(key_enc_output.ephemeral_pk.as_slice())?.jwk();
-                        let message_authentication_code =
-                            base64url.encode(key_enc_output.tag.as_slice()).to_string();
-
-                        Ok(KeyEncryption {
-                            algorithm: input.algorithm.clone().unwrap_or(
-                                KeyEncryptionAlgorithm::Asymmetric(
-                                    KeyEncryptionAlgorithmAsymmetric::EciesSecp256k1,
-                                ),
+                .map(|input| -> Result<KeyEncryption, ValidationError> {
+                    // the public key of the input is the leaf key of the record's path for
+                    // the scheme, so the record must have each property of the path.
+                    DerivationPath::record(
+                        &input.derivation_schema,
+                        &descriptor,
+                        self.derivation_context_id(),
+                    )
+                    .map_err(|err| ValidationError {
+                        message: err.to_string(),
+                    })?;
+
+                    let encryption_error =
+                        |err: crate::encryption::asymmetric::Error| ValidationError {
+                            message: format!("Error encrypting key: {}", err),
+                        };
+                    let jwk = PublicKey::try_from(input.public_key.to_public())
+                        .map_err(crate::encryption::asymmetric::Error::from)
+                        .map_err(encryption_error)?;
+                    let key_enc_output = jwk
+                        .encrypt(&encryption_input.key)
+                        .map_err(encryption_error)?;
+
+                    // the encrypted key is only the ciphertext, as the ephemeral public key,
+                    // IV and tag are each stored alongside it.
+                    let ciphertext_start =
+                        key_enc_output.ephemeral_pk.len() + key_enc_output.nonce.len();
+                    let ciphertext_end = key_enc_output.ciphertext.len() - key_enc_output.tag.len();
+                    let key = base64url
+                        .encode(&key_enc_output.ciphertext[ciphertext_start..ciphertext_end])
+                        .to_string();
+                    let initialization_vector = base64url
+                        .encode(key_enc_output.nonce.as_slice())
+                        .to_string();
+                    let ephemeral_public_key =
+                        PublicKey::from_bytes(key_enc_output.ephemeral_pk.as_slice())
+                            .map_err(encryption_error)?
+                            .jwk();
+                    let message_authentication_code =
+                        base64url.encode(key_enc_output.tag.as_slice()).to_string();
+
+                    Ok(KeyEncryption {
+                        algorithm: input.algorithm.clone().unwrap_or(
+                            KeyEncryptionAlgorithm::Asymmetric(
+                                KeyEncryptionAlgorithmAsymmetric::EciesSecp256k1,
                             ),
-                            derivation_scheme: input.derivation_schema.clone(),
-                            root_key_id: input.public_key_id.clone(),
-                            ephemeral_public_key,
-                            initialization_vector,
-                            encrypted_key: key,
-                            message_authentication_code,
-                            derived_public_key: match input.derivation_schema {
-                                DerivationScheme::ProtocolContext => Some(input.public_key.clone()),
-                                _ => None,
-                            },
-                        })
-                    },
-                )
-                .filter_map(|result| result.ok())
-                .collect();
+                        ),
+                        derivation_scheme: input.derivation_schema.clone(),
+                        root_key_id: input.public_key_id.clone(),
+                        ephemeral_public_key,
+                        initialization_vector,
+                        encrypted_key: key,
+                        message_authentication_code,
+                        derived_public_key: match input.derivation_schema {
+                            DerivationScheme::ProtocolContext => Some(input.public_key.clone()),
+                            _ => None,
+                        },
+                    })
+                })
+                .collect::<Result<Vec<KeyEncryption>, ValidationError>>()?;
 
             fields.encryption = Some(Encryption {
                 algorithm: encryption_input.clone().algorithm.unwrap_or(
@@ -405,6 +459,63 @@ impl MessageParameters for WriteParameters {
     fn protocol_rule(&self) -> Option<String> {
         self.protocol_role.clone()
     }
+
+    /// complete_fields sets the record ID of an initial write to its entry ID, and the context
+    /// ID of protocol records, both of which depend on the author of the message.
+    fn complete_fields(
+        &self,
+        descriptor: &Self::Descriptor,
+        fields: &mut Self::Fields,
+    ) -> Result<(), ValidationError> {
+        let author = fields
+            .authorization
+            .author()
+            .map_err(|err| ValidationError {
+                message: err.to_string(),
+            })?;
+
+        if let (None, Some(author)) = (&fields.record_id, author) {
+            fields.record_id = Some(descriptor.entry_id(&author)?);
+        }
+
+        if let (Some(_), Some(record_id)) = (&descriptor.protocol, &fields.record_id) {
+            fields.context_id = Some(context_id(record_id, self.parent_context_id.as_deref()));
+        }
+
+        Ok(())
+    }
+}
+
+/// context_id returns the context ID of a protocol record. A root record is its own context, and
+/// the context of any other record is nested within the context of its parent.
+pub fn context_id(record_id: &str, parent_context_id: Option<&str>) -> String {
+    match parent_context_id.map(|parent| parent.trim_end_matches('/')) {
+        Some(parent) if !parent.is_empty() => format!("{}/{}", parent, record_id),
+        _ => record_id.to_string(),
+    }
+}
+
+impl WriteDescriptor {
+    /// entry_id returns the entry ID of a RecordsWrite by the author, which is the CID of the
+    /// descriptor along with the DID of the author. The record ID of a record is the entry ID of
+    /// its initial write.
+    pub fn entry_id(&self, author: &str) -> Result<String, ValidationError> {
+        #[derive(Serialize)]
+        struct EntryIdInput<'a> {
+            #[serde(flatten)]
+            descriptor: &'a WriteDescriptor,
+            author: &'a str,
+        }
+
+        generate_cid_from_serialized(EntryIdInput {
+            descriptor: self,
+            author,
+        })
+        .map(|cid| cid.to_string())
+        .map_err(|err| ValidationError {
+            message: err.to_string(),
+        })
+    }
 }
 
 /// WriteDescriptor represents the RecordsWrite interface method for writing a record to the DWN.
@@ -656,6 +767,101 @@ mod test {
         assert_eq!(wd, de);
     }
 
+    #[test]
+    fn test_entry_id() {
+        let descriptor = WriteDescriptor {
+            data_cid: "bafkreidata".to_string(),
+            data_format: "application/json".to_string(),
+            ..Default::default()
+        };
+
+        let entry_id = descriptor.entry_id("did:example:alice").unwrap();
+        assert_eq!(entry_id, descriptor.entry_id("did:example:alice").unwrap());
+        assert_ne!(entry_id, descriptor.entry_id("did:example:bob").unwrap());
+        assert_ne!(entry_id, descriptor.cid().to_string());
+
+        let mut updated = descriptor.clone();
+        updated.message_timestamp += chrono::Duration::seconds(1);
+        assert_ne!(entry_id, updated.entry_id("did:example:alice").unwrap());
+    }
+
+    // the entry ID is the CID of the DAG-CBOR encoded descriptor with the author, as by
+    // dwn-sdk-js. The expected ID was computed separately from this implementation, by encoding
+    // the same properties as canonical DAG-CBOR and hashing them with SHA-256.
+    #[test]
+    fn test_entry_id_vector() {
+        let timestamp = chrono::DateTime::from_timestamp(1704067200, 0).unwrap();
+        let descriptor = WriteDescriptor {
+            protocol: Some("https://example.com/chat".to_string()),
+            protocol_path: Some("thread".to_string()),
+            schema: Some("https://example.com/thread".to_string()),
+            data_cid: "bafkreibm6jg3ux5qumhcn2b3flc3tyu6dmlb4xa7u5bf44yegnrjhc4yeq".to_string(),
+            data_size: 5,
+            date_created: timestamp,
+            message_timestamp: timestamp,
+            data_format: "text/plain".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            descriptor.entry_id("did:example:alice").unwrap(),
+            "bafyreie4zmj4bpfsfami7kukgtpxzsmtjkkuqgkavchp5u4ahzsqmdmqcq"
+        );
+    }
+
+    #[test]
+    fn test_context_id() {
+        assert_eq!(context_id("record", None), "record");
+        assert_eq!(context_id("record", Some("")), "record");
+        assert_eq!(context_id("record", Some("root")), "root/record");
+        assert_eq!(
+            context_id("record", Some("root/parent/")),
+            "root/parent/record"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_parameters_ids() {
+        let mut jwk = JWK::generate_ed25519().unwrap();
+        jwk.key_id = Some("did:example:alice#key1".to_string());
+
+        let parameters = WriteParameters {
+            protocol: Some("https://example.com/chat".to_string()),
+            protocol_path: Some("thread/chat".to_string()),
+            parent_context_id: Some("thread".to_string()),
+            data: Some(b"hello".to_vec()),
+            data_size: Some(5),
+            data_format: "text/plain".to_string(),
+            ..Default::default()
+        };
+        let message = Message::<WriteDescriptor>::create(parameters.clone(), Some(jwk.clone()))
+            .await
+            .unwrap();
+
+        let record_id = message.descriptor.entry_id("did:example:alice").unwrap();
+        assert_eq!(message.fields.record_id, Some(record_id.clone()));
+        assert_eq!(
+            message.fields.context_id,
+            Some(format!("thread/{}", record_id))
+        );
+        assert!(message.is_initial_write().unwrap());
+        assert!(message.verify_context_id(Some("thread")).is_ok());
+        assert!(message.verify_context_id(None).is_err());
+
+        // updates keep the record ID of the initial write.
+        let update = Message::<WriteDescriptor>::create(
+            WriteParameters {
+                record_id: Some(record_id.clone()),
+                ..parameters
+            },
+            Some(jwk),
+        )
+        .await
+        .unwrap();
+        assert_eq!(update.fields.record_id, Some(record_id));
+        assert!(update.verify_context_id(Some("thread")).is_ok());
+    }
+
     #[test]
     fn test_subscribe_descriptor() {
         let message_timestamp = DateTime::from_str(
@@ -2,7 +2,10 @@ use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 
 use crate::{
-    auth::{authorization::Authorization, jws::JWS},
+    auth::{
+        authorization::{check_descriptor_cid, Authorization},
+        jws::{AttestationPayload, JwsError, KeyResolver, JWS},
+    },
     encryption::Encryption,
     Value,
 };
@@ -19,6 +22,12 @@ pub trait MessageFields: Default {
         None
     }
 
+    /// strip_encoded_data removes the encoded data from the message fields (if any), for when
+    /// the message is needed without its data.
+    fn strip_encoded_data(&mut self) {
+        let _ = self.encoded_data();
+    }
+
     // encode_data encodes the data for the message
     fn encode_data(&mut self, _data: Value) {
         // no-op
@@ -50,6 +59,56 @@ impl Default for Fields {
     }
 }
 
+impl Fields {
+    /// authorization returns the authorization for the message, regardless of the fields type.
+    pub fn authorization(&self) -> &Authorization {
+        match self {
+            Fields::Write(write_fields) => &write_fields.authorization,
+            Fields::InitialWriteField(initial_write_field) => {
+                &initial_write_field.write_fields.authorization
+            }
+            Fields::Authorization(auth) => auth,
+        }
+    }
+}
+
+impl From<WriteFields> for Fields {
+    fn from(write_fields: WriteFields) -> Self {
+        Fields::Write(write_fields)
+    }
+}
+
+impl From<Authorization> for Fields {
+    fn from(authorization: Authorization) -> Self {
+        Fields::Authorization(authorization)
+    }
+}
+
+impl From<Fields> for WriteFields {
+    fn from(fields: Fields) -> Self {
+        match fields {
+            Fields::Write(write_fields) => write_fields,
+            Fields::InitialWriteField(initial_write_field) => initial_write_field.write_fields,
+            Fields::Authorization(authorization) => WriteFields {
+                authorization,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl From<Fields> for Authorization {
+    fn from(fields: Fields) -> Self {
+        match fields {
+            Fields::Write(write_fields) => write_fields.authorization,
+            Fields::InitialWriteField(initial_write_field) => {
+                initial_write_field.write_fields.authorization
+            }
+            Fields::Authorization(authorization) => authorization,
+        }
+    }
+}
+
 impl MessageFields for Fields {
     fn encoded_data(&mut self) -> Option<Value> {
         match self {
@@ -126,6 +185,28 @@ pub struct WriteFields {
     pub encoded_data: Option<String>,
 }
 
+impl WriteFields {
+    /// verify_attestation verifies the attestation of a RecordsWrite, and that it attests to the
+    /// given descriptor. The DID of the attester is returned, or `None` if the write has no
+    /// attestation.
+    pub async fn verify_attestation<R: KeyResolver>(
+        &self,
+        descriptor: &WriteDescriptor,
+        resolver: &R,
+    ) -> Result<Option<String>, JwsError> {
+        let Some(ref attestation) = self.attestation else {
+            return Ok(None);
+        };
+
+        let (attester, payload) = attestation
+            .verify::<AttestationPayload, R>(resolver)
+            .await?;
+        check_descriptor_cid(descriptor, &payload.descriptor_cid)?;
+
+        Ok(Some(attester))
+    }
+}
+
 impl MessageFields for WriteFields {
     fn encoded_data(&mut self) -> Option<Value> {
         Some(
@@ -149,6 +230,7 @@ impl MessageFields for WriteFields {
 mod tests {
     use crate::{
         auth::jws::SignatureEntry,
+        descriptors::MessageDescriptor,
         encryption::{
             DerivationScheme, KeyEncryption, KeyEncryptionAlgorithm,
             KeyEncryptionAlgorithmAsymmetric, KeyEncryptionAlgorithmSymmetric,
@@ -382,4 +464,42 @@ mod tests {
             _ => unreachable!(),
         }
     }
+
+    #[tokio::test]
+    async fn test_verify_attestation() {
+        let mut jwk = JWK::generate_ed25519().unwrap();
+        jwk.key_id = Some("did:example:alice#key1".to_string());
+
+        let descriptor = WriteDescriptor::default();
+        let mut fields = WriteFields::default();
+        assert_eq!(
+            fields.verify_attestation(&descriptor, &jwk).await.unwrap(),
+            None
+        );
+
+        let payload = AttestationPayload {
+            descriptor_cid: descriptor.cid(),
+        };
+        fields.attestation = Some(JWS::create(payload, Some(vec![jwk.clone()])).await.unwrap());
+        assert_eq!(
+            fields.verify_attestation(&descriptor, &jwk).await.unwrap(),
+            Some("did:example:alice".to_string())
+        );
+
+        // an attestation of another descriptor is rejected.
+        let mut other = descriptor.clone();
+        other.data_size += 1;
+        assert!(matches!(
+            fields.verify_attestation(&other, &jwk).await,
+            Err(JwsError::DescriptorCidMismatch { .. })
+        ));
+
+        // as is an attestation signed by another key.
+        let mut other_jwk = JWK::generate_ed25519().unwrap();
+        other_jwk.key_id = jwk.key_id.clone();
+        assert!(fields
+            .verify_attestation(&descriptor, &other_jwk)
+            .await
+            .is_err());
+    }
 }
@@ -0,0 +1,441 @@
+//! The indexes messages are stored with in the message store and event log.
+//!
+//! The index names are shared by the message stores, the event log and the [`crate::filters`]
+//! matched against them, and are the names used by dwn-sdk-js. [`MessageIndexes`] builds the
+//! canonical set of indexes for a message.
+use crate::{
+    auth::{Authorization, JwsError},
+    descriptors::{
+        ConfigureDescriptor, DeleteDescriptor, MessageDescriptor, Protocols, Records,
+        RecordsWriteDescriptor,
+    },
+    Descriptor, MapValue, Message, Value,
+};
+
+pub const INTERFACE: &str = "interface";
+pub const METHOD: &str = "method";
+pub const AUTHOR: &str = "author";
+pub const ATTESTER: &str = "attester";
+pub const RECIPIENT: &str = "recipient";
+pub const PROTOCOL: &str = "protocol";
+pub const PROTOCOL_PATH: &str = "protocolPath";
+pub const SCHEMA: &str = "schema";
+pub const RECORD_ID: &str = "recordId";
+pub const CONTEXT_ID: &str = "contextId";
+pub const PARENT_ID: &str = "parentId";
+pub const DATA_CID: &str = "dataCid";
+pub const DATA_SIZE: &str = "dataSize";
+pub const DATA_FORMAT: &str = "dataFormat";
+pub const DATE_CREATED: &str = "dateCreated";
+pub const DATE_PUBLISHED: &str = "datePublished";
+pub const MESSAGE_TIMESTAMP: &str = "messageTimestamp";
+pub const PUBLISHED: &str = "published";
+pub const PRUNE: &str = "prune";
+pub const IS_LATEST_BASE_STATE: &str = "isLatestBaseState";
+
+/// TAG_PREFIX is the prefix of record tags when they are flattened into the indexes of a
+/// message, such as `tag.status`.
+pub const TAG_PREFIX: &str = "tag.";
+
+/// MessageIndexes provides the indexes and tags a message is stored with. Indexes which depend
+/// on the state of the store rather than the message, such as `isLatestBaseState`, are added by
+/// the caller storing the message.
+pub trait MessageIndexes {
+    /// indexes returns the indexes of the message. The author of the message is indexed if the
+    /// message is signed.
+    fn indexes(&self) -> Result<MapValue, JwsError>;
+
+    /// tags returns the tags of the message, keyed by the tag name.
+    fn tags(&self) -> MapValue {
+        MapValue::new()
+    }
+
+    /// key_values returns the indexes of the message, with its tags flattened into them.
+    fn key_values(&self) -> Result<MapValue, JwsError> {
+        let mut indexes = self.indexes()?;
+        indexes.extend(flatten_tags(self.tags()));
+
+        Ok(indexes)
+    }
+}
+
+/// flatten_tags prefixes each tag name with [`TAG_PREFIX`], so that the tags can be stored with
+/// the indexes of a message.
+pub fn flatten_tags(tags: MapValue) -> MapValue {
+    tags.into_iter()
+        .map(|(tag, value)| (format!("{}{}", TAG_PREFIX, tag), value))
+        .collect()
+}
+
+/// split_tags splits flattened indexes into the indexes and the tags of a message. It is the
+/// inverse of [`MessageIndexes::key_values`].
+pub fn split_tags(key_values: MapValue) -> (MapValue, MapValue) {
+    key_values.into_iter().fold(
+        (MapValue::new(), MapValue::new()),
+        |(mut indexes, mut tags), (key, value)| {
+            match key.strip_prefix(TAG_PREFIX) {
+                Some(tag) => tags.insert(tag.to_string(), value),
+                None => indexes.insert(key, value),
+            };
+
+            (indexes, tags)
+        },
+    )
+}
+
+impl MessageIndexes for Message<RecordsWriteDescriptor> {
+    fn indexes(&self) -> Result<MapValue, JwsError> {
+        let descriptor = &self.descriptor;
+        let fields = &self.fields;
+
+        let mut indexes = base_indexes(
+            descriptor,
+            Value::DateTime(descriptor.message_timestamp),
+            &fields.authorization,
+        )?;
+        indexes.extend([
+            (
+                DATA_CID.to_string(),
+                Value::String(descriptor.data_cid.clone()),
+            ),
+            (
+                DATA_SIZE.to_string(),
+                Value::Number(descriptor.data_size as i64),
+            ),
+            (
+                DATA_FORMAT.to_string(),
+                Value::String(descriptor.data_format.clone()),
+            ),
+            (
+                DATE_CREATED.to_string(),
+                Value::DateTime(descriptor.date_created),
+            ),
+            (
+                PUBLISHED.to_string(),
+                Value::Bool(descriptor.published.unwrap_or_default()),
+            ),
+        ]);
+
+        let optional = [
+            (RECORD_ID, fields.record_id.clone()),
+            (CONTEXT_ID, fields.context_id.clone()),
+            (PROTOCOL, descriptor.protocol.clone()),
+            (PROTOCOL_PATH, descriptor.protocol_path.clone()),
+            (RECIPIENT, descriptor.recipient.clone()),
+            (SCHEMA, descriptor.schema.clone()),
+            (PARENT_ID, descriptor.parent_id.clone()),
+        ];
+        for (key, value) in optional {
+            if let Some(value) = value {
+                indexes.insert(key.to_string(), Value::String(value));
+            }
+        }
+
+        if let Some(date_published) = descriptor.date_published {
+            indexes.insert(DATE_PUBLISHED.to_string(), Value::DateTime(date_published));
+        }
+
+        if let Some(attester) = fields
+            .attestation
+            .as_ref()
+            .and_then(|attestation| attestation.signer().ok())
+        {
+            indexes.insert(ATTESTER.to_string(), Value::String(attester));
+        }
+
+        Ok(indexes)
+    }
+
+    fn tags(&self) -> MapValue {
+        self.descriptor.tags.clone().unwrap_or_default()
+    }
+}
+
+impl MessageIndexes for Message<DeleteDescriptor> {
+    fn indexes(&self) -> Result<MapValue, JwsError> {
+        let descriptor = &self.descriptor;
+
+        let mut indexes = base_indexes(
+            descriptor,
+            Value::DateTime(descriptor.message_timestamp),
+            &self.fields,
+        )?;
+        indexes.extend([
+            (
+                RECORD_ID.to_string(),
+                Value::String(descriptor.record_id.clone()),
+            ),
+            (PRUNE.to_string(), Value::Bool(descriptor.prune)),
+        ]);
+
+        Ok(indexes)
+    }
+}
+
+impl MessageIndexes for Message<ConfigureDescriptor> {
+    fn indexes(&self) -> Result<MapValue, JwsError> {
+        let descriptor = &self.descriptor;
+
+        let mut indexes = base_indexes(
+            descriptor,
+            Value::DateTime(descriptor.message_timestamp),
+            &self.fields,
+        )?;
+        indexes.extend([
+            (
+                PROTOCOL.to_string(),
+                Value::String(descriptor.definition.protocol.clone()),
+            ),
+            (
+                PUBLISHED.to_string(),
+                Value::Bool(descriptor.definition.published),
+            ),
+        ]);
+
+        Ok(indexes)
+    }
+}
+
+impl MessageIndexes for Message<Descriptor> {
+    fn indexes(&self) -> Result<MapValue, JwsError> {
+        let fields = self.fields.clone();
+
+        match &self.descriptor {
+            Descriptor::Records(Records::Write(descriptor)) => Message {
+                descriptor: descriptor.clone(),
+                fields: fields.into(),
+            }
+            .indexes(),
+            Descriptor::Records(Records::Delete(descriptor)) => Message {
+                descriptor: descriptor.clone(),
+                fields: fields.into(),
+            }
+            .indexes(),
+            Descriptor::Protocols(Protocols::Configure(descriptor)) => Message {
+                descriptor: descriptor.clone(),
+                fields: fields.into(),
+            }
+            .indexes(),
+            descriptor => base_indexes(
+                descriptor,
+                Value::DateTime(*descriptor.message_timestamp()),
+                self.fields.authorization(),
+            ),
+        }
+    }
+
+    fn tags(&self) -> MapValue {
+        match &self.descriptor {
+            Descriptor::Records(Records::Write(descriptor)) => {
+                descriptor.tags.clone().unwrap_or_default()
+            }
+            _ => MapValue::new(),
+        }
+    }
+}
+
+/// base_indexes returns the indexes shared by every message: its interface, method, timestamp
+/// and author.
+fn base_indexes<D: MessageDescriptor>(
+    descriptor: &D,
+    message_timestamp: Value,
+    authorization: &Authorization,
+) -> Result<MapValue, JwsError> {
+    let mut indexes = MapValue::from([
+        (
+            INTERFACE.to_string(),
+            Value::String(descriptor.interface().to_string()),
+        ),
+        (
+            METHOD.to_string(),
+            Value::String(descriptor.method().to_string()),
+        ),
+        (MESSAGE_TIMESTAMP.to_string(), message_timestamp),
+    ]);
+
+    if let Some(author) = authorization.author()? {
+        indexes.insert(AUTHOR.to_string(), Value::String(author));
+    }
+
+    Ok(indexes)
+}
+
+#[cfg(test)]
+mod test {
+    use ssi_jwk::JWK;
+
+    use super::*;
+    use crate::{
+        descriptors::{
+            records::{DeleteParameters, WriteParameters},
+            CONFIGURE, DELETE, PROTOCOLS, RECORDS, WRITE,
+        },
+        protocols::Definition,
+        Persona,
+    };
+
+    fn write_parameters() -> WriteParameters {
+        WriteParameters {
+            protocol: Some("http://example.com/".to_string()),
+            protocol_path: Some("thread".to_string()),
+            data: Some(b"hello".to_vec()),
+            data_size: Some(5),
+            data_format: "application/json".to_string(),
+            tags: Some(MapValue::from([(
+                "status".to_string(),
+                Value::String("draft".to_string()),
+            )])),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_indexes() {
+        let message = Message::<RecordsWriteDescriptor>::create(
+            write_parameters(),
+            Some(Persona::with_did("did:example:alice").signer().unwrap()),
+        )
+        .await
+        .unwrap();
+        let descriptor = &message.descriptor;
+        let string = |value: &str| Value::String(value.to_string());
+
+        let expected = MapValue::from([
+            (INTERFACE.to_string(), string(RECORDS)),
+            (METHOD.to_string(), string(WRITE)),
+            (AUTHOR.to_string(), string("did:example:alice")),
+            (
+                RECORD_ID.to_string(),
+                string(message.fields.record_id.as_ref().unwrap()),
+            ),
+            (
+                CONTEXT_ID.to_string(),
+                string(message.fields.context_id.as_ref().unwrap()),
+            ),
+            (PROTOCOL.to_string(), string("http://example.com/")),
+            (PROTOCOL_PATH.to_string(), string("thread")),
+            (DATA_CID.to_string(), string(&descriptor.data_cid)),
+            (DATA_SIZE.to_string(), Value::Number(5)),
+            (DATA_FORMAT.to_string(), string("application/json")),
+            (
+                DATE_CREATED.to_string(),
+                Value::DateTime(descriptor.date_created),
+            ),
+            (
+                MESSAGE_TIMESTAMP.to_string(),
+                Value::DateTime(descriptor.message_timestamp),
+            ),
+            (PUBLISHED.to_string(), Value::Bool(false)),
+        ]);
+
+        assert_eq!(message.indexes().unwrap(), expected);
+        assert_eq!(
+            message.into_generic().indexes().unwrap(),
+            expected,
+            "generic messages are indexed as their concrete descriptor"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_indexes() {
+        let message = Message::<RecordsWriteDescriptor>::create(write_parameters(), None::<JWK>)
+            .await
+            .unwrap();
+
+        let indexes = message.indexes().unwrap();
+        assert_eq!(indexes.get(AUTHOR), None);
+        assert_eq!(indexes.get(RECORD_ID), None);
+    }
+
+    #[tokio::test]
+    async fn test_tags() {
+        let message = Message::<RecordsWriteDescriptor>::create(
+            write_parameters(),
+            Some(Persona::with_did("did:example:alice").signer().unwrap()),
+        )
+        .await
+        .unwrap();
+
+        let tags = message.tags();
+        assert_eq!(
+            tags,
+            MapValue::from([("status".to_string(), Value::String("draft".to_string()))])
+        );
+
+        let key_values = message.key_values().unwrap();
+        assert_eq!(
+            key_values.get("tag.status"),
+            Some(&Value::String("draft".to_string()))
+        );
+        assert_eq!(key_values.get("status"), None);
+
+        let (indexes, split) = split_tags(key_values);
+        assert_eq!(indexes, message.indexes().unwrap());
+        assert_eq!(split, tags);
+    }
+
+    #[tokio::test]
+    async fn test_delete_indexes() {
+        let deleted = chrono::Utc::now();
+        let parameters = DeleteParameters {
+            record_id: "record".to_string(),
+            message_timestamp: Some(deleted),
+            prune: Some(true),
+            ..Default::default()
+        };
+        let message = Message::<DeleteDescriptor>::create(
+            parameters,
+            Some(Persona::with_did("did:example:bob").signer().unwrap()),
+        )
+        .await
+        .unwrap();
+
+        let expected = [
+            (INTERFACE, Value::String(RECORDS.to_string())),
+            (METHOD, Value::String(DELETE.to_string())),
+            (AUTHOR, Value::String("did:example:bob".to_string())),
+            (RECORD_ID, Value::String("record".to_string())),
+            (MESSAGE_TIMESTAMP, Value::DateTime(deleted)),
+            (PRUNE, Value::Bool(true)),
+        ];
+
+        let indexes = message.indexes().unwrap();
+        assert_eq!(indexes.len(), expected.len());
+        for (key, value) in expected {
+            assert_eq!(indexes.get(key), Some(&value), "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_configure_indexes() {
+        let now = chrono::Utc::now();
+        let message = Message {
+            descriptor: ConfigureDescriptor {
+                message_timestamp: now,
+                definition: Definition {
+                    protocol: "http://example.com/".to_string(),
+                    published: true,
+                    ..Default::default()
+                },
+            },
+            fields: Default::default(),
+        };
+
+        let indexes = message.indexes().unwrap();
+
+        assert_eq!(
+            indexes.get(INTERFACE),
+            Some(&Value::String(PROTOCOLS.to_string()))
+        );
+        assert_eq!(
+            indexes.get(METHOD),
+            Some(&Value::String(CONFIGURE.to_string()))
+        );
+        assert_eq!(
+            indexes.get(PROTOCOL),
+            Some(&Value::String("http://example.com/".to_string()))
+        );
+        assert_eq!(indexes.get(PUBLISHED), Some(&Value::Bool(true)));
+        assert_eq!(indexes.get(MESSAGE_TIMESTAMP), Some(&Value::DateTime(now)));
+    }
+}
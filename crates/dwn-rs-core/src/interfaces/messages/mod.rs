@@ -1,5 +1,7 @@
 pub mod descriptors;
 pub mod fields;
+pub mod indexes;
+pub mod permissions;
 pub mod protocols;
 
 use std::collections::TryReserveError;
@@ -10,7 +12,9 @@ use crate::fields::MessageFields;
 use crate::{auth::Authorization, interfaces::messages::descriptors::MessageParameters};
 use cid::Cid;
 pub use descriptors::Descriptor;
-use descriptors::{MessageDescriptor, MessageValidator, RecordsWriteDescriptor, ValidationError};
+use descriptors::{
+    records, MessageDescriptor, MessageValidator, RecordsWriteDescriptor, ValidationError,
+};
 pub use fields::Fields;
 
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
@@ -31,6 +35,35 @@ impl<D: MessageDescriptor + MessageValidator> Message<D> {
     }
 }
 
+impl Message<Descriptor> {
+    /// try_into_message converts the generic message into a message with a concrete descriptor,
+    /// failing if the descriptor is not of the requested type.
+    pub fn try_into_message<D>(self) -> Result<Message<D>, ValidationError>
+    where
+        D: MessageDescriptor + DeserializeOwned + TryFrom<Descriptor, Error = ValidationError>,
+        D::Fields: From<Fields>,
+    {
+        Ok(Message {
+            descriptor: D::try_from(self.descriptor)?,
+            fields: self.fields.into(),
+        })
+    }
+}
+
+impl<D> Message<D>
+where
+    D: MessageDescriptor + DeserializeOwned + Into<Descriptor>,
+    Fields: From<D::Fields>,
+{
+    /// into_generic converts the message into a message with a generic `Descriptor`.
+    pub fn into_generic(self) -> Message<Descriptor> {
+        Message {
+            descriptor: self.descriptor.into(),
+            fields: self.fields.into(),
+        }
+    }
+}
+
 impl Message<RecordsWriteDescriptor> {
     // attest is used to add an attestation to a message. It can be called multiple
     // times to add multiple attestations. The message must be a RecordsWriteDescriptor.
@@ -55,6 +88,101 @@ impl Message<RecordsWriteDescriptor> {
 
         Ok(())
     }
+
+    /// entry_id returns the entry ID of the message, computed from its descriptor and author.
+    pub fn entry_id(&self) -> Result<String, ValidationError> {
+        let author = self
+            .fields
+            .authorization
+            .author()
+            .map_err(|err| ValidationError {
+                message: err.to_string(),
+            })?
+            .ok_or_else(|| ValidationError {
+                message: "RecordsWrite must be signed to compute its entry ID".to_string(),
+            })?;
+
+        self.descriptor.entry_id(&author)
+    }
+
+    /// is_initial_write returns true if the message is the initial write of its record, which
+    /// is the write whose entry ID is the record ID.
+    pub fn is_initial_write(&self) -> Result<bool, ValidationError> {
+        Ok(self.fields.record_id.as_deref() == Some(self.entry_id()?.as_str()))
+    }
+
+    /// verify_context_id checks the context ID of the message, given the context ID of the
+    /// parent record (if any). Only protocol records have a context ID.
+    pub fn verify_context_id(
+        &self,
+        parent_context_id: Option<&str>,
+    ) -> Result<(), ValidationError> {
+        let expected = match (&self.descriptor.protocol, &self.fields.record_id) {
+            (Some(_), Some(record_id)) => Some(records::context_id(record_id, parent_context_id)),
+            (Some(_), None) => {
+                return Err(ValidationError {
+                    message: "recordId is required".to_string(),
+                })
+            }
+            (None, _) => None,
+        };
+
+        if self.fields.context_id != expected {
+            return Err(ValidationError {
+                message: format!(
+                    "contextId {:?} does not match the expected contextId {:?}",
+                    self.fields.context_id, expected
+                ),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// sign_as_owner adds the owner signature to a message written by another author, so that
+    /// the owner can keep the record in their own DWN. The message must already be signed by
+    /// its author.
+    pub async fn sign_as_owner<S: JwsSigner>(&mut self, signer: S) -> Result<(), ValidationError> {
+        self.owner_signature(signer, None).await
+    }
+
+    /// sign_as_owner_delegate adds the owner signature to a message, signed by a delegate of the
+    /// owner with the delegated grant the owner gave them.
+    pub async fn sign_as_owner_delegate<S: JwsSigner>(
+        &mut self,
+        signer: S,
+        grant: Message<RecordsWriteDescriptor>,
+    ) -> Result<(), ValidationError> {
+        self.owner_signature(signer, Some(grant)).await
+    }
+
+    async fn owner_signature<S: JwsSigner>(
+        &mut self,
+        signer: S,
+        grant: Option<Message<RecordsWriteDescriptor>>,
+    ) -> Result<(), ValidationError> {
+        if self.fields.authorization.signer().ok().flatten().is_none() {
+            return Err(ValidationError {
+                message: "the message must be signed by its author before the owner".to_string(),
+            });
+        }
+
+        let delegated_grant_id = grant
+            .as_ref()
+            .map(|grant| grant.stored_cid())
+            .transpose()
+            .map_err(|err| ValidationError {
+                message: err.to_string(),
+            })?;
+        let signature =
+            Self::create_signature(&self.descriptor, signer, delegated_grant_id, None, None)
+                .await?;
+
+        self.fields.authorization.owner_signature = Some(signature);
+        self.fields.authorization.owner_delegated_grant = grant.map(Box::new);
+
+        Ok(())
+    }
 }
 
 impl<D> Message<D>
@@ -66,6 +194,19 @@ where
         generate_cid_from_serialized(self)
     }
 
+    /// stored_cid returns the CID of the message without any encoded data. This is the CID the
+    /// message is stored with, and which other messages use to refer to it (such as the
+    /// `delegatedGrantId` of a signature).
+    pub fn stored_cid(&self) -> Result<Cid, EncodeError<TryReserveError>>
+    where
+        D: Clone,
+    {
+        let mut message = self.clone();
+        message.fields.strip_encoded_data();
+
+        message.cid()
+    }
+
     pub async fn create<S: JwsSigner>(
         parameters: D::Parameters,
         signer: Option<S>,
@@ -88,6 +229,7 @@ where
         // If the fields are None, we create an empty Fields instance.
         let mut fields = fields.unwrap_or_default();
         fields.set_authorization(auth);
+        parameters.complete_fields(&descriptor, &mut fields)?;
 
         Ok(Self { descriptor, fields })
     }
@@ -97,13 +239,17 @@ where
         signer: S,
         delegated_grant: Option<Message<RecordsWriteDescriptor>>,
         permission_grant_id: Option<String>,
-        protocol_rule: Option<String>,
+        protocol_role: Option<String>,
     ) -> Result<Authorization, ValidationError> {
         let delegated_grant_id: Option<Cid> = if let Some(delegated_grant) = delegated_grant.clone()
         {
-            Some(delegated_grant.cid().map_err(|err| ValidationError {
-                message: err.to_string(),
-            })?)
+            Some(
+                delegated_grant
+                    .stored_cid()
+                    .map_err(|err| ValidationError {
+                        message: err.to_string(),
+                    })?,
+            )
         } else {
             None
         };
@@ -113,7 +259,7 @@ where
             signer,
             delegated_grant_id,
             permission_grant_id,
-            protocol_rule,
+            protocol_role,
         )
         .await?;
 
@@ -134,7 +280,7 @@ where
         signer: S,
         delegated_grant_id: Option<Cid>,
         permission_grant_id: Option<String>,
-        protocol_rule: Option<String>,
+        protocol_role: Option<String>,
     ) -> Result<JWS, ValidationError> {
         let descriptor_cid = descriptor.cid();
 
@@ -142,7 +288,7 @@ where
             descriptor_cid,
             delegated_grant_id,
             permission_grant_id,
-            protocol_rule,
+            protocol_role,
         };
 
         let signature = jws::JWS::create(payload, Some(vec![signer]))
@@ -208,13 +354,19 @@ impl<'de> Deserialize<'de> for Message<Descriptor> {
 #[cfg(test)]
 mod test {
 
+    use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD as base64url};
     use chrono::Utc;
     use descriptors::{ReadDescriptor, Records};
     use dwn_rs_message_derive::descriptor;
     use fields::MessageFields;
     use serde_json::json;
 
-    use crate::auth::Authorization;
+    use ssi_jwk::JWK;
+
+    use crate::auth::{Authorization, JwsError, KeyResolver};
+    use crate::descriptors::records::WriteParameters as RecordsWriteParameters;
+    use crate::permissions::{PermissionGrantData, Scope};
+    use crate::Persona;
 
     use super::*;
 
@@ -312,4 +464,219 @@ mod test {
 
         assert_eq!(message, expected);
     }
+
+    /// Keys resolves the key IDs of the signers in a test.
+    struct Keys(Vec<JWK>);
+
+    impl KeyResolver for Keys {
+        async fn resolve_key(&self, kid: &str) -> Result<JWK, JwsError> {
+            self.0
+                .iter()
+                .find(|jwk| jwk.key_id.as_deref() == Some(kid))
+                .map(JWK::to_public)
+                .ok_or_else(|| JwsError::KeyResolutionError(kid.to_string(), "unknown".to_string()))
+        }
+    }
+
+    fn write_parameters() -> RecordsWriteParameters {
+        RecordsWriteParameters {
+            data: Some(b"hello".to_vec()),
+            data_size: Some(5),
+            data_format: "text/plain".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sign_as_owner() {
+        let (alice, bob) = (
+            Persona::with_did("did:example:alice").signer().unwrap(),
+            Persona::with_did("did:example:bob").signer().unwrap(),
+        );
+        let keys = Keys(vec![alice.clone(), bob.clone()]);
+
+        let mut unsigned =
+            Message::<RecordsWriteDescriptor>::create(write_parameters(), None::<JWK>)
+                .await
+                .unwrap();
+        assert!(unsigned.sign_as_owner(bob.clone()).await.is_err());
+
+        let mut message =
+            Message::<RecordsWriteDescriptor>::create(write_parameters(), Some(alice))
+                .await
+                .unwrap();
+        let authorization = &message.fields.authorization;
+        assert_eq!(authorization.owner().unwrap(), None);
+        assert!(authorization
+            .verify_owner(&message.descriptor, &keys)
+            .await
+            .unwrap()
+            .is_none());
+
+        message.sign_as_owner(bob).await.unwrap();
+        let authorization = &message.fields.authorization;
+        assert_eq!(
+            authorization.owner().unwrap().as_deref(),
+            Some("did:example:bob")
+        );
+        assert_eq!(
+            authorization.author().unwrap().as_deref(),
+            Some("did:example:alice")
+        );
+
+        let (owner, payload) = authorization
+            .verify_owner(&message.descriptor, &keys)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(owner, "did:example:bob");
+        assert_eq!(payload.descriptor_cid, message.descriptor.cid());
+    }
+
+    /// delegated_grant creates a grant of writing chat records from the grantor to the grantee,
+    /// which expires after the given number of days, with its data encoded as it is embedded.
+    async fn delegated_grant(
+        grantor: JWK,
+        grantee: &str,
+        delegated: bool,
+        days: i64,
+    ) -> Message<RecordsWriteDescriptor> {
+        let parameters = RecordsWriteParameters::permission_grant(
+            grantee,
+            &PermissionGrantData {
+                date_expires: Utc::now() + chrono::Duration::days(days),
+                request_id: None,
+                description: None,
+                delegated,
+                scope: Scope {
+                    interface: descriptors::RECORDS.to_string(),
+                    method: descriptors::WRITE.to_string(),
+                    protocol: Some("https://example.com/chat".to_string()),
+                    ..Default::default()
+                },
+                conditions: None,
+            },
+        )
+        .unwrap();
+        let encoded = base64url.encode(parameters.data.as_ref().unwrap());
+
+        let mut grant = Message::<RecordsWriteDescriptor>::create(parameters, Some(grantor))
+            .await
+            .unwrap();
+        grant.fields.encoded_data = Some(encoded);
+
+        grant
+    }
+
+    #[tokio::test]
+    async fn test_sign_as_owner_delegate() {
+        let (alice, bob, carol) = (
+            Persona::with_did("did:example:alice").signer().unwrap(),
+            Persona::with_did("did:example:bob").signer().unwrap(),
+            Persona::with_did("did:example:carol").signer().unwrap(),
+        );
+        let keys = Keys(vec![alice.clone(), bob.clone(), carol.clone()]);
+
+        // bob delegates signing as the owner of records to carol.
+        let grant = delegated_grant(bob.clone(), "did:example:carol", true, 1).await;
+        // the grant is named by its CID without any encoded data.
+        assert_ne!(grant.stored_cid().unwrap(), grant.cid().unwrap());
+
+        let mut message =
+            Message::<RecordsWriteDescriptor>::create(write_parameters(), Some(alice))
+                .await
+                .unwrap();
+        message
+            .sign_as_owner_delegate(carol, grant.clone())
+            .await
+            .unwrap();
+
+        let authorization = &message.fields.authorization;
+        assert_eq!(
+            authorization.owner().unwrap().as_deref(),
+            Some("did:example:bob")
+        );
+        let (owner, payload) = authorization
+            .verify_owner(&message.descriptor, &keys)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(owner, "did:example:bob");
+        assert_eq!(
+            payload.delegated_grant_id,
+            Some(grant.stored_cid().unwrap())
+        );
+
+        // the owner signature must name the embedded grant.
+        let mut tampered = message.clone();
+        let mut other_grant = grant;
+        other_grant.descriptor.recipient = Some("did:example:dave".to_string());
+        tampered.fields.authorization.owner_delegated_grant = Some(Box::new(other_grant));
+        assert!(matches!(
+            tampered
+                .fields
+                .authorization
+                .verify_owner(&tampered.descriptor, &keys)
+                .await,
+            Err(JwsError::DelegatedGrantMismatch)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sign_as_owner_delegate_invalid_grant() {
+        let (alice, bob, carol, dave) = (
+            Persona::with_did("did:example:alice").signer().unwrap(),
+            Persona::with_did("did:example:bob").signer().unwrap(),
+            Persona::with_did("did:example:carol").signer().unwrap(),
+            Persona::with_did("did:example:dave").signer().unwrap(),
+        );
+        let keys = Keys(vec![
+            alice.clone(),
+            bob.clone(),
+            carol.clone(),
+            dave.clone(),
+        ]);
+
+        // a record written by bob which isn't a grant at all.
+        let mut record = Message::<RecordsWriteDescriptor>::create(
+            RecordsWriteParameters {
+                recipient: Some("did:example:dave".to_string()),
+                ..write_parameters()
+            },
+            Some(bob.clone()),
+        )
+        .await
+        .unwrap();
+        record.fields.encoded_data = Some(base64url.encode(b"hello"));
+
+        let grants = vec![
+            // dave can't sign as bob with a record of bob's which isn't a grant,
+            record,
+            // nor with a grant given to carol,
+            delegated_grant(bob.clone(), "did:example:carol", true, 1).await,
+            // nor with a grant which isn't delegated,
+            delegated_grant(bob.clone(), "did:example:dave", false, 1).await,
+            // nor with a grant which has expired.
+            delegated_grant(bob.clone(), "did:example:dave", true, -1).await,
+        ];
+        for grant in grants {
+            let mut message =
+                Message::<RecordsWriteDescriptor>::create(write_parameters(), Some(alice.clone()))
+                    .await
+                    .unwrap();
+            message
+                .sign_as_owner_delegate(dave.clone(), grant)
+                .await
+                .unwrap();
+
+            assert!(matches!(
+                message
+                    .fields
+                    .authorization
+                    .verify_owner(&message.descriptor, &keys)
+                    .await,
+                Err(JwsError::InvalidDelegatedGrant(_))
+            ));
+        }
+    }
 }
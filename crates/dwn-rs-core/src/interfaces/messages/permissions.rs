@@ -0,0 +1,522 @@
+//! Permission grants, requests and revocations.
+//!
+//! Permissions are records of the DWN permissions protocol. A [`PermissionRequest`] asks the
+//! owner of a DWN for access, a [`PermissionGrant`] gives the grantee access within its
+//! [`Scope`], and a [`PermissionRevocation`] (written as a child of the grant) revokes it. Each
+//! is created with a builder on [`WriteParameters`], and parsed from the RecordsWrite it was
+//! written as.
+use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD as base64url};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+use crate::descriptors::{
+    records::{WriteDescriptor, WriteParameters},
+    CONFIGURE, DELETE, MESSAGES, PROTOCOLS, QUERY, READ, RECORDS, SUBSCRIBE, WRITE,
+};
+use crate::errors::PermissionsError;
+use crate::{MapValue, Message, Value};
+
+/// PERMISSIONS_PROTOCOL is the protocol of permission requests, grants and revocations.
+pub const PERMISSIONS_PROTOCOL: &str = "https://tbd.website/dwn/permissions";
+
+pub const REQUEST_PATH: &str = "request";
+pub const GRANT_PATH: &str = "grant";
+pub const REVOCATION_PATH: &str = "grant/revocation";
+
+const DATA_FORMAT: &str = "application/json";
+
+/// Scope is the interface method (and for records, the protocol) a permission applies to.
+/// Records scopes may be narrowed to a context or protocol path of the protocol.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
+pub struct Scope {
+    pub interface: String,
+    pub method: String,
+    pub protocol: Option<String>,
+    #[serde(rename = "contextId")]
+    pub context_id: Option<String>,
+    #[serde(rename = "protocolPath")]
+    pub protocol_path: Option<String>,
+}
+
+impl Scope {
+    pub fn validate(&self) -> Result<(), PermissionsError> {
+        let methods: &[&str] = match self.interface.as_str() {
+            RECORDS => &[READ, WRITE, QUERY, SUBSCRIBE, DELETE],
+            PROTOCOLS => &[CONFIGURE, QUERY],
+            MESSAGES => &[READ, QUERY, SUBSCRIBE],
+            interface => {
+                return Err(PermissionsError::InvalidScope(format!(
+                    "unknown interface {}",
+                    interface
+                )))
+            }
+        };
+        if !methods.contains(&self.method.as_str()) {
+            return Err(PermissionsError::InvalidScope(format!(
+                "unknown method {}{}",
+                self.interface, self.method
+            )));
+        }
+
+        match (
+            self.interface.as_str(),
+            &self.context_id,
+            &self.protocol_path,
+        ) {
+            (RECORDS, _, _) if self.protocol.is_none() => Err(PermissionsError::InvalidScope(
+                "records scopes must have a protocol".to_string(),
+            )),
+            (RECORDS, Some(_), Some(_)) => Err(PermissionsError::InvalidScope(
+                "records scopes may not have both a contextId and a protocolPath".to_string(),
+            )),
+            (RECORDS, _, _) | (_, None, None) => Ok(()),
+            (interface, _, _) => Err(PermissionsError::InvalidScope(format!(
+                "{} scopes may not have a contextId or protocolPath",
+                interface
+            ))),
+        }
+    }
+}
+
+/// Conditions are the conditions a permission may only be used under.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
+pub struct Conditions {
+    pub publication: Option<PublicationCondition>,
+}
+
+/// PublicationCondition requires (or prohibits) records written with a grant to be published.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum PublicationCondition {
+    Required,
+    Prohibited,
+}
+
+/// PermissionGrantData is the data of a permission grant record.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PermissionGrantData {
+    #[serde(
+        rename = "dateExpires",
+        serialize_with = "crate::ser::serialize_datetime"
+    )]
+    pub date_expires: DateTime<Utc>,
+    #[serde(rename = "requestId")]
+    pub request_id: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub delegated: bool,
+    pub scope: Scope,
+    pub conditions: Option<Conditions>,
+}
+
+/// PermissionRequestData is the data of a permission request record.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PermissionRequestData {
+    pub description: Option<String>,
+    #[serde(default)]
+    pub delegated: bool,
+    pub scope: Scope,
+    pub conditions: Option<Conditions>,
+}
+
+/// PermissionRevocationData is the data of a permission revocation record.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
+pub struct PermissionRevocationData {
+    pub description: Option<String>,
+}
+
+/// PermissionGrant grants the grantee access to the DWN of the grantor. Delegated
+/// grants allow the grantee to sign messages on behalf of the grantor.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PermissionGrant {
+    pub id: String,
+    pub grantor: String,
+    pub grantee: String,
+    pub date_granted: DateTime<Utc>,
+    pub date_expires: DateTime<Utc>,
+    pub request_id: Option<String>,
+    pub description: Option<String>,
+    pub delegated: bool,
+    pub scope: Scope,
+    pub conditions: Option<Conditions>,
+}
+
+impl PermissionGrant {
+    /// parse parses a permission grant from a RecordsWrite, with its data encoded.
+    pub fn parse(message: &Message<WriteDescriptor>) -> Result<Self, PermissionsError> {
+        Self::from_data(message, &encoded_data(message)?)
+    }
+
+    /// from_data parses a permission grant from a RecordsWrite and its data.
+    pub fn from_data(
+        message: &Message<WriteDescriptor>,
+        data: &[u8],
+    ) -> Result<Self, PermissionsError> {
+        let grant: PermissionGrantData = parse_data(message, GRANT_PATH, data)?;
+
+        Ok(Self {
+            id: record_id(message)?,
+            grantor: author(message)?,
+            grantee: message.descriptor.recipient.clone().ok_or_else(|| {
+                PermissionsError::InvalidMessage("grants must have a recipient".to_string())
+            })?,
+            date_granted: message.descriptor.date_created,
+            date_expires: grant.date_expires,
+            request_id: grant.request_id,
+            description: grant.description,
+            delegated: grant.delegated,
+            scope: grant.scope,
+            conditions: grant.conditions,
+        })
+    }
+}
+
+/// PermissionRequest is a request by the requester for a permission grant.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PermissionRequest {
+    pub id: String,
+    pub requester: String,
+    pub description: Option<String>,
+    pub delegated: bool,
+    pub scope: Scope,
+    pub conditions: Option<Conditions>,
+}
+
+impl PermissionRequest {
+    /// parse parses a permission request from a RecordsWrite, with its data encoded.
+    pub fn parse(message: &Message<WriteDescriptor>) -> Result<Self, PermissionsError> {
+        Self::from_data(message, &encoded_data(message)?)
+    }
+
+    /// from_data parses a permission request from a RecordsWrite and its data.
+    pub fn from_data(
+        message: &Message<WriteDescriptor>,
+        data: &[u8],
+    ) -> Result<Self, PermissionsError> {
+        let request: PermissionRequestData = parse_data(message, REQUEST_PATH, data)?;
+
+        Ok(Self {
+            id: record_id(message)?,
+            requester: author(message)?,
+            description: request.description,
+            delegated: request.delegated,
+            scope: request.scope,
+            conditions: request.conditions,
+        })
+    }
+}
+
+/// PermissionRevocation revokes the permission grant it is written under.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PermissionRevocation {
+    pub id: String,
+    pub grant_id: String,
+    pub revoker: String,
+    pub date_revoked: DateTime<Utc>,
+    pub description: Option<String>,
+}
+
+impl PermissionRevocation {
+    /// parse parses a permission revocation from a RecordsWrite, with its data encoded.
+    pub fn parse(message: &Message<WriteDescriptor>) -> Result<Self, PermissionsError> {
+        Self::from_data(message, &encoded_data(message)?)
+    }
+
+    /// from_data parses a permission revocation from a RecordsWrite and its data.
+    pub fn from_data(
+        message: &Message<WriteDescriptor>,
+        data: &[u8],
+    ) -> Result<Self, PermissionsError> {
+        let revocation: PermissionRevocationData = parse_data(message, REVOCATION_PATH, data)?;
+
+        Ok(Self {
+            id: record_id(message)?,
+            grant_id: message.descriptor.parent_id.clone().ok_or_else(|| {
+                PermissionsError::InvalidMessage("revocations must have a parent grant".to_string())
+            })?,
+            revoker: author(message)?,
+            date_revoked: message.descriptor.date_created,
+            description: revocation.description,
+        })
+    }
+}
+
+impl WriteParameters {
+    /// permission_grant returns the parameters of a RecordsWrite granting the grantee the
+    /// permission.
+    pub fn permission_grant(
+        grantee: &str,
+        grant: &PermissionGrantData,
+    ) -> Result<Self, PermissionsError> {
+        grant.scope.validate()?;
+        if let (Some(_), false) = (&grant.conditions, is_records_write(&grant.scope)) {
+            return Err(PermissionsError::InvalidScope(
+                "conditions may only be set on RecordsWrite grants".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            recipient: Some(grantee.to_string()),
+            ..permission_parameters(GRANT_PATH, &grant.scope.protocol, grant)?
+        })
+    }
+
+    /// permission_request returns the parameters of a RecordsWrite requesting the permission.
+    pub fn permission_request(request: &PermissionRequestData) -> Result<Self, PermissionsError> {
+        request.scope.validate()?;
+
+        permission_parameters(REQUEST_PATH, &request.scope.protocol, request)
+    }
+
+    /// permission_revocation returns the parameters of a RecordsWrite revoking the grant.
+    pub fn permission_revocation(
+        grant: &PermissionGrant,
+        revocation: &PermissionRevocationData,
+    ) -> Result<Self, PermissionsError> {
+        Ok(Self {
+            parent_context_id: Some(grant.id.clone()),
+            ..permission_parameters(REVOCATION_PATH, &grant.scope.protocol, revocation)?
+        })
+    }
+}
+
+/// permission_parameters returns the parameters of a RecordsWrite of the permissions protocol.
+/// Permissions for a protocol are tagged with it, so they can be queried by protocol.
+fn permission_parameters<T: Serialize>(
+    protocol_path: &str,
+    protocol: &Option<String>,
+    data: &T,
+) -> Result<WriteParameters, PermissionsError> {
+    let data = serde_json::to_vec(data)?;
+
+    Ok(WriteParameters {
+        protocol: Some(PERMISSIONS_PROTOCOL.to_string()),
+        protocol_path: Some(protocol_path.to_string()),
+        tags: protocol.as_ref().map(|protocol| {
+            MapValue::from([("protocol".to_string(), Value::String(protocol.clone()))])
+        }),
+        data_size: Some(data.len() as u64),
+        data: Some(data),
+        data_format: DATA_FORMAT.to_string(),
+        ..Default::default()
+    })
+}
+
+fn is_records_write(scope: &Scope) -> bool {
+    scope.interface == RECORDS && scope.method == WRITE
+}
+
+fn parse_data<T: DeserializeOwned>(
+    message: &Message<WriteDescriptor>,
+    protocol_path: &str,
+    data: &[u8],
+) -> Result<T, PermissionsError> {
+    let descriptor = &message.descriptor;
+    if descriptor.protocol.as_deref() != Some(PERMISSIONS_PROTOCOL)
+        || descriptor.protocol_path.as_deref() != Some(protocol_path)
+    {
+        return Err(PermissionsError::InvalidMessage(format!(
+            "expected a record of the permissions protocol at {}",
+            protocol_path
+        )));
+    }
+
+    Ok(serde_json::from_slice(data)?)
+}
+
+fn encoded_data(message: &Message<WriteDescriptor>) -> Result<Vec<u8>, PermissionsError> {
+    let encoded = message.fields.encoded_data.as_ref().ok_or_else(|| {
+        PermissionsError::InvalidMessage("the record data is not encoded".to_string())
+    })?;
+
+    Ok(base64url.decode(encoded)?)
+}
+
+fn record_id(message: &Message<WriteDescriptor>) -> Result<String, PermissionsError> {
+    message
+        .fields
+        .record_id
+        .clone()
+        .ok_or_else(|| PermissionsError::InvalidMessage("missing recordId".to_string()))
+}
+
+fn author(message: &Message<WriteDescriptor>) -> Result<String, PermissionsError> {
+    message
+        .fields
+        .authorization
+        .author()?
+        .ok_or_else(|| PermissionsError::InvalidMessage("the record is not signed".to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Persona;
+
+    fn records_scope() -> Scope {
+        Scope {
+            interface: RECORDS.to_string(),
+            method: WRITE.to_string(),
+            protocol: Some("https://example.com/chat".to_string()),
+            ..Default::default()
+        }
+    }
+
+    async fn create(parameters: WriteParameters, did: &str) -> Message<WriteDescriptor> {
+        let data = parameters.data.clone().unwrap();
+        let mut message = Message::<WriteDescriptor>::create(
+            parameters,
+            Some(Persona::with_did(did).signer().unwrap()),
+        )
+        .await
+        .unwrap();
+        message.fields.encoded_data = Some(base64url.encode(data));
+
+        message
+    }
+
+    #[test]
+    fn test_scope_validate() {
+        assert!(records_scope().validate().is_ok());
+        assert!(Scope {
+            interface: MESSAGES.to_string(),
+            method: READ.to_string(),
+            ..Default::default()
+        }
+        .validate()
+        .is_ok());
+
+        let invalid = vec![
+            Scope {
+                protocol: None,
+                ..records_scope()
+            },
+            Scope {
+                context_id: Some("context".to_string()),
+                protocol_path: Some("thread".to_string()),
+                ..records_scope()
+            },
+            Scope {
+                method: CONFIGURE.to_string(),
+                ..records_scope()
+            },
+            Scope {
+                interface: PROTOCOLS.to_string(),
+                method: QUERY.to_string(),
+                protocol_path: Some("thread".to_string()),
+                ..Default::default()
+            },
+            Scope {
+                interface: "Events".to_string(),
+                method: QUERY.to_string(),
+                ..Default::default()
+            },
+        ];
+        for scope in invalid {
+            assert!(scope.validate().is_err(), "{:?}", scope);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_permission_grant() {
+        let data = PermissionGrantData {
+            date_expires: Utc::now() + chrono::Duration::days(1),
+            request_id: None,
+            description: Some("chat with bob".to_string()),
+            delegated: true,
+            scope: records_scope(),
+            conditions: Some(Conditions {
+                publication: Some(PublicationCondition::Prohibited),
+            }),
+        };
+
+        let parameters = WriteParameters::permission_grant("did:example:bob", &data).unwrap();
+        assert_eq!(
+            parameters.tags,
+            Some(MapValue::from([(
+                "protocol".to_string(),
+                Value::String("https://example.com/chat".to_string())
+            )]))
+        );
+
+        let message = create(parameters, "did:example:alice").await;
+        assert_eq!(
+            message.descriptor.protocol_path,
+            Some(GRANT_PATH.to_string())
+        );
+
+        let grant = PermissionGrant::parse(&message).unwrap();
+        assert_eq!(grant.grantor, "did:example:alice");
+        assert_eq!(grant.grantee, "did:example:bob");
+        assert_eq!(grant.date_granted, message.descriptor.date_created);
+        assert_eq!(grant.date_expires, data.date_expires);
+        assert!(grant.delegated);
+        assert_eq!(grant.scope, data.scope);
+        assert_eq!(grant.conditions, data.conditions);
+
+        // a grant is not a request.
+        assert!(PermissionRequest::parse(&message).is_err());
+
+        let messages_scope = PermissionGrantData {
+            scope: Scope {
+                interface: MESSAGES.to_string(),
+                method: QUERY.to_string(),
+                ..Default::default()
+            },
+            ..data
+        };
+        assert!(WriteParameters::permission_grant("did:example:bob", &messages_scope).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_permission_request() {
+        let data = PermissionRequestData {
+            description: None,
+            delegated: false,
+            scope: records_scope(),
+            conditions: None,
+        };
+
+        let message = create(
+            WriteParameters::permission_request(&data).unwrap(),
+            "did:example:bob",
+        )
+        .await;
+
+        let request = PermissionRequest::parse(&message).unwrap();
+        assert_eq!(request.requester, "did:example:bob");
+        assert_eq!(request.scope, data.scope);
+        assert!(!request.delegated);
+    }
+
+    #[tokio::test]
+    async fn test_permission_revocation() {
+        let grant = PermissionGrant {
+            id: "bafyreigrant".to_string(),
+            grantor: "did:example:alice".to_string(),
+            grantee: "did:example:bob".to_string(),
+            date_granted: Utc::now(),
+            date_expires: Utc::now() + chrono::Duration::days(1),
+            request_id: None,
+            description: None,
+            delegated: false,
+            scope: records_scope(),
+            conditions: None,
+        };
+
+        let parameters =
+            WriteParameters::permission_revocation(&grant, &PermissionRevocationData::default())
+                .unwrap();
+        let message = create(parameters, "did:example:alice").await;
+
+        let revocation = PermissionRevocation::parse(&message).unwrap();
+        assert_eq!(revocation.grant_id, grant.id);
+        assert_eq!(revocation.revoker, "did:example:alice");
+        assert_eq!(revocation.description, None);
+    }
+}
@@ -1,9 +1,11 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 use ssi_jwk::JWK;
 
+use crate::{descriptors::ValidationError, normalize_url};
+
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Type {
@@ -21,6 +23,169 @@ pub struct Definition {
     pub structure: BTreeMap<String, RuleSet>,
 }
 
+impl Definition {
+    /// validate checks the structure of the protocol definition. The protocol and type schemas
+    /// must be normalized URLs, each type must have valid data formats, and every rule set in
+    /// the structure must be for a declared type, with actions and constraints which refer to
+    /// rule sets that exist.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_normalized("protocol", &self.protocol)?;
+
+        for (name, protocol_type) in &self.types {
+            if let Some(schema) = &protocol_type.schema {
+                validate_normalized(&format!("schema of type {}", name), schema)?;
+            }
+
+            for data_format in protocol_type.data_formats.iter().flatten() {
+                if !is_mime_type(data_format) {
+                    return Err(invalid(format!(
+                        "data format {} of type {} is not a valid MIME type",
+                        data_format, name
+                    )));
+                }
+            }
+        }
+
+        let mut rule_sets = BTreeMap::new();
+        collect_rule_sets(&self.structure, None, &mut rule_sets);
+
+        for (path, rule_set) in &rule_sets {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            if !self.types.contains_key(name) {
+                return Err(invalid(format!(
+                    "rule set {} is not for a type declared by the protocol",
+                    path
+                )));
+            }
+
+            rule_set.validate(path, &rule_sets)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl RuleSet {
+    fn validate(
+        &self,
+        path: &str,
+        rule_sets: &BTreeMap<String, &RuleSet>,
+    ) -> Result<(), ValidationError> {
+        if let Some(Size {
+            min: Some(min),
+            max: Some(max),
+        }) = self.size
+        {
+            if min > max {
+                return Err(invalid(format!(
+                    "$size of {} has a min greater than its max",
+                    path
+                )));
+            }
+        }
+
+        if let Some(tags) = &self.tags {
+            let undeclared = tags
+                .required_tags
+                .iter()
+                .filter(|tag| !tags.tags.contains_key(*tag))
+                .collect::<BTreeSet<_>>();
+            if !undeclared.is_empty() {
+                return Err(invalid(format!(
+                    "$requiredTags of {} are not declared: {:?}",
+                    path, undeclared
+                )));
+            }
+        }
+
+        for action in &self.actions {
+            match action {
+                Action::Who(ActionWho { who, of, .. }) => match (who, of) {
+                    (Who::Author, None) => {
+                        return Err(invalid(format!(
+                            "an author action of {} must name the record it is the author `of`",
+                            path
+                        )))
+                    }
+                    (_, Some(of)) if !rule_sets.contains_key(of) => {
+                        return Err(invalid(format!(
+                            "action of {} refers to {}, which is not a rule set",
+                            path, of
+                        )))
+                    }
+                    _ => {}
+                },
+                Action::Role(ActionRole { role, .. }) => match rule_sets.get(role) {
+                    Some(rule_set) if rule_set.role == Some(true) => {}
+                    Some(_) => {
+                        return Err(invalid(format!(
+                            "action of {} refers to {}, which is not a $role",
+                            path, role
+                        )))
+                    }
+                    None => {
+                        return Err(invalid(format!(
+                            "action of {} refers to role {}, which is not a rule set",
+                            path, role
+                        )))
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// collect_rule_sets collects each rule set of the structure by its protocol path.
+fn collect_rule_sets<'a>(
+    rules: &'a BTreeMap<String, RuleSet>,
+    parent: Option<&str>,
+    rule_sets: &mut BTreeMap<String, &'a RuleSet>,
+) {
+    for (name, rule_set) in rules {
+        let path = match parent {
+            Some(parent) => format!("{}/{}", parent, name),
+            None => name.clone(),
+        };
+
+        collect_rule_sets(&rule_set.rules, Some(&path), rule_sets);
+        rule_sets.insert(path, rule_set);
+    }
+}
+
+fn validate_normalized(name: &str, url: &str) -> Result<(), ValidationError> {
+    match normalize_url(url) {
+        Ok(normalized) if normalized == url => Ok(()),
+        Ok(normalized) => Err(invalid(format!(
+            "{} {} must be normalized as {}",
+            name, url, normalized
+        ))),
+        Err(err) => Err(invalid(format!("{} {} is invalid: {}", name, url, err))),
+    }
+}
+
+/// is_mime_type returns true if the value is a `type/subtype` MIME type, optionally with
+/// parameters.
+fn is_mime_type(value: &str) -> bool {
+    let is_token = |token: &str| {
+        !token.is_empty()
+            && token
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+    };
+
+    let essence = value.split(';').next().unwrap_or_default().trim();
+    match essence.split_once('/') {
+        Some((media_type, subtype)) => is_token(media_type) && is_token(subtype),
+        None => false,
+    }
+}
+
+fn invalid(message: String) -> ValidationError {
+    ValidationError { message }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub enum Who {
     #[serde(rename = "anyone")]
@@ -219,3 +384,129 @@ pub struct TagContains {
     #[serde(rename = "maxLength")]
     pub max_length: Option<usize>,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn definition() -> Definition {
+        serde_json::from_value(serde_json::json!({
+            "protocol": "https://example.com/chat",
+            "published": true,
+            "types": {
+                "thread": { "schema": "https://example.com/thread", "dataFormats": ["application/json"] },
+                "participant": {},
+                "chat": { "dataFormats": ["text/plain; charset=utf-8"] },
+            },
+            "structure": {
+                "thread": {
+                    "$actions": [{ "who": "anyone", "can": ["create"] }],
+                    "participant": {
+                        "$role": true,
+                        "$actions": [{ "who": "author", "of": "thread", "can": ["create"] }],
+                    },
+                    "chat": {
+                        "$size": { "min": 1, "max": 1000 },
+                        "$actions": [
+                            { "role": "thread/participant", "can": ["create", "read"] },
+                            { "who": "recipient", "of": "thread", "can": ["read"] },
+                        ],
+                    },
+                },
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validate_definition() {
+        assert!(definition().validate().is_ok());
+
+        let mut definition = definition();
+        definition.protocol = "https://example.com/chat?query".to_string();
+        assert!(definition.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_invalid_definitions() {
+        let tcs: Vec<(&str, fn(&mut Definition))> = vec![
+            ("undeclared type", |definition| {
+                definition.types.remove("chat");
+            }),
+            ("invalid data format", |definition| {
+                definition.types.get_mut("chat").unwrap().data_formats =
+                    Some(vec!["text".to_string()]);
+            }),
+            ("unnormalized schema", |definition| {
+                definition.types.get_mut("thread").unwrap().schema =
+                    Some("https://example.com/thread#fragment".to_string());
+            }),
+            ("size min greater than max", |definition| {
+                chat(definition).size = Some(Size {
+                    min: Some(10),
+                    max: Some(1),
+                });
+            }),
+            ("undeclared required tag", |definition| {
+                chat(definition).tags = Some(Tags {
+                    required_tags: vec!["status".to_string()],
+                    allow_undefined_tags: None,
+                    tags: BTreeMap::new(),
+                });
+            }),
+            ("unknown of", |definition| {
+                chat(definition).actions.push(Action::Who(ActionWho {
+                    who: Who::Recipient,
+                    of: Some("thread/unknown".to_string()),
+                    can: vec![Can::Read],
+                }));
+            }),
+            ("author without of", |definition| {
+                chat(definition).actions.push(Action::Who(ActionWho {
+                    who: Who::Author,
+                    of: None,
+                    can: vec![Can::Read],
+                }));
+            }),
+            ("role which is not a $role", |definition| {
+                chat(definition).actions.push(Action::Role(ActionRole {
+                    role: "thread".to_string(),
+                    can: vec![Can::Read],
+                }));
+            }),
+            ("unknown role", |definition| {
+                chat(definition).actions.push(Action::Role(ActionRole {
+                    role: "thread/moderator".to_string(),
+                    can: vec![Can::Read],
+                }));
+            }),
+        ];
+
+        for (name, modify) in tcs {
+            let mut definition = definition();
+            modify(&mut definition);
+            assert!(definition.validate().is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_is_mime_type() {
+        assert!(is_mime_type("application/json"));
+        assert!(is_mime_type("application/vnd.api+json"));
+        assert!(is_mime_type("text/plain; charset=utf-8"));
+        assert!(!is_mime_type("json"));
+        assert!(!is_mime_type("application/"));
+        assert!(!is_mime_type("/json"));
+        assert!(!is_mime_type("text/plain/extra"));
+    }
+
+    fn chat(definition: &mut Definition) -> &mut RuleSet {
+        definition
+            .structure
+            .get_mut("thread")
+            .unwrap()
+            .rules
+            .get_mut("chat")
+            .unwrap()
+    }
+}
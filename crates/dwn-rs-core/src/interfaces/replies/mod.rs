@@ -13,6 +13,23 @@ pub struct Status {
     pub detail: String,
 }
 
+impl Status {
+    pub fn new(code: i32, detail: &str) -> Self {
+        Self {
+            code,
+            detail: detail.to_string(),
+        }
+    }
+
+    pub fn ok() -> Self {
+        Self::new(200, "OK")
+    }
+
+    pub fn accepted() -> Self {
+        Self::new(202, "Accepted")
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Response {
     pub status: Status,
@@ -20,6 +37,12 @@ pub struct Response {
     pub reply: Reply,
 }
 
+impl Response {
+    pub fn new(status: Status, reply: Reply) -> Self {
+        Self { status, reply }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Empty {}
 
@@ -6,5 +6,5 @@ use crate::{descriptors::protocols, Message};
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Query {
-    pub entries: Option<Message<protocols::ConfigureDescriptor>>,
+    pub entries: Option<Vec<Message<protocols::ConfigureDescriptor>>>,
 }
@@ -14,6 +14,7 @@
 //! - [`messages::Fields`]: Additional fields that can be included in a message.
 //! - [`value::Value`]: A generic value type that can be used in messages.
 //! - [`value::MapValue`]: A map of values that can be used in messages.`
+//! - [`Dwn`]: A Decentralized Web Node, which processes messages against the DWN stores.
 //!
 //! Additionally, there are strongly typed values for common DWN messages, including:
 //! - [`messages::records::RecordsRead`]: A message for reading records.
@@ -23,15 +24,19 @@
 //! - [`messages::records::RecordsDelete`]: A descriptor for reading records.
 #![doc(issue_tracker_base_url = "https://github.com/enmand/dwn-rsissues/")]
 pub mod auth;
+pub mod dids;
+pub mod dwn;
 pub mod encryption;
 pub mod errors;
 pub mod events;
 pub mod filters;
+mod handlers;
 pub mod interfaces;
 mod ser;
 pub mod stores;
 pub mod value;
 
+pub use dwn::*;
 pub use events::*;
 pub use filters::*;
 pub use interfaces::*;
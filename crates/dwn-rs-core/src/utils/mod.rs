@@ -4,10 +4,16 @@ use k256::{PublicKey, SecretKey};
 use partially::Partial;
 use rand::{distributions::Alphanumeric, Rng};
 use ssi_dids_core::DIDBuf;
+use ssi_jwk::JWK;
 use std::str::FromStr;
 use thiserror::Error;
 use url::Url;
 
+use crate::{
+    dids::{key::did_key, DidResolutionError},
+    encryption::asymmetric::{self, secp256k1, SecretKeyTrait},
+};
+
 #[derive(Error, Debug)]
 pub enum URLError {
     #[error("Invalid URL: {0}")]
@@ -37,6 +43,10 @@ pub fn normalize_url(url: &str) -> Result<String, URLError> {
 pub enum PersonaError {
     #[error("DID error: {0}")]
     DIDError(#[from] ssi_dids_core::InvalidDID<String>),
+    #[error("DID key error: {0}")]
+    DIDKeyError(#[from] DidResolutionError),
+    #[error("Key error: {0}")]
+    KeyError(#[from] asymmetric::Error),
 }
 
 #[derive(Partial, Debug)]
@@ -79,9 +89,54 @@ impl Persona {
         })
     }
 
+    /// generate_did_key generates a persona with a new secp256k1 key, identified by the
+    /// `did:key` DID of the key. Unlike the `did:example:` DIDs of [`Persona::generate`], the
+    /// DID can be resolved to the persona's public key.
+    pub fn generate_did_key() -> Result<Self, PersonaError> {
+        let secp = SecretKey::random(&mut rand::thread_rng());
+        let keypair = (secp.clone(), secp.public_key());
+
+        let did = did_key(&keypair.1.into())?;
+        let fragment = did.trim_start_matches("did:key:").to_string();
+        let key_id = format!("{}#{}", did, fragment);
+
+        Self::generate(PartialPersona {
+            did: Some(DIDBuf::from_str(&did)?),
+            key_id: Some(key_id),
+            keypair: Some(keypair),
+        })
+    }
+
     pub fn public_key(&self) -> k256::PublicKey {
         self.keypair.1
     }
+
+    /// jwk returns the public key of the persona as a JWK, with the persona's key ID.
+    pub fn jwk(&self) -> JWK {
+        let mut jwk: JWK = self.keypair.1.into();
+        jwk.key_id = Some(self.key_id.clone());
+        jwk
+    }
+
+    /// signer returns the private key of the persona as a JWK, with the persona's key ID, for
+    /// signing messages as the persona.
+    pub fn signer(&self) -> Result<JWK, PersonaError> {
+        let mut jwk = secp256k1::SecretKey::from_bytes(&self.keypair.0.to_bytes())?.jwk()?;
+        jwk.key_id = Some(self.key_id.clone());
+        Ok(jwk)
+    }
+}
+
+#[cfg(test)]
+impl Persona {
+    /// with_did generates a persona with the given DID, for tests which expect known DIDs.
+    pub(crate) fn with_did(did: &str) -> Self {
+        Self::generate(PartialPersona {
+            did: Some(DIDBuf::from_str(did).unwrap()),
+            ..Default::default()
+        })
+        .unwrap()
+    }
 }
 
 pub fn generate_random_string(len: usize) -> String {
@@ -91,3 +146,31 @@ pub fn generate_random_string(len: usize) -> String {
         .map(char::from)
         .collect()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dids::key;
+
+    #[test]
+    fn test_persona_generate_did_key() {
+        let persona = Persona::generate_did_key().unwrap();
+
+        let did = persona.did.to_string();
+        assert!(did.starts_with("did:key:zQ3s"));
+        assert!(persona.key_id.starts_with(&format!("{}#", persona.did)));
+
+        let document = key::resolve(&did).unwrap();
+        let method = document.verification_method(&persona.key_id).unwrap();
+        assert_eq!(method.jwk().unwrap().params, persona.jwk().params);
+    }
+
+    #[test]
+    fn test_persona_signer() {
+        let persona = Persona::with_did("did:example:alice");
+
+        let signer = persona.signer().unwrap();
+        assert_eq!(signer.key_id, Some(persona.key_id.clone()));
+        assert_eq!(signer.to_public().params, persona.jwk().params);
+    }
+}
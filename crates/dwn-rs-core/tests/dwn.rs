@@ -0,0 +1,689 @@
+//! End-to-end tests of processing RecordsWrite, RecordsRead, RecordsQuery and RecordsDelete
+//! messages with a [`Dwn`] backed by in-memory SurrealDB stores.
+
+use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD as base64url};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::{stream, StreamExt};
+use ssi_jwk::JWK;
+
+use dwn_rs_core::{
+    auth::{JwsError, KeyResolver},
+    descriptors::{
+        protocols::ConfigureParameters,
+        records::{DeleteParameters, QueryParameters, ReadParameters, WriteParameters},
+        ConfigureDescriptor, DeleteDescriptor, ReadDescriptor, RecordsQueryDescriptor,
+        RecordsWriteDescriptor,
+    },
+    emitter::EventStreamer,
+    filters::message_filters::Records as RecordsFilter,
+    protocols::{Definition, RuleSet, Type},
+    replies::records::Read,
+    Dwn, Message, MessageReply, Persona, Reply,
+};
+use dwn_rs_stores::SurrealDB;
+
+const SCHEMA: &str = "https://example.com/note";
+const PROTOCOL: &str = "https://example.com/chat";
+
+type Data = stream::Iter<std::vec::IntoIter<Bytes>>;
+type TestDwn = Dwn<SurrealDB, SurrealDB, SurrealDB, SurrealDB, Keys>;
+
+/// Keys resolves the key IDs of the personas in a test to their public keys.
+struct Keys(Vec<JWK>);
+
+impl KeyResolver for Keys {
+    async fn resolve_key(&self, kid: &str) -> Result<JWK, JwsError> {
+        self.0
+            .iter()
+            .find(|jwk| jwk.key_id.as_deref() == Some(kid))
+            .cloned()
+            .ok_or_else(|| JwsError::KeyResolutionError(kid.to_string(), "unknown".to_string()))
+    }
+}
+
+async fn store() -> SurrealDB {
+    let mut db = SurrealDB::new();
+    db.connect("mem://").await.unwrap();
+    db
+}
+
+/// dwn opens a DWN which resolves the keys of the given personas.
+async fn dwn(personas: &[&Persona]) -> TestDwn {
+    let keys = Keys(personas.iter().map(|persona| persona.jwk()).collect());
+    let mut dwn = Dwn::new(
+        store().await,
+        store().await,
+        store().await,
+        store().await,
+        EventStreamer::new(),
+        keys,
+    );
+    dwn.open().await.unwrap();
+
+    dwn
+}
+
+fn tenant(persona: &Persona) -> String {
+    persona.did.to_string()
+}
+
+/// at returns a fixed time, offset by the given number of seconds, so that the order of
+/// messages doesn't depend on how quickly they are created.
+fn at(seconds: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(1_700_000_000 + seconds, 0).unwrap()
+}
+
+fn data(data: &[u8]) -> Option<Data> {
+    Some(stream::iter(vec![Bytes::copy_from_slice(data)]))
+}
+
+fn initial_parameters(data: &[u8], timestamp: DateTime<Utc>) -> WriteParameters {
+    WriteParameters {
+        schema: Some(SCHEMA.to_string()),
+        data: Some(data.to_vec()),
+        data_size: Some(data.len() as u64),
+        data_format: "text/plain".to_string(),
+        date_created: Some(timestamp),
+        message_timestamp: Some(timestamp),
+        ..Default::default()
+    }
+}
+
+fn update_parameters(
+    initial: &Message<RecordsWriteDescriptor>,
+    data: &[u8],
+    timestamp: DateTime<Utc>,
+) -> WriteParameters {
+    WriteParameters {
+        record_id: initial.fields.record_id.clone(),
+        date_created: Some(initial.descriptor.date_created),
+        ..initial_parameters(data, timestamp)
+    }
+}
+
+async fn write(persona: &Persona, parameters: WriteParameters) -> Message<RecordsWriteDescriptor> {
+    Message::create(parameters, Some(persona.signer().unwrap()))
+        .await
+        .unwrap()
+}
+
+async fn process_write(
+    dwn: &TestDwn,
+    tenant: &str,
+    message: &Message<RecordsWriteDescriptor>,
+    data: Option<Data>,
+) -> i32 {
+    let reply = dwn
+        .process_message(tenant, message.clone().into_generic(), data)
+        .await;
+
+    reply.response.status.code
+}
+
+async fn read(dwn: &TestDwn, tenant: &str, persona: &Persona, record_id: &str) -> MessageReply {
+    let message = Message::<ReadDescriptor>::create(
+        ReadParameters {
+            filters: RecordsFilter {
+                record_id: Some(record_id.to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Some(persona.signer().unwrap()),
+    )
+    .await
+    .unwrap();
+
+    dwn.process_message(tenant, message.into_generic(), None::<Data>)
+        .await
+}
+
+async fn read_data(reply: MessageReply) -> Vec<u8> {
+    assert_eq!(reply.response.status.code, 200);
+
+    reply.data.unwrap().data.collect::<Vec<u8>>().await
+}
+
+async fn delete(
+    dwn: &TestDwn,
+    tenant: &str,
+    persona: &Persona,
+    record_id: &str,
+    prune: bool,
+    timestamp: DateTime<Utc>,
+) -> i32 {
+    let message = Message::<DeleteDescriptor>::create(
+        DeleteParameters {
+            record_id: record_id.to_string(),
+            message_timestamp: Some(timestamp),
+            prune: Some(prune),
+            ..Default::default()
+        },
+        Some(persona.signer().unwrap()),
+    )
+    .await
+    .unwrap();
+
+    let reply = dwn
+        .process_message(tenant, message.into_generic(), None::<Data>)
+        .await;
+
+    reply.response.status.code
+}
+
+/// configure_chat configures the chat protocol, of threads with nested replies, for the tenant.
+async fn configure_chat(dwn: &TestDwn, persona: &Persona) {
+    let reply = RuleSet {
+        rules: [("reply".to_string(), RuleSet::default())].into(),
+        ..Default::default()
+    };
+    let thread = RuleSet {
+        rules: [("reply".to_string(), reply)].into(),
+        ..Default::default()
+    };
+    let definition = Definition {
+        protocol: PROTOCOL.to_string(),
+        published: true,
+        types: ["thread", "reply"]
+            .into_iter()
+            .map(|name| {
+                (
+                    name.to_string(),
+                    Type {
+                        schema: None,
+                        data_formats: None,
+                    },
+                )
+            })
+            .collect(),
+        structure: [("thread".to_string(), thread)].into(),
+    };
+
+    let message = Message::<ConfigureDescriptor>::create(
+        ConfigureParameters {
+            message_timestamp: Some(at(0)),
+            definition,
+            ..Default::default()
+        },
+        Some(persona.signer().unwrap()),
+    )
+    .await
+    .unwrap();
+    let reply = dwn
+        .process_message(&tenant(persona), message.into_generic(), None::<Data>)
+        .await;
+    assert_eq!(reply.response.status.code, 202);
+}
+
+fn record_id(message: &Message<RecordsWriteDescriptor>) -> String {
+    message.fields.record_id.clone().unwrap()
+}
+
+#[tokio::test]
+async fn test_newest_write_wins() {
+    let alice = Persona::generate(Default::default()).unwrap();
+    let dwn = dwn(&[&alice]).await;
+    let tenant = tenant(&alice);
+
+    let initial = write(&alice, initial_parameters(b"first", at(0))).await;
+    assert_eq!(
+        process_write(&dwn, &tenant, &initial, data(b"first")).await,
+        202
+    );
+
+    let newest = write(&alice, update_parameters(&initial, b"newest", at(2))).await;
+    assert_eq!(
+        process_write(&dwn, &tenant, &newest, data(b"newest")).await,
+        202
+    );
+
+    // a write older than the latest write of the record is rejected.
+    let older = write(&alice, update_parameters(&initial, b"older", at(1))).await;
+    assert_eq!(
+        process_write(&dwn, &tenant, &older, data(b"older")).await,
+        409
+    );
+
+    let reply = read(&dwn, &tenant, &alice, &record_id(&initial)).await;
+    assert_eq!(read_data(reply).await, b"newest");
+}
+
+#[tokio::test]
+async fn test_equal_timestamps_larger_cid_wins() {
+    let alice = Persona::generate(Default::default()).unwrap();
+    let dwn = dwn(&[&alice]).await;
+    let tenant = tenant(&alice);
+
+    let initial = write(&alice, initial_parameters(b"first", at(0))).await;
+    assert_eq!(
+        process_write(&dwn, &tenant, &initial, data(b"first")).await,
+        202
+    );
+
+    let mut updates = Vec::new();
+    for data in [&b"one"[..], &b"two"[..]] {
+        let message = write(&alice, update_parameters(&initial, data, at(1))).await;
+        let cid = message
+            .clone()
+            .into_generic()
+            .stored_cid()
+            .unwrap()
+            .to_string();
+        updates.push((cid, message, data));
+    }
+    updates.sort_by(|(a, ..), (b, ..)| a.cmp(b));
+    let [(_, smaller, smaller_data), (_, larger, larger_data)] = &updates[..] else {
+        unreachable!();
+    };
+
+    assert_eq!(
+        process_write(&dwn, &tenant, larger, data(larger_data)).await,
+        202
+    );
+    assert_eq!(
+        process_write(&dwn, &tenant, smaller, data(smaller_data)).await,
+        409
+    );
+
+    let reply = read(&dwn, &tenant, &alice, &record_id(&initial)).await;
+    assert_eq!(read_data(reply).await, *larger_data);
+}
+
+#[tokio::test]
+async fn test_immutable_properties() {
+    let alice = Persona::generate(Default::default()).unwrap();
+    let dwn = dwn(&[&alice]).await;
+    let tenant = tenant(&alice);
+
+    let initial = write(&alice, initial_parameters(b"first", at(0))).await;
+    assert_eq!(
+        process_write(&dwn, &tenant, &initial, data(b"first")).await,
+        202
+    );
+
+    let changes = [
+        WriteParameters {
+            schema: Some("https://example.com/other".to_string()),
+            ..update_parameters(&initial, b"second", at(1))
+        },
+        WriteParameters {
+            recipient: Some(tenant.clone()),
+            ..update_parameters(&initial, b"second", at(1))
+        },
+        WriteParameters {
+            date_created: Some(at(1)),
+            ..update_parameters(&initial, b"second", at(2))
+        },
+    ];
+    for parameters in changes {
+        let update = write(&alice, parameters).await;
+        assert_eq!(
+            process_write(&dwn, &tenant, &update, data(b"second")).await,
+            400
+        );
+    }
+
+    let reply = read(&dwn, &tenant, &alice, &record_id(&initial)).await;
+    assert_eq!(read_data(reply).await, b"first");
+}
+
+#[tokio::test]
+async fn test_data_mismatch() {
+    let alice = Persona::generate(Default::default()).unwrap();
+    let dwn = dwn(&[&alice]).await;
+    let tenant = tenant(&alice);
+
+    let message = write(&alice, initial_parameters(b"hello", at(0))).await;
+
+    // data of the same size, which doesn't match the dataCid.
+    assert_eq!(
+        process_write(&dwn, &tenant, &message, data(b"jello")).await,
+        400
+    );
+    // data which doesn't match the dataSize.
+    assert_eq!(
+        process_write(&dwn, &tenant, &message, data(b"hello!")).await,
+        400
+    );
+    assert_eq!(
+        process_write(&dwn, &tenant, &message, data(b"hell")).await,
+        400
+    );
+
+    let reply = read(&dwn, &tenant, &alice, &record_id(&message)).await;
+    assert_eq!(reply.response.status.code, 404);
+
+    assert_eq!(
+        process_write(&dwn, &tenant, &message, data(b"hello")).await,
+        202
+    );
+}
+
+#[tokio::test]
+async fn test_write_without_data() {
+    let alice = Persona::generate(Default::default()).unwrap();
+    let dwn = dwn(&[&alice]).await;
+    let tenant = tenant(&alice);
+
+    // an initial write is stored without its data, but isn't readable until data is written.
+    let initial = write(&alice, initial_parameters(b"hello", at(0))).await;
+    assert_eq!(process_write(&dwn, &tenant, &initial, None).await, 202);
+    let reply = read(&dwn, &tenant, &alice, &record_id(&initial)).await;
+    assert_eq!(reply.response.status.code, 404);
+
+    // a later write can't reuse data which was never stored.
+    let update = write(&alice, update_parameters(&initial, b"hello", at(1))).await;
+    assert_eq!(process_write(&dwn, &tenant, &update, None).await, 400);
+    let reply = read(&dwn, &tenant, &alice, &record_id(&initial)).await;
+    assert_eq!(reply.response.status.code, 404);
+
+    assert_eq!(
+        process_write(&dwn, &tenant, &update, data(b"hello")).await,
+        202
+    );
+
+    // once the data is stored, later writes of the same data may omit it.
+    let published = write(
+        &alice,
+        WriteParameters {
+            published: Some(true),
+            ..update_parameters(&initial, b"hello", at(2))
+        },
+    )
+    .await;
+    assert_eq!(process_write(&dwn, &tenant, &published, None).await, 202);
+
+    let reply = read(&dwn, &tenant, &alice, &record_id(&initial)).await;
+    assert_eq!(read_data(reply).await, b"hello");
+}
+
+#[tokio::test]
+async fn test_write_after_delete() {
+    let alice = Persona::generate(Default::default()).unwrap();
+    let dwn = dwn(&[&alice]).await;
+    let tenant = tenant(&alice);
+
+    let initial = write(&alice, initial_parameters(b"first", at(0))).await;
+    assert_eq!(
+        process_write(&dwn, &tenant, &initial, data(b"first")).await,
+        202
+    );
+    assert_eq!(
+        delete(&dwn, &tenant, &alice, &record_id(&initial), false, at(1)).await,
+        202
+    );
+
+    let update = write(&alice, update_parameters(&initial, b"second", at(2))).await;
+    assert_eq!(
+        process_write(&dwn, &tenant, &update, data(b"second")).await,
+        400
+    );
+
+    // reading the deleted record returns its tombstone.
+    let reply = read(&dwn, &tenant, &alice, &record_id(&initial)).await;
+    assert_eq!(reply.response.status.code, 404);
+    let Reply::RecordsRead(Read { entry: Some(entry) }) = reply.response.reply else {
+        panic!("expected the RecordsDelete of the record");
+    };
+    assert!(entry.records_write.is_none());
+    assert_eq!(
+        entry.records_delete.unwrap().descriptor.record_id,
+        record_id(&initial)
+    );
+    assert_eq!(
+        entry.initial_write.and_then(|write| write.fields.record_id),
+        initial.fields.record_id
+    );
+}
+
+#[tokio::test]
+async fn test_read_not_found_and_unauthorized() {
+    let alice = Persona::generate(Default::default()).unwrap();
+    let bob = Persona::generate(Default::default()).unwrap();
+    let dwn = dwn(&[&alice, &bob]).await;
+    let tenant = tenant(&alice);
+
+    let reply = read(&dwn, &tenant, &alice, "unknown").await;
+    assert_eq!(reply.response.status.code, 404);
+
+    let private = write(&alice, initial_parameters(b"private", at(0))).await;
+    assert_eq!(
+        process_write(&dwn, &tenant, &private, data(b"private")).await,
+        202
+    );
+    let public = write(
+        &alice,
+        WriteParameters {
+            published: Some(true),
+            date_published: Some(at(0)),
+            ..initial_parameters(b"public", at(0))
+        },
+    )
+    .await;
+    assert_eq!(
+        process_write(&dwn, &tenant, &public, data(b"public")).await,
+        202
+    );
+
+    let reply = read(&dwn, &tenant, &bob, &record_id(&private)).await;
+    assert_eq!(reply.response.status.code, 401);
+    assert!(reply.data.is_none());
+
+    let reply = read(&dwn, &tenant, &bob, &record_id(&public)).await;
+    assert_eq!(read_data(reply).await, b"public");
+
+    let reply = read(&dwn, &tenant, &alice, &record_id(&private)).await;
+    assert_eq!(read_data(reply).await, b"private");
+}
+
+#[tokio::test]
+async fn test_prune_descendants() {
+    let alice = Persona::generate(Default::default()).unwrap();
+    let dwn = dwn(&[&alice]).await;
+    let tenant = tenant(&alice);
+    configure_chat(&dwn, &alice).await;
+
+    // threads, each with a reply, and a nested reply to the reply.
+    let mut threads = Vec::new();
+    for _ in 0..2 {
+        let thread = write(
+            &alice,
+            WriteParameters {
+                protocol: Some(PROTOCOL.to_string()),
+                protocol_path: Some("thread".to_string()),
+                ..initial_parameters(b"thread", at(0))
+            },
+        )
+        .await;
+        let reply = write(
+            &alice,
+            WriteParameters {
+                protocol: Some(PROTOCOL.to_string()),
+                protocol_path: Some("thread/reply".to_string()),
+                parent_context_id: thread.fields.context_id.clone(),
+                ..initial_parameters(b"reply", at(0))
+            },
+        )
+        .await;
+        let nested = write(
+            &alice,
+            WriteParameters {
+                protocol: Some(PROTOCOL.to_string()),
+                protocol_path: Some("thread/reply/reply".to_string()),
+                parent_context_id: reply.fields.context_id.clone(),
+                ..initial_parameters(b"nested", at(0))
+            },
+        )
+        .await;
+
+        for (message, contents) in [
+            (&thread, &b"thread"[..]),
+            (&reply, &b"reply"[..]),
+            (&nested, &b"nested"[..]),
+        ] {
+            assert_eq!(
+                process_write(&dwn, &tenant, message, data(contents)).await,
+                202
+            );
+        }
+        threads.push([thread, reply, nested]);
+    }
+
+    let [pruned, kept] = &threads[..] else {
+        unreachable!();
+    };
+    assert_eq!(
+        delete(&dwn, &tenant, &alice, &record_id(&pruned[0]), true, at(1)).await,
+        202
+    );
+    assert_eq!(
+        delete(&dwn, &tenant, &alice, &record_id(&kept[0]), false, at(1)).await,
+        202
+    );
+
+    // the descendants of a pruned record are removed entirely, without tombstones.
+    for descendant in &pruned[1..] {
+        let reply = read(&dwn, &tenant, &alice, &record_id(descendant)).await;
+        assert_eq!(reply.response.status.code, 404);
+        assert!(matches!(reply.response.reply, Reply::Empty(_)));
+    }
+
+    // deleting a record without pruning leaves its descendants.
+    for (descendant, contents) in kept[1..].iter().zip([&b"reply"[..], &b"nested"[..]]) {
+        let reply = read(&dwn, &tenant, &alice, &record_id(descendant)).await;
+        assert_eq!(read_data(reply).await, contents);
+    }
+
+    for thread in [&pruned[0], &kept[0]] {
+        let reply = read(&dwn, &tenant, &alice, &record_id(thread)).await;
+        assert_eq!(reply.response.status.code, 404);
+        assert!(matches!(reply.response.reply, Reply::RecordsRead(_)));
+    }
+}
+
+#[tokio::test]
+async fn test_protocol_structure() {
+    let alice = Persona::generate(Default::default()).unwrap();
+    let dwn = dwn(&[&alice]).await;
+    let tenant = tenant(&alice);
+
+    let chat = |protocol_path: &str, parent_context_id: Option<String>| WriteParameters {
+        protocol: Some(PROTOCOL.to_string()),
+        protocol_path: Some(protocol_path.to_string()),
+        parent_context_id,
+        ..initial_parameters(b"chat", at(1))
+    };
+
+    // records of a protocol which isn't configured are rejected, even from the tenant.
+    let thread = write(&alice, chat("thread", None)).await;
+    assert_eq!(
+        process_write(&dwn, &tenant, &thread, data(b"chat")).await,
+        400
+    );
+
+    configure_chat(&dwn, &alice).await;
+    assert_eq!(
+        process_write(&dwn, &tenant, &thread, data(b"chat")).await,
+        202
+    );
+
+    // the tenant's records must still follow the structure of the protocol.
+    for (protocol_path, parent_context_id) in [
+        ("reply", None),
+        ("thread/reply", None),
+        ("thread/unknown", thread.fields.context_id.clone()),
+        ("thread/reply/reply", thread.fields.context_id.clone()),
+    ] {
+        let message = write(&alice, chat(protocol_path, parent_context_id)).await;
+        assert_eq!(
+            process_write(&dwn, &tenant, &message, data(b"chat")).await,
+            400,
+            "{}",
+            protocol_path
+        );
+    }
+
+    let reply = write(
+        &alice,
+        chat("thread/reply", thread.fields.context_id.clone()),
+    )
+    .await;
+    assert_eq!(
+        process_write(&dwn, &tenant, &reply, data(b"chat")).await,
+        202
+    );
+}
+
+#[tokio::test]
+async fn test_delegated_grant_must_be_a_grant() {
+    let alice = Persona::generate(Default::default()).unwrap();
+    let mallory = Persona::generate(Default::default()).unwrap();
+    let dwn = dwn(&[&alice, &mallory]).await;
+    let tenant = tenant(&alice);
+
+    // mallory embeds a record alice wrote to them, which isn't a grant, as a delegated grant so
+    // that the write appears to be authored by alice.
+    let mut record = write(
+        &alice,
+        WriteParameters {
+            recipient: Some(mallory.did.to_string()),
+            ..initial_parameters(b"note", at(0))
+        },
+    )
+    .await;
+    record.fields.encoded_data = Some(base64url.encode(b"note"));
+
+    let forged = write(
+        &mallory,
+        WriteParameters {
+            delegated_grant: Some(record),
+            ..initial_parameters(b"forged", at(1))
+        },
+    )
+    .await;
+    assert_eq!(
+        process_write(&dwn, &tenant, &forged, data(b"forged")).await,
+        401
+    );
+
+    let reply = read(&dwn, &tenant, &alice, &record_id(&forged)).await;
+    assert_eq!(reply.response.status.code, 404);
+}
+
+#[tokio::test]
+async fn test_query_with_unknown_grant() {
+    let alice = Persona::generate(Default::default()).unwrap();
+    let bob = Persona::generate(Default::default()).unwrap();
+    let dwn = dwn(&[&alice, &bob]).await;
+    let tenant = tenant(&alice);
+
+    let query = |permission_grant_id: Option<&str>| {
+        Message::<RecordsQueryDescriptor>::create(
+            QueryParameters {
+                permission_grant_id: permission_grant_id.map(str::to_string),
+                ..Default::default()
+            },
+            Some(bob.signer().unwrap()),
+        )
+    };
+
+    let reply = dwn
+        .process_message(
+            &tenant,
+            query(None).await.unwrap().into_generic(),
+            None::<Data>,
+        )
+        .await;
+    assert_eq!(reply.response.status.code, 200);
+
+    // a query naming a grant alice never wrote is rejected, rather than treated as ungranted.
+    let reply = dwn
+        .process_message(
+            &tenant,
+            query(Some("bafyreibogus")).await.unwrap().into_generic(),
+            None::<Data>,
+        )
+        .await;
+    assert_eq!(reply.response.status.code, 401);
+}
@@ -78,6 +78,25 @@ pub(crate) fn impl_descriptor_macro_attr(attrs: DescriptorAttr, input: TokenStre
 
     item_ser.ident = item_ser_ident.clone();
 
+    // descriptors with a `message_timestamp` field return it as their timestamp.
+    let message_timestamp = match items.fields {
+        Fields::Named(ref fields)
+            if fields.named.iter().any(|field| {
+                field
+                    .ident
+                    .as_ref()
+                    .is_some_and(|i| i == "message_timestamp")
+            }) =>
+        {
+            quote! {
+                fn message_timestamp(&self) -> Option<&chrono::DateTime<chrono::Utc>> {
+                    Some(&self.message_timestamp)
+                }
+            }
+        }
+        _ => quote! {},
+    };
+
     let mut into_idents: TokenStream = quote! {};
     let mut from_idents: TokenStream = quote! {};
 
@@ -120,7 +139,7 @@ pub(crate) fn impl_descriptor_macro_attr(attrs: DescriptorAttr, input: TokenStre
     let output = quote_spanned! { ast.span() =>
         #[serde_with::skip_serializing_none]
         #[derive(serde::Serialize, serde::Deserialize, Default, Debug, PartialEq, Clone)]
-        #[serde(into = #intofrom, from = #intofrom)]
+        #[serde(into = #intofrom, try_from = #intofrom)]
         #items
 
         #[derive(serde::Deserialize, serde::Serialize, Clone)]
@@ -136,11 +155,22 @@ pub(crate) fn impl_descriptor_macro_attr(attrs: DescriptorAttr, input: TokenStre
             }
         }
 
-        impl From<#item_ser_ident> for #ident {
-            fn from(internal: #item_ser_ident) -> Self {
-                #ident {
-                    #from_idents
+        // Deserialization checks the interface and method, so that descriptors are only
+        // matched to the concrete type they were created as (e.g. in `untagged` enums).
+        impl TryFrom<#item_ser_ident> for #ident {
+            type Error = String;
+
+            fn try_from(internal: #item_ser_ident) -> Result<Self, Self::Error> {
+                if internal.interface != #interface || internal.method != #method {
+                    return Err(format!(
+                        "expected {}{} descriptor, found {}{}",
+                        #interface, #method, internal.interface, internal.method
+                    ));
                 }
+
+                Ok(#ident {
+                    #from_idents
+                })
             }
         }
 
@@ -156,6 +186,8 @@ pub(crate) fn impl_descriptor_macro_attr(attrs: DescriptorAttr, input: TokenStre
             fn method(&self) -> &'static str {
                 #method
             }
+
+            #message_timestamp
         }
 
         #[derive(serde::Deserialize)]
@@ -267,5 +299,26 @@ mod tests {
         assert!(output
             .to_string()
             .contains("impl MessageDescriptor for Example"));
+        assert!(!output.to_string().contains("fn message_timestamp"));
+    }
+
+    #[test]
+    fn test_impl_descriptor_macro_attr_with_message_timestamp() {
+        let input: TokenStream = quote! {
+            pub struct Example {
+                pub message_timestamp: chrono::DateTime<chrono::Utc>,
+            }
+        };
+
+        let attrs = DescriptorAttr {
+            interface: format_ident!("ExampleInterface"),
+            method: format_ident!("ExampleMethod"),
+            fields: parse_quote! { FieldsNamed },
+            parameters: Some(parse_quote! { FieldsNamed }),
+        };
+
+        let output = impl_descriptor_macro_attr(attrs, input);
+
+        assert!(output.to_string().contains("fn message_timestamp"));
     }
 }
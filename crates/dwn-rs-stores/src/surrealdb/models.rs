@@ -3,6 +3,7 @@ use std::str::FromStr;
 use cid::Cid;
 use dwn_rs_core::{
     filters::{MessageSort, MessageWatermark, NoSort},
+    indexes::{DATE_CREATED, DATE_PUBLISHED, MESSAGE_TIMESTAMP},
     value::{MapValue, Value},
 };
 use serde::{Deserialize, Serialize};
@@ -41,9 +42,9 @@ pub(crate) struct GetEncodedMessage {
 impl CursorValue<MessageSort> for GetEncodedMessage {
     fn cursor_value(&self, sort: MessageSort) -> Value {
         match sort {
-            MessageSort::DateCreated(_) => self.indexes.get("dateCreated").unwrap().clone(),
-            MessageSort::DatePublished(_) => self.indexes.get("datePublished").unwrap().clone(),
-            MessageSort::Timestamp(_) => self.indexes.get("messageTimestamp").unwrap().clone(),
+            MessageSort::DateCreated(_) => self.indexes.get(DATE_CREATED).unwrap().clone(),
+            MessageSort::DatePublished(_) => self.indexes.get(DATE_PUBLISHED).unwrap().clone(),
+            MessageSort::Timestamp(_) => self.indexes.get(MESSAGE_TIMESTAMP).unwrap().clone(),
         }
     }
 
@@ -2,7 +2,7 @@ use alloc::{boxed::Box, string::String};
 
 use async_std::channel::unbounded;
 use dwn_rs_core::{
-    emitter::EventStreamer, subscription::SubscriptionFn, Descriptor, MapValue,
+    emitter::EventStreamer, subscription::SubscriptionFn, Descriptor, Filters, MapValue,
     MessageEvent as CoreMessageEvent,
 };
 use js_sys::Promise;
@@ -12,7 +12,7 @@ use wasm_bindgen_futures::{spawn_local, JsFuture};
 
 use crate::{
     events::{EventSubscription, MessageEvent},
-    filter::IndexMap,
+    filter::{Filter, IndexMap},
 };
 
 #[wasm_bindgen]
@@ -57,18 +57,22 @@ impl EventStream {
         self.events.emit(tenant, evt.into(), indextags).await;
     }
 
+    /// subscribe subscribes the listener to the events emitted for the tenant. If filters are
+    /// given, the listener is only called for events whose indexes match them.
     #[wasm_bindgen]
     pub async fn subscribe(
         &self,
         tenant: &str,
         id: &str,
         listener: js_sys::Function,
+        filters: Option<Filter>,
     ) -> Result<EventSubscription, JsError> {
         trace!("subscribing js function to event stream");
         let sub = subscription_for_func(id, listener).await?.run();
+        let filters = filters.map(Filters::from).unwrap_or_default();
 
         self.events
-            .subscribe(tenant, id, SubscriptionFn::channel(sub))
+            .subscribe(tenant, id, filters, SubscriptionFn::channel(sub))
             .await
             .map_err(JsError::from)
             .map(|s| s.try_into().expect_throw("unable to convert subscription"))
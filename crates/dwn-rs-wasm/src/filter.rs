@@ -3,6 +3,7 @@ use wasm_bindgen::prelude::*;
 
 use dwn_rs_core::{
     filters::{FilterKey, FilterSet, Filters, ValueFilter},
+    indexes::{split_tags, TAG_PREFIX},
     value::MapValue,
 };
 
@@ -32,7 +33,7 @@ impl From<Filter> for Filters {
                     let fs = f
                         .into_iter()
                         .fold(ValueFilter::default(), |mut filters, (k, v)| {
-                            if let Some(tag) = k.strip_prefix("tag.") {
+                            if let Some(tag) = k.strip_prefix(TAG_PREFIX) {
                                 filters.insert(FilterKey::Tag(tag.to_string()), v);
                             } else {
                                 filters.insert(FilterKey::Index(k), v);
@@ -53,18 +54,7 @@ impl From<IndexMap> for (MapValue, MapValue) {
         let m = serde_wasm_bindgen::from_value::<MapValue>(value.into())
             .expect_throw("unable to deserialize indexes");
 
-        m.into_iter().fold(
-            (MapValue::new(), MapValue::new()),
-            |(mut indexes, mut tags), (k, v)| {
-                if let Some(tag) = k.strip_prefix("tag.") {
-                    tags.insert(tag.to_string(), v);
-                } else {
-                    indexes.insert(k, v);
-                }
-
-                (indexes, tags)
-            },
-        )
+        split_tags(m)
     }
 }
 